@@ -68,6 +68,12 @@ export interface AggregateMetadata {
   source: string | null;
   /** Variable name the aggregate result is bound to in the projection. */
   output: string;
+  /**
+   * Who computes the aggregate: the verifier over the disclosed rows
+   * (the default), or the circuit over private rows, in which case the
+   * value is the proof's public `aggregate` input.
+   */
+  evaluation?: 'verifier' | 'in-circuit';
 }
 
 export interface OrderByMetadata {
@@ -401,6 +407,11 @@ export function applyPostProcessing(
   if (aggregates.length > 0) {
     const aggRow: ResultRow = {};
     for (const agg of aggregates) {
+      if (agg.evaluation === 'in-circuit') {
+        throw new Error(
+          `?${agg.output} is computed in-circuit over private rows; read it from the proof's public aggregate input`
+        );
+      }
       aggRow[agg.output] = computeAggregate(agg, disclosedRows);
     }
     working = [aggRow];
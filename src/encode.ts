@@ -64,28 +64,53 @@ export function runJson(fn: string) {
 }
 
 export function stringToFieldFn(str: string) {
-  return `utils::encode_string("${str.replaceAll('"', '\\"')}")`;
+  const escaped = str.replaceAll('\\', '\\\\').replaceAll('"', '\\"');
+  return `utils::encode_string("${escaped}")`;
 }
 
+const XSD = 'http://www.w3.org/2001/XMLSchema#';
+
+// The integer datatypes whose value slot is the integer itself. Kept in
+// step with `special_literal_handling` in `transform/src/expr.rs`, which
+// encodes the constants a circuit compares these literals against.
+export const integerDatatypes = new Set([
+  'integer', 'int', 'long', 'short', 'byte',
+  'nonNegativeInteger', 'positiveInteger', 'negativeInteger', 'nonPositiveInteger',
+  'unsignedInt', 'unsignedLong', 'unsignedShort', 'unsignedByte',
+].map((local) => XSD + local));
+
+const I64_MIN = -(2n ** 63n);
+const I64_MAX = 2n ** 63n - 1n;
+
 export function specialLiteralHandling(term: Literal) {
   // TODO: Add more special handling for different datatypes
   if (term.datatype && term.datatype.value === 'http://www.w3.org/2001/XMLSchema#boolean') {
-    if (term.value.toLowerCase() === 'true' || term.value === '1')
+    if (term.value === 'true' || term.value === '1')
       return '1';
-    if (term.value.toLowerCase() === 'false' || term.value === '0')
+    if (term.value === 'false' || term.value === '0')
       return '0';
+    // Fallback: treat as string if not a valid xsd:boolean lexical form
+    return stringToFieldFn(term.value);
   }
-  if (term.datatype && term.datatype.value === 'http://www.w3.org/2001/XMLSchema#integer') {
-    const parsed = parseInt(term.value, 10);
-    if (!Number.isNaN(parsed)) {
-      return parsed.toString();
+  if (term.datatype && integerDatatypes.has(term.datatype.value)) {
+    // Only the xsd:integer lexical space within i64, as the transform
+    // parses it; `parseInt` would also accept "12abc" or lose precision.
+    if (/^[+-]?[0-9]+$/.test(term.value)) {
+      const parsed = BigInt(term.value);
+      if (parsed >= I64_MIN && parsed <= I64_MAX) {
+        return parsed.toString();
+      }
     }
     // Fallback: treat as string if parse fails (invalid integer literal)
     return stringToFieldFn(term.value);
   }
   if (term.datatype && term.datatype.value === 'http://www.w3.org/2001/XMLSchema#dateTime') {
-    // Convert to epoch milliseconds for numeric comparisons
-    const ms = Date.parse(term.value);
+    // Convert to epoch milliseconds for numeric comparisons. Only a
+    // dateTime with a timezone names an instant; `Date.parse` would read
+    // a floating one in the signer's local time, so it stays a string,
+    // as in the transform.
+    const zoned = /^-?[0-9]{4}-[0-9]{2}-[0-9]{2}T[0-9]{2}:[0-9]{2}:[0-9]{2}(\.[0-9]+)?(Z|[+-][0-9]{2}:[0-9]{2})$/;
+    const ms = zoned.test(term.value) ? Date.parse(term.value) : NaN;
     if (!Number.isNaN(ms)) {
       return ms.toString();
     }
@@ -57,6 +57,7 @@ export default {
     },
     stringHashOutputSize: {},
     stringLenMax: {},
+    merkleDepth: {},
   },
   rename: {
     hash2: "fieldHash",
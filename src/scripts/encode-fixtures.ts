@@ -0,0 +1,103 @@
+/**
+ * Write the term-encoding fixtures `transform`'s tests cross-check
+ * against (`transform/tests/fixtures/encode-ts.json`), so the constants
+ * the Rust transform emits and the encodings `sign.ts` commits to cannot
+ * drift apart.
+ *
+ * Usage:
+ *   npx tsx src/scripts/encode-fixtures.ts                          # Noir expressions only
+ *   npx tsx src/scripts/encode-fixtures.ts --values --string-hash sha256
+ *
+ * Options:
+ *   --values        Also evaluate each term's strings with `nargo execute`
+ *                   in `noir/bin/encode`; `noir/lib/consts` must have been
+ *                   set up with the string hash named by --string-hash.
+ *   --string-hash   String hash `noir/lib/consts` was set up with.
+ */
+import fs from 'fs';
+import path from 'path';
+import { fileURLToPath } from 'url';
+import { DataFactory as DF } from 'n3';
+import type { Term } from '@rdfjs/types';
+import { encodeStrings, getTermEncodingString } from '../encode.js';
+import { stringHashes } from '../config.js';
+
+const __dirname = path.dirname(fileURLToPath(import.meta.url));
+const OUTPUT_PATH = path.resolve(__dirname, '..', '..', 'transform', 'tests', 'fixtures', 'encode-ts.json');
+
+const XSD = 'http://www.w3.org/2001/XMLSchema#';
+const EX = 'http://example.org/';
+
+// One term per encoding branch of `termToFieldFn` / `specialLiteralHandling`,
+// plus the lexical forms the two implementations have disagreed on.
+const terms: Term[] = [
+  DF.namedNode(`${EX}name`),
+  DF.literal('Alice'),
+  DF.literal('Alice', 'en'),
+  DF.literal('say "hi" \\ bye'),
+  DF.literal('true', DF.namedNode(`${XSD}boolean`)),
+  DF.literal('0', DF.namedNode(`${XSD}boolean`)),
+  DF.literal('TRUE', DF.namedNode(`${XSD}boolean`)),
+  DF.literal('42', DF.namedNode(`${XSD}integer`)),
+  DF.literal('-7', DF.namedNode(`${XSD}integer`)),
+  DF.literal('+007', DF.namedNode(`${XSD}integer`)),
+  DF.literal('12abc', DF.namedNode(`${XSD}integer`)),
+  DF.literal('99999999999999999999', DF.namedNode(`${XSD}integer`)),
+  DF.literal('5', DF.namedNode(`${XSD}int`)),
+  DF.literal('255', DF.namedNode(`${XSD}unsignedByte`)),
+  DF.literal('2024-01-15T10:30:00Z', DF.namedNode(`${XSD}dateTime`)),
+  DF.literal('2024-01-15T10:30:00.250+02:00', DF.namedNode(`${XSD}dateTime`)),
+  DF.literal('2024-01-15T10:30:00', DF.namedNode(`${XSD}dateTime`)),
+  DF.literal('3.5', DF.namedNode(`${XSD}decimal`)),
+  DF.literal('x', DF.namedNode(`${EX}custom`)),
+];
+
+// The N-Triples / SPARQL spelling of a term, which the Rust test splices
+// into a query.
+function termSyntax(term: Term): string {
+  if (term.termType === 'NamedNode') return `<${term.value}>`;
+  if (term.termType !== 'Literal') throw new Error(`Unsupported fixture term: ${term.termType}`);
+  const lexical = `"${term.value.replaceAll('\\', '\\\\').replaceAll('"', '\\"')}"`;
+  if (term.language) return `${lexical}@${term.language}`;
+  return `${lexical}^^<${term.datatype.value}>`;
+}
+
+function parseArgs() {
+  const args = process.argv.slice(2);
+  let values = false;
+  let stringHash: string | undefined;
+  for (let i = 0; i < args.length; i++) {
+    if (args[i] === '--values') {
+      values = true;
+    } else if (args[i] === '--string-hash' && args[i + 1]) {
+      stringHash = args[++i];
+    }
+  }
+  if (values && !(stringHashes as readonly (string | undefined)[]).includes(stringHash)) {
+    console.error(`--values needs --string-hash, one of: ${stringHashes.join(', ')}`);
+    process.exit(1);
+  }
+  return { values, stringHash };
+}
+
+const { values, stringHash } = parseArgs();
+
+const fixtures: Record<string, unknown> = {
+  generator: 'src/scripts/encode-fixtures.ts',
+  terms: terms.map((term) => ({
+    term: termSyntax(term),
+    expression: getTermEncodingString(term),
+  })),
+};
+
+if (values) {
+  const strings = [...new Set(terms.flatMap((term) =>
+    term.termType === 'Literal' ? [term.value, term.language, term.datatype.value] : [term.value]))];
+  const encodings = encodeStrings(strings);
+  fixtures.stringHash = stringHash;
+  fixtures.strings = Object.fromEntries(strings.map((s, i) => [s, encodings[i]]));
+}
+
+fs.mkdirSync(path.dirname(OUTPUT_PATH), { recursive: true });
+fs.writeFileSync(OUTPUT_PATH, JSON.stringify(fixtures, null, 2) + '\n');
+console.log(`Wrote ${terms.length} term fixtures to ${path.relative(process.cwd(), OUTPUT_PATH)}`);
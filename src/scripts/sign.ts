@@ -3,14 +3,14 @@ import crypto from 'crypto';
 import fs from "fs";
 import path from "path";
 import N3 from "n3";
-import type { Quad } from "@rdfjs/types";
+import type { Literal, Quad } from "@rdfjs/types";
 import dereferenceToStore from "rdf-dereference-store";
 import { RDFC10 } from "rdfjs-c14n";
 import secp256k1 from 'secp256k1';
 // @ts-expect-error
 import secp256r1 from 'secp256r1';
 import { Command } from 'commander';
-import { getTermEncodingString, runJson } from '../encode.js';
+import { getTermEncodingString, integerDatatypes, runJson, specialLiteralHandling } from '../encode.js';
 import { quadToStringQuad } from 'rdf-string-ttl';
 import { defaultConfig } from '../config.js';
 import { EdDSAPoseidon } from "@zk-kit/eddsa-poseidon";
@@ -56,6 +56,39 @@ export interface PrefixTree3Data {
   highSentinelDirections: boolean[];
 }
 
+/**
+ * Prefix-run commitment data (`spec/prefix-tree-commitment.md` Sec.9).
+ * One leaf per quad, sorted by `hash3_sp_g(s, p, g)` first, so the
+ * quads under a prefix form a contiguous run that completeness
+ * circuits open in full.
+ */
+export interface RunTreeData {
+  /** Quads in input order -- `[encoded_s, encoded_p, encoded_o, encoded_g]`. */
+  quads: string[][];
+  /** Per-quad Merkle paths against `rootRun`. */
+  paths: string[][];
+  /** Per-quad direction bits. */
+  direction: boolean[][];
+  /** Low-sentinel inclusion path (sorted index 0). */
+  lowSentinelPath: string[];
+  lowSentinelDirections: boolean[];
+  /** High-sentinel inclusion path (sorted index N + 1). */
+  highSentinelPath: string[];
+  highSentinelDirections: boolean[];
+}
+
+/**
+ * Value-sorted prefix-run commitment data
+ * (`spec/prefix-tree-commitment.md` Sec.10). Holds the quads of every
+ * prefix whose objects all have an integer, boolean or dateTime value
+ * (one domain per prefix), sorted by prefix and then by that value, so
+ * a prefix's MIN / MAX sits at one end of its run.
+ */
+export interface ExtremumTreeData extends RunTreeData {
+  /** Per-quad object value (`specialLiteralHandling`), as a decimal string. */
+  values: string[];
+}
+
 export interface SignedData {
   triples: string[][];
   paths: string[][];
@@ -79,6 +112,20 @@ export interface SignedData {
    * absent for round-3-only deployments.
    */
   prefix3?: PrefixTree3Data;
+  /**
+   * Prefix-run sorted Merkle root, for completeness circuits. Set to
+   * `"0x0"` when the tree isn't built.
+   */
+  rootRun?: string;
+  /** Prefix-run commitment data; absent with `rootRun`. */
+  run?: RunTreeData;
+  /**
+   * Value-sorted prefix-run root, for proven MIN / MAX circuits. Set to
+   * `"0x0"` when no prefix has only valued objects.
+   */
+  rootExtremum?: string;
+  /** Value-sorted prefix-run commitment data; absent with `rootExtremum`. */
+  extremum?: ExtremumTreeData;
   /**
    * Signature over `root` (round 3). When the signer builds the
    * prefix-3 tree, the round-3 root is signed separately from
@@ -95,6 +142,16 @@ export interface SignedData {
    * verifier code. See `spec/prefix-tree-commitment.md` Sec.8.6.
    */
   signaturePrefix3?: unknown;
+  /**
+   * Signature over `bound_root_message(RUN_ROOT_TAG, root, rootRun)`,
+   * under the same key. Present iff `run` is.
+   */
+  signatureRun?: unknown;
+  /**
+   * Signature over `bound_root_message(EXTREMUM_ROOT_TAG, root,
+   * rootExtremum)`, under the same key. Present iff `extremum` is.
+   */
+  signatureExtremum?: unknown;
   pubKey: unknown;
   nquads: Array<{
     subject: string;
@@ -172,6 +229,81 @@ export async function processQuadsForPrefix3(quads: Quad[]): Promise<{
   };
 }
 
+/**
+ * Process RDF quads for the prefix-run sorted Merkle tree
+ * (`spec/prefix-tree-commitment.md` Sec.9). Quads are not
+ * deduplicated here: canonicalisation already yields a set, and every
+ * quad needs its own leaf for a run to be complete.
+ *
+ * Returns the Noir call that builds the tree, or `null` if the
+ * dataset is empty.
+ */
+export async function processQuadsForRun(quads: Quad[]): Promise<{
+  noirInput: string;
+} | null> {
+  if (quads.length === 0) {
+    return null;
+  }
+  const { triples } = await processQuadsForMerkle(quads);
+  return {
+    noirInput: `utils::run::merkle_run::<consts::MERKLE_DEPTH, ${triples.length}>([${triples.join(',')}])`,
+  };
+}
+
+/**
+ * The value domain of a literal's numeric special encoding --
+ * `integer` (any xsd integer subtype), `boolean` or `dateTime` -- or
+ * `null` when the literal has no i64 value to sort by.
+ */
+function valueDomain(term: Quad['object']): string | null {
+  if (term.termType !== 'Literal') return null;
+  if (!/^-?[0-9]+$/.test(specialLiteralHandling(term))) return null;
+  if (integerDatatypes.has(term.datatype.value)) return 'integer';
+  return term.datatype.value;
+}
+
+/**
+ * Process RDF quads for the value-sorted prefix-run tree
+ * (`spec/prefix-tree-commitment.md` Sec.10). A prefix `(s, p, g)`
+ * enters the tree only when every object under it is a literal with a
+ * numeric special encoding in one value domain -- integers, booleans or
+ * dateTimes. A prefix with any other object (a string, IRI, decimal,
+ * floating dateTime, or a second domain) gets no leaves at all, so a
+ * MIN / MAX over it cannot be proven rather than being proven over
+ * part of its objects.
+ *
+ * Returns the valued quads' values and the Noir call that builds the
+ * tree, or `null` if no prefix qualifies.
+ */
+export async function processQuadsForExtremum(quads: Quad[]): Promise<{
+  values: string[];
+  noirInput: string;
+} | null> {
+  const domains = new Map<string, string | null>();
+  const prefixOf = (quad: Quad) => [quad.subject, quad.predicate, quad.graph].map(t => getTermEncodingString(t)).join('|');
+  for (const quad of quads) {
+    const key = prefixOf(quad);
+    const domain = valueDomain(quad.object);
+    const seen = domains.get(key);
+    domains.set(key, seen === undefined || seen === domain ? domain : null);
+  }
+  const valued: Quad[] = [];
+  const values: string[] = [];
+  for (const quad of quads) {
+    if (domains.get(prefixOf(quad)) === null) continue;
+    valued.push(quad);
+    values.push(specialLiteralHandling(quad.object as Literal));
+  }
+  if (valued.length === 0) {
+    return null;
+  }
+  const { triples } = await processQuadsForMerkle(valued);
+  return {
+    values,
+    noirInput: `utils::extremum::merkle_extremum::<consts::MERKLE_DEPTH, ${triples.length}>([${triples.join(',')}], [${values.join(',')}])`,
+  };
+}
+
 /**
  * Internal -- materialise a fresh signing key for `signatureScheme`.
  * Returns the private key bytes plus the public-key payload that the
@@ -276,6 +408,20 @@ async function signRoot(
   throw new Error(`Unsupported signature type: ${signatureScheme}`);
 }
 
+/**
+ * Internal -- a `0x`-prefixed field element as the 32-byte
+ * little-endian array `Field::to_le_bytes` gives (the `root_u8` shape).
+ */
+function fieldToLeBytes(hex: string): number[] {
+  let value = BigInt(hex);
+  const bytes: number[] = [];
+  for (let i = 0; i < 32; i++) {
+    bytes.push(Number(value & 0xffn));
+    value >>= 8n;
+  }
+  return bytes;
+}
+
 /**
  * Generate cryptographic signature(s) for the dataset's Merkle root(s).
  * Shared by `sign.ts` and `index.ts`.
@@ -322,6 +468,22 @@ export async function generateSignature(jsonRes: any, signatureScheme: string =
     jsonRes.signaturePrefix3 = round6.signature;
   }
 
+  // Prefix-run and value-sorted roots are not signed bare: the signed
+  // message binds each to the dataset root under its tree's tag
+  // (`utils::bound_root_message`), so a derived root cannot be replayed
+  // against another dataset or as the other tree kind.
+  const derived: Array<[string, string, string]> = [
+    ['rootRun', 'utils::run::RUN_ROOT_TAG', 'signatureRun'],
+    ['rootExtremum', 'utils::extremum::EXTREMUM_ROOT_TAG', 'signatureExtremum'],
+  ];
+  for (const [rootKey, tag, signatureKey] of derived) {
+    const rootHex: string | undefined = jsonRes[rootKey];
+    if (!rootHex || rootHex === '0x0' || rootHex === '0x00') continue;
+    const message: string = runJson(`[utils::bound_root_message(${tag}, ${jsonRes.root}, ${rootHex})]`)[0];
+    const signed = await signRoot(signatureScheme, message, fieldToLeBytes(message), keyPair);
+    jsonRes[signatureKey] = signed.signature;
+  }
+
   delete jsonRes.root_u8;
   delete jsonRes.rootPrefix3_u8;
 }
@@ -343,6 +505,8 @@ export async function signRdfData(inputPath: string): Promise<SignedData> {
 
   const { noirInput } = await processQuadsForMerkle(quads);
   const prefix3Spec = await processQuadsForPrefix3(quads);
+  const runSpec = await processQuadsForRun(quads);
+  const extremumSpec = await processQuadsForExtremum(quads);
 
   // Generate the two Merkle trees in **separate** Noir executions.
   // Noir's `print([...])` insists every array element has the same
@@ -389,6 +553,39 @@ export async function signRdfData(inputPath: string): Promise<SignedData> {
     jsonRes.rootPrefix3 = "0x0";
   }
 
+  if (runSpec) {
+    const runRes: any = runJson(`[${runSpec.noirInput}]`)[0];
+    jsonRes.rootRun = runRes.root;
+    jsonRes.run = {
+      quads: runRes.quads,
+      paths: runRes.paths,
+      direction: runRes.direction,
+      lowSentinelPath: runRes.low_sentinel_path,
+      lowSentinelDirections: runRes.low_sentinel_directions,
+      highSentinelPath: runRes.high_sentinel_path,
+      highSentinelDirections: runRes.high_sentinel_directions,
+    };
+  } else {
+    jsonRes.rootRun = "0x0";
+  }
+
+  if (extremumSpec) {
+    const extremumRes: any = runJson(`[${extremumSpec.noirInput}]`)[0];
+    jsonRes.rootExtremum = extremumRes.root;
+    jsonRes.extremum = {
+      quads: extremumRes.quads,
+      values: extremumSpec.values,
+      paths: extremumRes.paths,
+      direction: extremumRes.direction,
+      lowSentinelPath: extremumRes.low_sentinel_path,
+      lowSentinelDirections: extremumRes.low_sentinel_directions,
+      highSentinelPath: extremumRes.high_sentinel_path,
+      highSentinelDirections: extremumRes.high_sentinel_directions,
+    };
+  } else {
+    jsonRes.rootExtremum = "0x0";
+  }
+
   // Add quad string representations
   jsonRes.nquads = quads.map((quad: Quad) => quadToStringQuad(quad));
 
@@ -428,6 +625,8 @@ export async function processRdfDataWithoutSigning(inputPath: string): Promise<S
   jsonRes.signature = [];
   jsonRes.pubKey = {};
   jsonRes.rootPrefix3 = "0x0";
+  jsonRes.rootRun = "0x0";
+  jsonRes.rootExtremum = "0x0";
   delete jsonRes.root_u8;
   delete jsonRes.rootPrefix3_u8;
 
@@ -0,0 +1,169 @@
+//! The `noir/lib/consts` package for a transform's configuration.
+//!
+//! `types`, `utils` and every generated circuit reach the field hash,
+//! string hash, signature scheme and Merkle depth through `dep::consts`.
+//! The TypeScript `setup.ts` fills `consts/src/lib.nr.template` from
+//! `src/mappings.ts`; [`build_consts_package`] renders the same template
+//! from [`crate::TransformOptions`] so a transform's output carries the
+//! setup it was generated for. The Nargo manifest only pulls in the
+//! libraries the chosen primitives use.
+
+use crate::encode::{sha256, StringHash};
+use crate::TransformOptions;
+
+const CONSTS_LIB_TEMPLATE: &str = include_str!("../../noir/lib/consts/src/lib.nr.template");
+
+/// Field hash behind `consts::hash2` / `consts::hash4` -- the
+/// `fieldHash` entry of the TypeScript setup config.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldHash {
+    Pedersen,
+    Poseidon,
+    Poseidon2,
+    Mimc,
+}
+
+impl FieldHash {
+    /// Name as written in the TypeScript config and in metadata.
+    pub fn name(self) -> &'static str {
+        match self {
+            FieldHash::Pedersen => "pedersen",
+            FieldHash::Poseidon => "poseidon",
+            FieldHash::Poseidon2 => "poseidon2",
+            FieldHash::Mimc => "mimc",
+        }
+    }
+
+    fn hash2(self) -> &'static str {
+        match self {
+            FieldHash::Pedersen => "std::hash::pedersen_hash",
+            FieldHash::Poseidon => "dep::hashes::phash2",
+            FieldHash::Poseidon2 => "dep::hashes::p2hash2",
+            FieldHash::Mimc => "dep::mimc::mimc_bn254",
+        }
+    }
+
+    fn hash4(self) -> &'static str {
+        match self {
+            FieldHash::Pedersen => "std::hash::pedersen_hash",
+            FieldHash::Poseidon => "dep::hashes::phash4",
+            FieldHash::Poseidon2 => "dep::hashes::p2hash4",
+            FieldHash::Mimc => "dep::mimc::mimc_bn254",
+        }
+    }
+}
+
+/// Issuer signature scheme: the `noir/lib/signatures/<name>` package
+/// re-exported as `consts::signature`. One variant per `mappings.ts`
+/// `signature` entry; `BabyJubJub` is rejected at transform time, as
+/// `src/config.ts` leaves it out of `signatures` and `sign.ts` has no
+/// signer for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureScheme {
+    Secp256k1,
+    Secp256r1,
+    BabyJubJub,
+    BabyJubJubOpt,
+    Schnorr,
+}
+
+impl SignatureScheme {
+    /// Package directory under `noir/lib/signatures`, also the name in
+    /// the TypeScript config and in metadata.
+    pub fn name(self) -> &'static str {
+        match self {
+            SignatureScheme::Secp256k1 => "secp256k1",
+            SignatureScheme::Secp256r1 => "secp256r1",
+            SignatureScheme::BabyJubJub => "babyjubjub",
+            SignatureScheme::BabyJubJubOpt => "babyjubjubOpt",
+            SignatureScheme::Schnorr => "schnorr",
+        }
+    }
+}
+
+/// `consts::hash_string` for each string hash (`mappings.ts`
+/// `stringHash`).
+fn string_hash_fn(hash: StringHash) -> &'static str {
+    match hash {
+        StringHash::Sha256 => "dep::sha256::digest",
+        StringHash::Blake2s => "std::hash::blake2s",
+        StringHash::Blake3 => "std::hash::blake3",
+        StringHash::Sha384 => "dep::sha512::sha384::digest",
+        StringHash::Sha512 => "dep::sha512::sha512::digest",
+        StringHash::Keccak256 => "dep::hashes::keccak256",
+    }
+}
+
+/// Body of `consts::hash_string_var` (`mappings.ts` `stringHashVar`).
+/// Only sha256 has a variable-length API; the others assert, keeping
+/// round-2 byte binding sound (`spec/encoding.md` sec.6.5).
+fn string_hash_var_body(hash: StringHash) -> String {
+    if hash == StringHash::Sha256 {
+        return "dep::sha256::sha256_var(input, length as u64)".to_string();
+    }
+    format!(
+        "{{ let _ = input; let _ = length; assert(false, \"hash_string_var: {} lacks variable-length API -- round-2 byte-binding is sha256-only\"); [0; {}] }}",
+        hash.name(),
+        hash.output_size()
+    )
+}
+
+/// The rendered `consts` package, to be written over
+/// `noir/lib/consts/Nargo.toml` and `noir/lib/consts/src/lib.nr`.
+#[derive(serde::Serialize, Clone)]
+pub struct ConstsPackage {
+    pub nargo_toml: String,
+    pub lib_nr: String,
+}
+
+impl ConstsPackage {
+    /// `sha256:<hex>` over both files, recorded as
+    /// `metadata.config.constsDigest` so a verifier can check its
+    /// installed `consts` against the one the circuit expects.
+    pub fn digest(&self) -> String {
+        let input = format!("{}\n{}", self.nargo_toml, self.lib_nr);
+        let hex: String = sha256(input.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+        format!("sha256:{}", hex)
+    }
+}
+
+pub(crate) fn build_consts_package(options: &TransformOptions) -> ConstsPackage {
+    let lib_nr = CONSTS_LIB_TEMPLATE
+        .replace("{{merkleDepth}}", &options.merkle_depth.to_string())
+        .replace("{{stringLenMax}}", &options.string_len_max.to_string())
+        .replace("{{hash2}}", options.field_hash.hash2())
+        .replace("{{hash4}}", options.field_hash.hash4())
+        .replace("{{stringHashVar}}", &string_hash_var_body(options.string_hash))
+        .replace("{{stringHashOutputSize}}", &options.string_hash.output_size().to_string())
+        .replace("{{stringHash}}", string_hash_fn(options.string_hash))
+        .replace("{{signature}}", options.signature.name());
+
+    let mut nargo_toml = String::from(
+        "[package]\n\
+         name = \"consts\"\n\
+         type = \"lib\"\n\
+         authors = [\"\"]\n\
+         \n\
+         [dependencies]\n",
+    );
+    nargo_toml.push_str(&format!(
+        "signature = {{ path = \"../signatures/{}\" }}\n",
+        options.signature.name()
+    ));
+    if options.string_hash == StringHash::Sha256 {
+        nargo_toml.push_str("sha256 = { tag = \"v0.2.1\", git = \"https://github.com/noir-lang/sha256\" }\n");
+    }
+    if matches!(options.string_hash, StringHash::Sha384 | StringHash::Sha512) {
+        nargo_toml.push_str("sha512 = { tag = \"0.1.0\", git = \"https://github.com/jeswr/sha512\" }\n");
+    }
+    if matches!(options.field_hash, FieldHash::Poseidon | FieldHash::Poseidon2)
+        || options.string_hash == StringHash::Keccak256
+    {
+        nargo_toml.push_str("hashes = { path = \"../hashes\" }\n");
+    }
+    if options.field_hash == FieldHash::Mimc {
+        nargo_toml.push_str("mimc = { tag = \"v0.1.0\", git = \"https://github.com/noir-lang/mimc\" }\n");
+    }
+
+    ConstsPackage { nargo_toml, lib_nr }
+}
@@ -13,8 +13,17 @@
 
 use std::collections::BTreeMap;
 
-use crate::expr::{filter_to_noir, serialize_term};
-use crate::{Assertion, OptionalBlock, PatternInfo, QueryInfo, Term, TransformOptions};
+use crate::consts::ConstsPackage;
+use crate::encode::fold_static_encodings;
+use crate::expr::{
+    computed_binding_local, computed_binding_to_noir, filter_condition, is_computed_witness,
+    parameter_position, serialize_term,
+};
+use crate::{
+    AggregateKind, Assertion, DisclosureMode, GraphContext, InCircuitAggregate, OptionalBlock, PatternInfo, QueryInfo, Term,
+    TransformOptions,
+};
+use spargebra::term::GroundTerm;
 
 const MAIN_TEMPLATE: &str = include_str!("../template/main-verify.template.nr");
 const MAIN_TEMPLATE_SIMPLE: &str = include_str!("../template/main-simple.template.nr");
@@ -27,7 +36,13 @@ pub(crate) struct EmitResult {
     pub sparql_nr: String,
     pub hidden: Vec<serde_json::Value>,
     pub has_hidden: bool,
+    /// Fresh output witnesses for string-valued functions (REPLACE /
+    /// ENCODE_FOR_URI) and language tags, in `computed[]` order. Non-empty iff the
+    /// circuit takes the private `computed: Computed` input.
+    pub computed: Vec<serde_json::Value>,
     pub needs_xpath: bool,
+    pub needs_ebv: bool,
+    pub needs_lang: bool,
     pub has_not_exists: bool,
     /// Round-5 prefix-3 commitment is in use (any prefix-3 NOT EXISTS
     /// or any prefix-3 OPTIONAL collapse).
@@ -37,6 +52,68 @@ pub(crate) struct EmitResult {
     /// Total number of prefix-3 boundary-case dispatch tags
     /// (`BoundaryCasesPrefix3` length).
     pub num_prefix3_dispatches: usize,
+    /// `checkBinding` takes the public `now: i64` input (`NOW()`).
+    pub has_now: bool,
+    /// `checkBinding` takes the public `params: Params` input (query
+    /// parameters).
+    pub has_params: bool,
+    /// Hidden inputs pinned to a `params` slot because they read a
+    /// query parameter: the hidden entry plus its `position`.
+    pub parameter_values: Vec<serde_json::Value>,
+    /// Some projected variable is committed or pseudonymised, so
+    /// `variables` turns private and `checkBinding` takes the public
+    /// `disclosed: Disclosed` output.
+    pub has_disclosure: bool,
+    /// Length of the private `salts: Salts` input (one per committed
+    /// variable); zero when there is none.
+    pub num_salts: usize,
+    /// `checkBinding` takes the public `verifier_context: Field` input
+    /// (some variable is pseudonymised).
+    pub has_context: bool,
+    /// `checkBinding` takes the public `verifier_key` / `ephemeral_key`
+    /// points and the private `ephemeral_secret` scalar (some variable
+    /// is encrypted).
+    pub has_encryption: bool,
+    /// Number of signed datasets (`TransformOptions::datasets`).
+    pub num_datasets: usize,
+    /// Dataset index each `bgp` slot is checked against.
+    pub bgp_datasets: Vec<usize>,
+    /// `TransformOptions::issuer_registry_depth`.
+    pub issuer_registry_depth: Option<usize>,
+    /// `main` takes the public `context` input.
+    pub binds_context: bool,
+    /// `checkBinding` also takes `context` and the public `scope` /
+    /// `nullifier` inputs.
+    pub has_nullifier: bool,
+    /// Roots are `TimedRoot`s checked against the public `now`
+    /// (`TransformOptions::root_validity`).
+    pub root_validity: bool,
+    /// Each root is also proven absent from the public
+    /// `revocation_root` list (`TransformOptions::revocation_check`).
+    pub revocation_check: bool,
+    /// `main` and `checkBinding` take the public `answer` bit.
+    pub has_answer: bool,
+    /// Row capacity of a completeness run: `main` takes the private
+    /// `run` and the public `run_len` / `boundary_cases_run`, and
+    /// checks the run against the prefix-run root.
+    pub run_capacity: Option<usize>,
+    /// `main` takes the private `extremum` and the public
+    /// `boundary_case_extremum`, and checks the extremum against the
+    /// value-sorted prefix-run root.
+    pub has_extremum: bool,
+    /// The aggregate is computed in-circuit over private rows:
+    /// `variables` is private and `main` takes the public `aggregate`
+    /// (or, with `threshold`, `threshold`) input.
+    pub has_in_circuit_aggregate: bool,
+    /// The public input is a threshold the aggregate reaches rather
+    /// than the aggregate itself.
+    pub aggregate_threshold: bool,
+}
+
+/// The dataset a `GRAPH` term is signed in: its entry in
+/// `TransformOptions::dataset_graphs`, else dataset 0.
+fn graph_dataset(iri: &str, options: &TransformOptions) -> usize {
+    options.dataset_graphs.get(iri).copied().unwrap_or(0)
 }
 
 /// True if any part of the pattern tree carries a non-membership
@@ -51,7 +128,7 @@ pub(crate) struct EmitResult {
 /// top-level constraints matter; the recursive walk is a defence-in-
 /// depth check should those rejections ever loosen without an
 /// emit-side update.
-fn pattern_has_not_exists(pat: &PatternInfo) -> bool {
+pub(crate) fn pattern_has_not_exists(pat: &PatternInfo) -> bool {
     if !pat.not_exists.is_empty()
         || !pat.prefix_not_exists.is_empty()
         || !pat.easy_optionals.is_empty()
@@ -197,6 +274,16 @@ pub(crate) fn generate_circuit_for_optional_combination(
         order_by: base_info.order_by.clone(),
         limit: base_info.limit,
         offset: base_info.offset,
+        uses_now: base_info.uses_now,
+        parameters: base_info.parameters.clone(),
+        disclosure: base_info.disclosure.clone(),
+        dataset: base_info.dataset.clone(),
+        binds_context: base_info.binds_context,
+        nullifier_secret: base_info.nullifier_secret.clone(),
+        answer_bit: base_info.answer_bit,
+        complete_run: base_info.complete_run.clone(),
+        extremum: base_info.extremum.clone(),
+        in_circuit_aggregate: base_info.in_circuit_aggregate.clone(),
     };
 
     generate_sparql_nr_from_query_info(&combo_info, options)
@@ -218,6 +305,66 @@ pub(crate) fn generate_sparql_nr_from_query_info(
                 .into(),
         );
     }
+    // Non-membership is witnessed in dataset 0's sorted trees, so an
+    // absence claim about another issuer's graph would hold vacuously.
+    let absent_graphs = info
+        .pattern
+        .not_exists
+        .iter()
+        .map(|c| &c.absent_terms[3])
+        .chain(info.pattern.prefix_not_exists.iter().map(|c| &c.absent_terms[3]))
+        .chain(info.pattern.easy_optionals.iter().map(|eo| &eo.inner_terms[3]))
+        .chain(info.complete_run.iter().map(|run| &run.terms[3]))
+        .chain(info.extremum.iter().map(|ext| &ext.terms[3]));
+    for term in absent_graphs {
+        if let Term::Static(GroundTerm::NamedNode(nn)) = term
+            && graph_dataset(nn.as_str(), options) != 0
+        {
+            return Err(format!(
+                "NOT EXISTS / MINUS / OPTIONAL over GRAPH <{}> needs non-membership in dataset {}, \
+                 but non-membership is only witnessed against dataset 0",
+                nn.as_str(),
+                graph_dataset(nn.as_str(), options)
+            ));
+        }
+    }
+    let mut bgp_datasets: Vec<usize> = info
+        .pattern
+        .patterns
+        .iter()
+        .map(|p| match &p.graph {
+            GraphContext::NamedNode(iri) => Ok(graph_dataset(iri, options)),
+            // A `FROM`-merged default graph has to stay within one
+            // issuer: the slot is checked against a single root.
+            GraphContext::Merged(iris) => {
+                let first = iris.first().map_or(0, |iri| graph_dataset(iri, options));
+                match iris.iter().find(|iri| graph_dataset(iri, options) != first) {
+                    Some(iri) => Err(format!(
+                        "FROM <{}> is in dataset {}, but the other FROM graphs are in dataset {}; \
+                         a merged default graph must come from one dataset",
+                        iri,
+                        graph_dataset(iri, options),
+                        first
+                    )),
+                    None => Ok(first),
+                }
+            }
+            // The slot's root is fixed at compile time, so a graph
+            // variable can't follow its binding into another dataset.
+            GraphContext::Variable(g) if options.datasets > 1 => Err(format!(
+                "GRAPH ?{} ranges over every named graph, but with {} datasets each triple pattern is \
+                 checked against one root fixed at transform time; name the graph (GRAPH <iri>) so \
+                 TransformOptions::dataset_graphs can place it",
+                g, options.datasets
+            )),
+            _ => Ok(0),
+        })
+        .collect::<Result<_, String>>()?;
+    if let Some(aggregate) = &info.in_circuit_aggregate {
+        // One copy of the body's slots per row, laid out row by row.
+        bgp_datasets = bgp_datasets.repeat(aggregate.rows);
+    }
+
     let mut binding_map: BTreeMap<String, Term> = BTreeMap::new();
     for b in &info.pattern.bindings {
         if !info.variables.contains(&b.variable) && !binding_map.contains_key(&b.variable) {
@@ -229,6 +376,37 @@ pub(crate) fn generate_sparql_nr_from_query_info(
     let mut union_assertions: Vec<Vec<String>> = Vec::new();
     let mut hidden: Vec<serde_json::Value> = Vec::new();
 
+    // Computed BINDs (`BIND(REPLACE(?x, "a", "b") AS ?y)`,
+    // `BIND(IRI(?x) AS ?y)`) become `let bind_y = { checks; witness };`
+    // locals at the top of `checkBinding`, in binding order so a later
+    // BIND can read an earlier one. References to `?y` resolve to `bind_y.hash` via
+    // `serialize_term`. The locals' checks are unconditional, so a
+    // UNION branch cannot carry one (its checks would constrain every
+    // branch); nor can an assertion, which has no variable to name the
+    // local after.
+    let mut computed_lets: Vec<String> = Vec::new();
+    for b in &info.pattern.bindings {
+        if let Term::Computed(expr) = &b.term {
+            let code = computed_binding_to_noir(expr, info, &binding_map, &mut hidden)?;
+            computed_lets.push(format!("let {} = {};", computed_binding_local(&b.variable), code));
+        }
+    }
+    let is_computed = |t: &Term| matches!(t, Term::Computed(_));
+    let branch_has_computed = info.pattern.union_branches.iter().flatten().any(|branch| {
+        branch.bindings.iter().any(|b| is_computed(&b.term))
+            || branch.assertions.iter().any(|a| is_computed(&a.0) || is_computed(&a.1))
+    });
+    if branch_has_computed
+        || info.pattern.assertions.iter().any(|a| is_computed(&a.0) || is_computed(&a.1))
+    {
+        return Err(
+            "computed BIND (REPLACE / ENCODE_FOR_URI / MD5 / SHA* / IRI / BNODE / STRDT / \
+             STRLANG) is only supported in the top-level group pattern -- inside a UNION branch or an EXISTS block its \
+             in-circuit checks cannot be scoped to that branch"
+                .into(),
+        );
+    }
+
     if let Some(branches) = &info.pattern.union_branches {
         for branch in branches {
             let mut branch_bindings = binding_map.clone();
@@ -263,7 +441,7 @@ pub(crate) fn generate_sparql_nr_from_query_info(
             }
 
             for f in &branch.filters {
-                let expr = filter_to_noir(f, info, &branch_bindings, &mut hidden)?;
+                let expr = filter_condition(f, info, &branch_bindings, &mut hidden)?;
                 branch_asserts.push(expr);
             }
 
@@ -273,7 +451,10 @@ pub(crate) fn generate_sparql_nr_from_query_info(
         for b in &info.pattern.bindings {
             let left = Term::Variable(b.variable.clone());
             let l = serialize_term(&left, info, &binding_map);
-            let r = serialize_term(&b.term, info, &binding_map);
+            let r = match &b.term {
+                Term::Computed(_) => format!("{}.hash", computed_binding_local(&b.variable)),
+                term => serialize_term(term, info, &binding_map),
+            };
             // Both sides resolve to the same `bgp[i].terms[j]` slot when the
             // variable is not projected and is bound by the same triple
             // position (e.g. ASK queries with no projected variables). The
@@ -296,7 +477,7 @@ pub(crate) fn generate_sparql_nr_from_query_info(
         }
 
         for f in &info.pattern.filters {
-            let expr = filter_to_noir(f, info, &binding_map, &mut hidden)?;
+            let expr = filter_condition(f, info, &binding_map, &mut hidden)?;
             assertions.push(expr);
         }
     }
@@ -422,9 +603,15 @@ pub(crate) fn generate_sparql_nr_from_query_info(
         // four `bgp[matched_idx].terms[j]` slots to the substituted
         // inner term. For prefix-3 collapses, the inner-only position
         // is unconstrained -- the matched arm doesn't pin it.
+        // Under an answer bit a projected inner-only variable is pinned
+        // too, so the matched arm discloses the object it found.
         let free_position = eo
             .prefix_kind
             .map(|k| k.free_position())
+            .filter(|_| {
+                !info.answer_bit
+                    || !eo.inner_only_var.as_ref().is_some_and(|v| info.variables.contains(v))
+            })
             .unwrap_or(usize::MAX);
         let matched_clauses: Vec<String> = (0..4)
             .filter(|j| *j != free_position)
@@ -488,7 +675,16 @@ pub(crate) fn generate_sparql_nr_from_query_info(
             }
         };
 
-        easy_optional_lines.push(format!("({}) | ({})", matched_arm, unmatched_arm));
+        if info.answer_bit {
+            // The public `answer` picks the arm: a solution exists, or
+            // the brackets show none does.
+            easy_optional_lines.push(format!(
+                "(answer & ({})) | ((!answer) & ({}))",
+                matched_arm, unmatched_arm
+            ));
+        } else {
+            easy_optional_lines.push(format!("({}) | ({})", matched_arm, unmatched_arm));
+        }
     }
 
     let total_prefix3_constraints = prefix3_eo_idx;
@@ -500,11 +696,42 @@ pub(crate) fn generate_sparql_nr_from_query_info(
         "emit / IR disagree on prefix-3 dispatch count"
     );
 
+    // Computed witnesses were recorded alongside the `Field` hidden
+    // inputs; split them out into their own `computed: Computed` input.
+    let (computed, hidden): (Vec<serde_json::Value>, Vec<serde_json::Value>) =
+        hidden.into_iter().partition(is_computed_witness);
+
+    // A hidden value read off a query parameter (its numeric, decimal
+    // or temporal value, ...) depends on nothing the prover holds, so
+    // the verifier computes it too: each is pinned to a public `params`
+    // slot after the parameters' own term hashes.
+    let mut parameter_values: Vec<serde_json::Value> = Vec::new();
+    let mut parameter_lines: Vec<String> = Vec::new();
+    for (k, h) in hidden.iter().enumerate() {
+        let parameter = h
+            .get("input")
+            .filter(|input| input.get("type").and_then(|t| t.as_str()) == Some("variable"))
+            .and_then(|input| input.get("value"))
+            .and_then(|v| v.as_str())
+            .filter(|name| parameter_position(info, name).is_some());
+        if let Some(name) = parameter {
+            let position = info.parameters.len() + parameter_values.len();
+            parameter_lines.push(format!("hidden[{}] == params[{}]", k, position));
+            let mut entry = h.clone();
+            entry["parameter"] = name.into();
+            entry["position"] = position.into();
+            parameter_values.push(entry);
+        }
+    }
+
     let mut sparql_nr = String::new();
     sparql_nr.push_str("// Generated by sparql_noir transform\n");
     sparql_nr.push_str("use dep::consts;\n");
     if options.skip_signing {
         sparql_nr.push_str("use super::Triple;\n");
+        if !computed.is_empty() {
+            sparql_nr.push_str("use super::TermWitness;\n");
+        }
     } else {
         sparql_nr.push_str("use dep::utils;\n");
         sparql_nr.push_str("use dep::types::Triple;\n");
@@ -514,19 +741,47 @@ pub(crate) fn generate_sparql_nr_from_query_info(
         if has_prefix3 {
             sparql_nr.push_str("use dep::types::PrefixTriple3;\n");
         }
+        if info.complete_run.is_some() {
+            sparql_nr.push_str("use dep::types::Run;\n");
+        }
+        if info.extremum.is_some() {
+            sparql_nr.push_str("use dep::types::Extremum;\n");
+        }
+        if !computed.is_empty() {
+            sparql_nr.push_str("use dep::types::TermWitness;\n");
+        }
+    }
+    if info.disclosure.iter().any(|(_, mode)| *mode == DisclosureMode::Encrypt) {
+        sparql_nr.push_str("use std::embedded_curve_ops::{EmbeddedCurvePoint, EmbeddedCurveScalar, fixed_base_scalar_mul, multi_scalar_mul};\n");
     }
 
+    // Three-valued filters (`filter_condition`) call `ebv::EBVResult`
+    // whether or not they read an EBV hidden input.
     let needs_ebv = hidden.iter().any(|h| {
         h.get("computedType")
             .and_then(|v| v.as_str())
             .map(|t| t == "ebv_value" || t == "ebv_datatype")
             .unwrap_or(false)
-    });
+    }) || assertions.iter().any(|a| a.contains("ebv::"))
+        || union_assertions
+            .iter()
+            .any(|branch| branch.iter().any(|a| a.contains("ebv::")));
     if needs_ebv {
         sparql_nr.push_str("use dep::ebv;\n");
     }
 
+    // LANG / LANGMATCHES read a language-tag witness through `dep::lang`,
+    // which (unlike `utils`) is available in skip-signing mode too.
+    let needs_lang = assertions.iter().any(|a| a.contains("lang::"))
+        || union_assertions
+            .iter()
+            .any(|branch| branch.iter().any(|a| a.contains("lang::")));
+    if needs_lang {
+        sparql_nr.push_str("use dep::lang;\n");
+    }
+
     let needs_xpath = assertions.iter().any(|a| a.contains("xpath::"))
+        || computed_lets.iter().any(|l| l.contains("xpath::"))
         || union_assertions
             .iter()
             .any(|branch| branch.iter().any(|a| a.contains("xpath::")));
@@ -559,9 +814,11 @@ pub(crate) fn generate_sparql_nr_from_query_info(
             || union_assertions
                 .iter()
                 .any(|branch| branch.iter().any(|a| a.contains("utils::")))
+            || !computed_lets.is_empty()
     ) {
         return Err(
-            "round-2 string operators (STRLEN / STRSTARTS / CONTAINS) cannot run in \
+            "round-2 string operators (STRLEN / STRSTARTS / CONTAINS / REPLACE / \
+             ENCODE_FOR_URI / MD5 / SHA* / IRI / BNODE / STRDT / STRLANG) cannot run in \
              skip-signing mode -- they call into `noir/lib/utils` which is excluded from \
              the simplified skip-signing template. Re-run without --skip-signing or land \
              a string-op skip-signing variant of `utils` (round-3 follow-up)."
@@ -570,25 +827,194 @@ pub(crate) fn generate_sparql_nr_from_query_info(
     }
     let _ = body_needs_utils; // already imported in the signed branch
 
+    // An in-circuit aggregate checks the query body once per row in
+    // `checkRow`, over per-row `RowBGP` / `Row` / `RowHidden` /
+    // `RowComputed` inputs; `checkBinding` wraps it over `rows` of each.
+    let aggregate = info.in_circuit_aggregate.as_ref();
+    let (bgp_type, variables_type, hidden_type, computed_type) = match aggregate {
+        Some(_) => ("RowBGP", "Row", "RowHidden", "RowComputed"),
+        None => ("BGP", "Variables", "Hidden", "Computed"),
+    };
+
     sparql_nr.push_str("\n");
     sparql_nr.push_str(&format!(
-        "pub(crate) type BGP = [Triple; {}];\n",
+        "pub(crate) type {} = [Triple; {}];\n",
+        bgp_type,
         info.pattern.patterns.len()
     ));
+    if let Some(aggregate) = aggregate {
+        sparql_nr.push_str(&format!(
+            "pub(crate) type BGP = [Triple; {}];\n",
+            info.pattern.patterns.len() * aggregate.rows
+        ));
+    }
+    if options.datasets > 1 && !options.skip_signing {
+        // Multiple issuers: `main` checks `bgp[i]` against
+        // `roots[BGP_DATASETS[i]]` and each root's signature against
+        // `public_key[ROOT_KEYS[i]]`. Prefix-3 and prefix-run roots,
+        // and value-sorted roots, when present, follow the dataset roots
+        // and belong to dataset 0.
+        let mut root_keys: Vec<usize> = (0..options.datasets).collect();
+        if has_prefix3 {
+            root_keys.push(0);
+        }
+        if info.complete_run.is_some() {
+            root_keys.push(0);
+        }
+        if info.extremum.is_some() {
+            root_keys.push(0);
+        }
+        let join = |v: &[usize]| v.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", ");
+        sparql_nr.push_str(&format!(
+            "pub(crate) global BGP_DATASETS: [u32; {}] = [{}];\n",
+            bgp_datasets.len(),
+            join(&bgp_datasets)
+        ));
+        sparql_nr.push_str(&format!(
+            "pub(crate) global ROOT_KEYS: [u32; {}] = [{}];\n",
+            root_keys.len(),
+            join(&root_keys)
+        ));
+    }
+    if (info.complete_run.is_some() || info.extremum.is_some()) && !options.skip_signing {
+        // Prefix-run and value-sorted roots are signed bound to the
+        // dataset root under their tree's tag
+        // (`utils::bound_root_message`); a `0` tag marks a root signed
+        // as-is. `main` checks `bind_root(roots[i], ROOT_TAGS[i], ..)`.
+        let mut root_tags = vec!["0"; options.datasets + usize::from(has_prefix3)];
+        if info.complete_run.is_some() {
+            root_tags.push("dep::utils::run::RUN_ROOT_TAG");
+        }
+        if info.extremum.is_some() {
+            root_tags.push("dep::utils::extremum::EXTREMUM_ROOT_TAG");
+        }
+        sparql_nr.push_str(&format!(
+            "pub(crate) global ROOT_TAGS: [Field; {}] = [{}];\n",
+            root_tags.len(),
+            root_tags.join(", ")
+        ));
+    }
 
-    sparql_nr.push_str("pub(crate) struct Variables {\n");
+    sparql_nr.push_str(&format!("pub(crate) struct {} {{\n", variables_type));
     for v in &info.variables {
-        sparql_nr.push_str(&format!("  pub(crate) {}: Field,\n", v));
+        match &info.complete_run {
+            // One slot per run row, zero past `run_len`.
+            Some(run) if run.variable == *v => {
+                sparql_nr.push_str(&format!("  pub(crate) {}: [Field; {}],\n", v, run.capacity));
+            }
+            _ => sparql_nr.push_str(&format!("  pub(crate) {}: Field,\n", v)),
+        }
     }
     sparql_nr.push_str("}\n\n");
+    let is_sum = aggregate.is_some_and(|a| matches!(a.kind, AggregateKind::Sum { .. }));
+    if let Some(aggregate) = aggregate {
+        // The rows, how many of them are in use and, for a SUM, the
+        // value of each row's source literal.
+        if is_sum {
+            sparql_nr.push_str(
+                "pub(crate) struct RowValue {\n  pub(crate) value: i64,\n  pub(crate) lexical: Field,\n  pub(crate) language: Field,\n}\n\n",
+            );
+        }
+        sparql_nr.push_str("pub(crate) struct Variables {\n");
+        sparql_nr.push_str(&format!("  pub(crate) rows: [Row; {}],\n", aggregate.rows));
+        sparql_nr.push_str("  pub(crate) row_count: u32,\n");
+        if is_sum {
+            sparql_nr.push_str(&format!("  pub(crate) values: [RowValue; {}],\n", aggregate.rows));
+        }
+        sparql_nr.push_str("}\n\n");
+    }
+
+    // Disclosure modes (`TransformOptions::disclosure`). `Variables`
+    // then holds the private bindings and `Disclosed` mirrors it field
+    // for field with what is published: the term hash itself, its
+    // salted commitment, its pseudonym under `verifier_context`, or
+    // its encryption to `verifier_key`. Salt and key-stream slots
+    // follow the whole query's committed / encrypted variables, so a
+    // variant that drops one leaves its slot unused.
+    let has_disclosure = !info.disclosure.is_empty();
+    let committed: Vec<&str> = info
+        .disclosure
+        .iter()
+        .filter(|(_, mode)| *mode == DisclosureMode::Commit)
+        .map(|(v, _)| v.as_str())
+        .collect();
+    let has_context = info.disclosure.iter().any(|(_, mode)| *mode == DisclosureMode::Pseudonym);
+    let encrypted: Vec<&str> = info
+        .disclosure
+        .iter()
+        .filter(|(_, mode)| *mode == DisclosureMode::Encrypt)
+        .map(|(v, _)| v.as_str())
+        .collect();
+    let has_encryption = !encrypted.is_empty();
+    let mut disclosure_lines: Vec<String> = Vec::new();
+    if has_encryption {
+        // ECDH on the embedded curve: the prover's ephemeral key pair
+        // must be well formed and non-trivial, or the key stream would
+        // be computable without the verifier's secret key.
+        disclosure_lines.push("!verifier_key.is_infinite".to_string());
+        disclosure_lines.push("!ephemeral_key.is_infinite".to_string());
+        disclosure_lines.push("ephemeral_key == fixed_base_scalar_mul(ephemeral_secret)".to_string());
+    }
+    if has_disclosure {
+        sparql_nr.push_str("pub(crate) struct Disclosed {\n");
+        for v in &info.variables {
+            sparql_nr.push_str(&format!("  pub(crate) {}: Field,\n", v));
+            let mode = info.disclosure.iter().find(|(d, _)| d == v).map(|(_, mode)| *mode);
+            disclosure_lines.push(match mode {
+                Some(DisclosureMode::Commit) => {
+                    let salt = committed.iter().position(|c| c == v).expect("committed variable");
+                    format!("disclosed.{0} == consts::hash2([variables.{0}, salts[{1}]])", v, salt)
+                }
+                Some(DisclosureMode::Pseudonym) => {
+                    format!("disclosed.{0} == consts::hash2([variables.{0}, verifier_context])", v)
+                }
+                Some(DisclosureMode::Encrypt) => {
+                    let slot = encrypted.iter().position(|e| e == v).expect("encrypted variable");
+                    format!(
+                        "disclosed.{0} == variables.{0} + consts::hash2([shared_secret.x, {1}])",
+                        v, slot
+                    )
+                }
+                _ => format!("disclosed.{0} == variables.{0}", v),
+            });
+        }
+        sparql_nr.push_str("}\n\n");
+        if !committed.is_empty() {
+            sparql_nr.push_str(&format!("pub(crate) type Salts = [Field; {}];\n", committed.len()));
+        }
+    }
 
     let has_hidden = !hidden.is_empty();
     if has_hidden {
         sparql_nr.push_str(&format!(
-            "pub(crate) type Hidden = [Field; {}];\n",
+            "pub(crate) type {} = [Field; {}];\n",
+            hidden_type,
             hidden.len()
         ));
     }
+    let has_params = !info.parameters.is_empty();
+    if has_params {
+        sparql_nr.push_str(&format!(
+            "pub(crate) type Params = [Field; {}];\n",
+            info.parameters.len() + parameter_values.len()
+        ));
+    }
+    let has_computed = !computed.is_empty();
+    if has_computed {
+        sparql_nr.push_str(&format!(
+            "pub(crate) type {} = [TermWitness; {}];\n",
+            computed_type,
+            computed.len()
+        ));
+    }
+    if let Some(aggregate) = aggregate {
+        if has_hidden {
+            sparql_nr.push_str(&format!("pub(crate) type Hidden = [RowHidden; {}];\n", aggregate.rows));
+        }
+        if has_computed {
+            sparql_nr.push_str(&format!("pub(crate) type Computed = [RowComputed; {}];\n", aggregate.rows));
+        }
+    }
     if has_not_exists {
         // The public `BoundaryCases` array encodes which
         // `verify_non_membership_*_no_inclusion` primitive fires for
@@ -619,9 +1045,35 @@ pub(crate) fn generate_sparql_nr_from_query_info(
         ));
     }
 
-    let mut params = String::from("bgp: BGP, variables: Variables");
+    if let Some(run) = &info.complete_run {
+        // The rows and bracket leaves of a completeness run
+        // (`spec/prefix-tree-commitment.md` Sec.9).
+        sparql_nr.push_str(&format!("pub(crate) type CompleteRun = Run<{}>;\n", run.capacity));
+    }
+
+    let mut params = format!("bgp: {}, variables: {}", bgp_type, variables_type);
+    if is_sum {
+        params.push_str(", value: RowValue");
+    }
+    if has_disclosure {
+        params.push_str(", disclosed: Disclosed");
+        if !committed.is_empty() {
+            params.push_str(", salts: Salts");
+        }
+        if has_context {
+            params.push_str(", verifier_context: Field");
+        }
+        if has_encryption {
+            params.push_str(
+                ", verifier_key: EmbeddedCurvePoint, ephemeral_key: EmbeddedCurvePoint, ephemeral_secret: EmbeddedCurveScalar",
+            );
+        }
+    }
     if has_hidden {
-        params.push_str(", hidden: Hidden");
+        params.push_str(&format!(", hidden: {}", hidden_type));
+    }
+    if has_computed {
+        params.push_str(&format!(", computed: {}", computed_type));
     }
     if has_not_exists {
         params.push_str(", low_sentinel: SentinelLeaf, high_sentinel: SentinelLeaf, boundary_cases: BoundaryCases");
@@ -629,11 +1081,37 @@ pub(crate) fn generate_sparql_nr_from_query_info(
     if has_prefix3 {
         params.push_str(", bgp_prefix3: BgpPrefix3, low_sentinel_3: SentinelLeaf, high_sentinel_3: SentinelLeaf, boundary_cases_prefix3: BoundaryCasesPrefix3");
     }
-    sparql_nr.push_str(&format!(
-        "pub(crate) fn checkBinding({}) {{\n",
-        params
-    ));
+    if info.complete_run.is_some() {
+        params.push_str(", run: CompleteRun, run_len: u32, boundary_cases_run: [Field; 2]");
+    }
+    if info.extremum.is_some() {
+        params.push_str(", extremum: Extremum, boundary_case_extremum: Field");
+    }
+    if info.answer_bit {
+        params.push_str(", answer: bool");
+    }
+    if info.uses_now {
+        params.push_str(", now: i64");
+    }
+    if has_params {
+        params.push_str(", params: Params");
+    }
+    // Last, matching `main.nr`'s `{{x1}}` argument order.
+    if info.nullifier_secret.is_some() {
+        params.push_str(", context: Field, scope: Field, nullifier: Field");
+    }
+    if aggregate.is_some() {
+        sparql_nr.push_str(&format!("pub(crate) fn checkRow({}) -> Field {{\n", params));
+    } else {
+        sparql_nr.push_str(&format!(
+            "pub(crate) fn checkBinding({}) {{\n",
+            params
+        ));
+    }
 
+    for line in &computed_lets {
+        sparql_nr.push_str(&format!("  {}\n", line));
+    }
     if !union_assertions.is_empty() {
         for (idx, branch) in union_assertions.iter().enumerate() {
             let expr = if branch.is_empty() {
@@ -657,6 +1135,9 @@ pub(crate) fn generate_sparql_nr_from_query_info(
             sparql_nr.push_str(&format!("  assert({});\n", a));
         }
     }
+    for line in &parameter_lines {
+        sparql_nr.push_str(&format!("  assert({});\n", line));
+    }
     for call in &not_exists_calls {
         sparql_nr.push_str(&format!("  {};\n", call));
     }
@@ -666,37 +1147,317 @@ pub(crate) fn generate_sparql_nr_from_query_info(
     for line in &easy_optional_lines {
         sparql_nr.push_str(&format!("  assert({});\n", line));
     }
-    sparql_nr.push_str("}\n");
+    if let Some(run) = &info.complete_run {
+        // The rows are every quad under the queried prefix; each one
+        // discloses its free position, and the slots past `run_len`
+        // are zero.
+        let [a, b, c] = run.prefix_kind.fixed_positions();
+        sparql_nr.push_str(&format!(
+            "  utils::run::verify_run_bounds(run, run_len, boundary_cases_run, utils::prefix3::hash3_sp_g({}, {}, {}));\n",
+            serialize_term(&run.terms[a], info, &binding_map),
+            serialize_term(&run.terms[b], info, &binding_map),
+            serialize_term(&run.terms[c], info, &binding_map),
+        ));
+        sparql_nr.push_str(&format!(
+            "  for i in 0..{cap} {{\n\
+             \x20   if i < run_len {{\n\
+             \x20     assert(variables.{v}[i] == run.rows[i].terms[{free}]);\n\
+             \x20   }} else {{\n\
+             \x20     assert(variables.{v}[i] == 0);\n\
+             \x20   }}\n\
+             \x20 }}\n",
+            cap = run.capacity,
+            v = run.variable,
+            free = run.prefix_kind.free_position(),
+        ));
+    }
+    if let Some(ext) = &info.extremum {
+        // The row is the first (MIN) or last (MAX) quad under the
+        // queried prefix in value order, and discloses its free
+        // position.
+        let [a, b, c] = ext.prefix_kind.fixed_positions();
+        sparql_nr.push_str(&format!(
+            "  utils::extremum::verify_extremum_bounds(extremum, {}, boundary_case_extremum, utils::prefix3::hash3_sp_g({}, {}, {}));\n",
+            ext.maximum,
+            serialize_term(&ext.terms[a], info, &binding_map),
+            serialize_term(&ext.terms[b], info, &binding_map),
+            serialize_term(&ext.terms[c], info, &binding_map),
+        ));
+        sparql_nr.push_str(&format!(
+            "  assert(variables.{} == extremum.row.terms[{}]);\n",
+            ext.variable,
+            ext.prefix_kind.free_position(),
+        ));
+    }
+    if has_encryption {
+        sparql_nr.push_str(
+            "  let shared_secret = multi_scalar_mul([verifier_key], [ephemeral_secret]);\n",
+        );
+    }
+    for line in &disclosure_lines {
+        sparql_nr.push_str(&format!("  assert({});\n", line));
+    }
+    if let Some(secret) = &info.nullifier_secret {
+        // One nullifier per holder, context and scope: the verifier
+        // rejects one it has already seen.
+        sparql_nr.push_str(&format!(
+            "  assert(nullifier == consts::hash2([consts::hash2([{}, context]), scope]));\n",
+            serialize_term(&Term::Variable(secret.clone()), info, &binding_map)
+        ));
+    }
+    if let Some(aggregate) = aggregate {
+        if let (true, Some(source)) = (is_sum, &aggregate.source) {
+            // The row's value opens its source literal, an xsd:integer.
+            sparql_nr.push_str(&format!(
+                "  assert(utils::extremum::valued_literal(value.lexical, value.value, value.language, {}) == {}, \"aggregate: value does not open the row's source literal\");\n",
+                "consts::encode_string(\"http://www.w3.org/2001/XMLSchema#integer\")",
+                serialize_term(&Term::Variable(source.clone()), info, &binding_map)
+            ));
+        }
+        // The row's key: distinct keys imply distinct solutions.
+        let key: Vec<String> = aggregate
+            .key
+            .iter()
+            .map(|v| serialize_term(&Term::Variable(v.clone()), info, &binding_map))
+            .collect();
+        if key.is_empty() {
+            sparql_nr.push_str("  0\n");
+        } else {
+            sparql_nr.push_str(&format!("  utils::aggregate::solution_key([{}])\n", key.join(", ")));
+        }
+        sparql_nr.push_str("}\n\n");
+        sparql_nr.push_str(&in_circuit_aggregate_wrapper(
+            aggregate,
+            info.pattern.patterns.len(),
+            has_hidden,
+            has_computed,
+            info.uses_now,
+            has_params,
+        ));
+    } else {
+        sparql_nr.push_str("}\n");
+    }
+
+    // Constant string encodings -- and, under Poseidon, the term hashes
+    // over them -- are evaluated here instead of in-circuit when the
+    // caller names the string hash `consts` was set up with.
+    let sparql_nr = match options.static_string_hash {
+        Some(hash) => fold_static_encodings(&sparql_nr, hash, options.field_hash),
+        None => sparql_nr,
+    };
 
     Ok(EmitResult {
         sparql_nr,
         hidden,
         has_hidden,
+        computed,
         needs_xpath,
+        needs_ebv,
+        needs_lang,
         has_not_exists,
         has_prefix3,
         bgp_prefix3_len,
         num_prefix3_dispatches: total_prefix3_constraints,
+        has_now: info.uses_now,
+        has_params,
+        parameter_values,
+        has_disclosure,
+        num_salts: committed.len(),
+        has_context,
+        has_encryption,
+        num_datasets: options.datasets,
+        bgp_datasets,
+        issuer_registry_depth: options.issuer_registry_depth,
+        binds_context: info.binds_context,
+        has_nullifier: info.nullifier_secret.is_some(),
+        root_validity: options.root_validity,
+        revocation_check: options.revocation_check,
+        has_answer: info.answer_bit,
+        run_capacity: info.complete_run.as_ref().map(|run| run.capacity),
+        has_extremum: info.extremum.is_some(),
+        has_in_circuit_aggregate: aggregate.is_some(),
+        aggregate_threshold: aggregate.is_some_and(|a| a.threshold),
     })
 }
 
-/// Substitute the `{{h0}}` / `{{h1}}` / `{{h2}}` (Hidden inputs),
-/// `{{n0}}` / `{{n1}}` / `{{n2}}` / `{{n3}}` / `{{n4}}` (NOT EXISTS /
-/// round-3 sentinel scaffolding), and `{{p0}}` / `{{p1}}` / `{{p2}}` /
-/// `{{p3}}` / `{{p4}}` (round-5 prefix-3 scaffolding) placeholders in
-/// the embedded `main.nr` template. The signed and skip-signing
-/// variants share placeholder syntax; `{{n*}}` / `{{p*}}` placeholders
-/// only appear in the signed template (skip-signing rejects
-/// non-membership upstream).
+/// `checkBinding` for an in-circuit aggregate: run `checkRow` on each
+/// of the first `row_count` rows, require their keys to strictly
+/// increase (so no solution is counted twice) and compare the COUNT /
+/// SUM with the public `aggregate` or `threshold`. See
+/// `spec/disclosure.md` Sec.8.
+fn in_circuit_aggregate_wrapper(
+    aggregate: &InCircuitAggregate,
+    row_len: usize,
+    has_hidden: bool,
+    has_computed: bool,
+    has_now: bool,
+    has_params: bool,
+) -> String {
+    let is_sum = matches!(aggregate.kind, AggregateKind::Sum { .. });
+    let public = if aggregate.threshold { "threshold" } else { "aggregate" };
+    let mut params = String::from("bgp: BGP, variables: Variables");
+    let mut args = String::from("row_bgp, variables.rows[r]");
+    if is_sum {
+        args.push_str(", variables.values[r]");
+    }
+    if has_hidden {
+        params.push_str(", hidden: Hidden");
+        args.push_str(", hidden[r]");
+    }
+    if has_computed {
+        params.push_str(", computed: Computed");
+        args.push_str(", computed[r]");
+    }
+    params.push_str(&format!(", {}: i64", public));
+    if has_now {
+        params.push_str(", now: i64");
+        args.push_str(", now");
+    }
+    if has_params {
+        params.push_str(", params: Params");
+        args.push_str(", params");
+    }
+
+    let mut out = format!("pub(crate) fn checkBinding({}) {{\n", params);
+    out.push_str(&format!(
+        "  assert(variables.row_count <= {}, \"aggregate: row_count exceeds the row bound\");\n",
+        aggregate.rows
+    ));
+    out.push_str(&format!("  let mut keys: [Field; {}] = [0; {}];\n", aggregate.rows, aggregate.rows));
+    if is_sum {
+        out.push_str("  let mut total: i64 = 0;\n");
+    }
+    out.push_str(&format!(
+        "  for r in 0..{rows} {{\n\
+         \x20   if r < variables.row_count {{\n\
+         \x20     let mut row_bgp: RowBGP = [bgp[0]; {len}];\n\
+         \x20     for j in 0..{len} {{\n\
+         \x20       row_bgp[j] = bgp[r * {len} + j];\n\
+         \x20     }}\n\
+         \x20     keys[r] = checkRow({args});\n",
+        rows = aggregate.rows,
+        len = row_len,
+        args = args,
+    ));
+    if is_sum {
+        // Non-negative values keep a partial sum a lower bound.
+        out.push_str(
+            "      assert(variables.values[r].value >= 0, \"aggregate: SUM needs non-negative values\");\n\
+             \x20     total += variables.values[r].value;\n",
+        );
+    }
+    out.push_str("    }\n  }\n");
+    out.push_str("  utils::aggregate::assert_strictly_ascending(keys, variables.row_count);\n");
+    let value = if is_sum { "total" } else { "variables.row_count as i64" };
+    if aggregate.threshold {
+        out.push_str(&format!("  assert({} >= threshold);\n", value));
+    } else {
+        out.push_str(&format!("  assert({} == aggregate);\n", value));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// An optional group of `main` inputs -- the public answer bit, a
+/// completeness run, a proven extremum or an in-circuit aggregate --
+/// rendered into the `{{f0}}` – `{{f4}}` slots of the signed template.
+#[derive(Default)]
+struct MainSection {
+    /// `use` lines ahead of the `sparql` import.
+    imports: String,
+    /// Types imported from `sparql`.
+    types: String,
+    /// `main` parameters.
+    params: String,
+    /// Checks `main` runs before `checkBinding`.
+    checks: String,
+    /// Arguments passed on to `checkBinding`.
+    args: String,
+}
+
+impl MainSection {
+    /// Whether the query has a solution (metadata `answer`).
+    fn answer() -> Self {
+        MainSection {
+            params: ",\n    answer: pub bool".into(),
+            args: ", answer".into(),
+            ..MainSection::default()
+        }
+    }
+
+    /// Completeness run against the prefix-run root `roots[root]`;
+    /// `checkBinding` checks the run's ordering against the queried
+    /// prefix. See `spec/prefix-tree-commitment.md` Sec.9.
+    fn complete_run(root: usize) -> Self {
+        MainSection {
+            imports: "use dep::utils::run::verify_run_inclusion;\n\n".into(),
+            types: ", CompleteRun".into(),
+            params: ",\n    run: CompleteRun,\n    run_len: pub u32,\n    boundary_cases_run: pub [Field; 2]".into(),
+            checks: format!(
+                "    // Completeness run: rows and bracket leaves are leaves of\n\
+                 \x20   // the prefix-run tree.\n\
+                 \x20   verify_run_inclusion(run, run_len, boundary_cases_run, roots[{}].value);\n\n",
+                root
+            ),
+            args: ", run, run_len, boundary_cases_run".into(),
+        }
+    }
+
+    /// Proven extremum against the value-sorted prefix-run root
+    /// `roots[root]`; `checkBinding` checks the row is at the end of
+    /// its prefix's run. See `spec/prefix-tree-commitment.md` Sec.10.
+    fn extremum(root: usize) -> Self {
+        MainSection {
+            imports: "use dep::types::Extremum;\nuse dep::utils::extremum::verify_extremum_inclusion;\n\n".into(),
+            params: ",\n    extremum: Extremum,\n    boundary_case_extremum: pub Field".into(),
+            checks: format!(
+                "    // Proven extremum: the row and its neighbour are leaves of\n\
+                 \x20   // the value-sorted prefix-run tree.\n\
+                 \x20   verify_extremum_inclusion(extremum, boundary_case_extremum, roots[{}].value);\n\n",
+                root
+            ),
+            args: ", extremum, boundary_case_extremum".into(),
+            ..MainSection::default()
+        }
+    }
+
+    /// In-circuit aggregate: the rows stay private and only the
+    /// aggregate, or the threshold it reaches, is public. See
+    /// `spec/disclosure.md` Sec.8.
+    fn aggregate(threshold: bool) -> Self {
+        let public = if threshold { "threshold" } else { "aggregate" };
+        MainSection {
+            params: format!(",\n    {}: pub i64", public),
+            args: format!(", {}", public),
+            ..MainSection::default()
+        }
+    }
+
+    /// Fill the `{{f0}}` – `{{f4}}` slots with `sections` in order.
+    fn fill(main_nr: String, sections: &[MainSection]) -> String {
+        let join = |field: fn(&MainSection) -> &str| sections.iter().map(field).collect::<String>();
+        main_nr
+            .replace("{{f0}}", &join(|s| &s.imports))
+            .replace("{{f1}}", &join(|s| &s.types))
+            .replace("{{f2}}", &join(|s| &s.params))
+            .replace("{{f3}}", &join(|s| &s.checks))
+            .replace("{{f4}}", &join(|s| &s.args))
+    }
+}
+
+/// Fill the embedded `main.nr` template. Each `{{xN}}` placeholder
+/// family is one optional group of inputs; the signed and skip-signing
+/// variants share the syntax, but only the signed template has the
+/// non-membership, prefix-3 and [`MainSection`] slots.
 pub(crate) fn fill_main_nr_template(
     skip_signing: bool,
-    has_hidden: bool,
-    has_not_exists: bool,
+    emitted: &EmitResult,
     num_not_exists: usize,
-    has_prefix3: bool,
-    bgp_prefix3_len: usize,
-    num_prefix3_dispatches: usize,
 ) -> String {
+    let has_not_exists = emitted.has_not_exists;
+    let has_prefix3 = emitted.has_prefix3;
+    let bgp_prefix3_len = emitted.bgp_prefix3_len;
+    let num_prefix3_dispatches = emitted.num_prefix3_dispatches;
     // Consistency check: `has_not_exists` is the boolean view of
     // `num_not_exists > 0`. A mismatch means a caller has thrown the
     // two sources out of sync upstream — fail loudly rather than emit
@@ -723,7 +1484,28 @@ pub(crate) fn fill_main_nr_template(
         MAIN_TEMPLATE
     };
     let mut main_nr = template.to_string();
-    if has_hidden {
+    let prefix3_root = emitted.num_datasets;
+    // Derived-tree roots follow the dataset and prefix-3 roots and
+    // belong to dataset 0.
+    let first_derived_root = prefix3_root + usize::from(has_prefix3);
+    let mut num_roots = first_derived_root;
+    let mut sections = Vec::new();
+    if emitted.run_capacity.is_some() {
+        sections.push(MainSection::complete_run(num_roots));
+        num_roots += 1;
+    }
+    if emitted.has_extremum {
+        sections.push(MainSection::extremum(num_roots));
+        num_roots += 1;
+    }
+    if emitted.has_in_circuit_aggregate {
+        sections.push(MainSection::aggregate(emitted.aggregate_threshold));
+    }
+    if emitted.has_answer {
+        sections.push(MainSection::answer());
+    }
+    let root_type = if emitted.root_validity { "TimedRoot" } else { "Root" };
+    if emitted.has_hidden {
         main_nr = main_nr
             .replace("{{h0}}", ", Hidden")
             .replace("{{h1}}", ",\n    hidden: Hidden")
@@ -734,6 +1516,21 @@ pub(crate) fn fill_main_nr_template(
             .replace("{{h1}}", "")
             .replace("{{h2}}", "");
     }
+    if !emitted.computed.is_empty() {
+        // Private output witnesses for REPLACE / ENCODE_FOR_URI and
+        // language-tag witnesses for LANG / LANGMATCHES. Each is bound
+        // inside `checkBinding`; see `noir/lib/utils` (computed string
+        // witnesses) and `noir/lib/lang`.
+        main_nr = main_nr
+            .replace("{{c0}}", ", Computed")
+            .replace("{{c1}}", ",\n    computed: Computed")
+            .replace("{{c2}}", ", computed");
+    } else {
+        main_nr = main_nr
+            .replace("{{c0}}", "")
+            .replace("{{c1}}", "")
+            .replace("{{c2}}", "");
+    }
     if has_not_exists {
         // Sentinel + boundary-cases scaffolding for NOT EXISTS / MINUS.
         // The signer's sorted Merkle commitment carries permanent low /
@@ -776,7 +1573,7 @@ pub(crate) fn fill_main_nr_template(
         // Round-5 prefix-3 commitment scaffolding -- two-root signer
         // ABI. `roots[1]` is the prefix-3 sorted Merkle root,
         // committed alongside `roots[0]` (leaf-hash sorted) by the
-        // signer. The prover supplies `low_sentinel_3` /
+        // signer. With several datasets it follows their roots. The prover supplies `low_sentinel_3` /
         // `high_sentinel_3` / `bgp_prefix3` and the per-dispatch
         // `boundary_cases_prefix3` tag. See
         // `spec/prefix-tree-commitment.md` Sec.8.
@@ -810,57 +1607,246 @@ pub(crate) fn fill_main_nr_template(
                      \x20   verify_high_sentinel_inclusion(high_sentinel_3, roots[1].value);\n\
                      \x20   for ptriple in bgp_prefix3 {\n\
                      \x20       verify_inclusion_prefix3(ptriple, roots[1].value);\n\
-                     \x20   }\n\n",
+                     \x20   }\n\n"
+                    .replace("roots[1]", &format!("roots[{}]", prefix3_root))
+                    .as_str(),
             )
             .replace(
                 "{{p4}}",
                 ", bgp_prefix3, low_sentinel_3, high_sentinel_3, boundary_cases_prefix3",
-            )
-            .replace("{{r0}}", "Root; 2")
-            .replace("{{r1}}", "0..2");
+            );
     } else {
         main_nr = main_nr
             .replace("{{p0}}", "")
             .replace("{{p1}}", "")
             .replace("{{p2}}", "")
             .replace("{{p3}}", "")
-            .replace("{{p4}}", "")
-            .replace("{{r0}}", "Root; 1")
-            .replace("{{r1}}", "0..1");
+            .replace("{{p4}}", "");
+    }
+    main_nr = MainSection::fill(main_nr, &sections);
+    main_nr = main_nr
+        .replace("{{r0}}", &format!("{}; {}", root_type, num_roots))
+        .replace("{{r1}}", &format!("0..{}", num_roots));
+    if emitted.num_datasets > 1 {
+        // Multiple issuers: one key and root per dataset, with each
+        // slot's dataset fixed by the query (metadata `bgpDatasets`).
+        main_nr = main_nr
+            .replace("{{i0}}", &emitted.num_datasets.to_string())
+            .replace("{{i1}}", "sparql::ROOT_KEYS[i]")
+            .replace("{{i2}}", "i in 0..bgp.len()")
+            .replace("{{i3}}", "bgp[i]")
+            .replace("{{i4}}", "sparql::BGP_DATASETS[i]");
+    } else {
+        main_nr = main_nr
+            .replace("{{i0}}", "1")
+            .replace("{{i1}}", "0")
+            .replace("{{i2}}", "triple in bgp")
+            .replace("{{i3}}", "triple")
+            .replace("{{i4}}", "0");
+    }
+    if let Some(depth) = emitted.issuer_registry_depth {
+        // Issuer registry: the keys go private, each proven to be a
+        // leaf of the public `issuer_registry` root (metadata
+        // `issuerRegistry`).
+        main_nr = main_nr
+            .replace(
+                "{{k0}}",
+                "use dep::types::IssuerKey;\nuse dep::utils::verify_issuer_key;",
+            )
+            .replace("{{k1}}", &format!("IssuerKey<{}>", depth))
+            .replace("{{k2}}", "\n    issuer_registry: pub Field,")
+            .replace(
+                "{{k3}}",
+                "    // Each issuer key is in the registry of authorised issuers;\n\
+                 \x20   // which one signed stays private\n\
+                 \x20   for key in public_key {\n\
+                 \x20       verify_issuer_key(key, issuer_registry);\n\
+                 \x20   }\n\n",
+            )
+            .replace("{{k4}}", ".key");
+    } else {
+        main_nr = main_nr
+            .replace("{{k0}}", "use dep::consts::signature::PubKey;")
+            .replace("{{k1}}", "PubKey")
+            .replace("{{k2}}", "")
+            .replace("{{k3}}", "")
+            .replace("{{k4}}", "");
+    }
+    if emitted.has_disclosure {
+        // Committed / pseudonymised variables: the bindings go private
+        // and the public `disclosed` output carries what the verifier
+        // sees (metadata `disclosure`).
+        let mut imports = String::from(", Disclosed");
+        let mut inputs = String::from(",\n    disclosed: pub Disclosed");
+        let mut args = String::from(", disclosed");
+        if emitted.num_salts > 0 {
+            imports.push_str(", Salts");
+            inputs.push_str(",\n    salts: Salts");
+            args.push_str(", salts");
+        }
+        if emitted.has_context {
+            inputs.push_str(",\n    verifier_context: pub Field");
+            args.push_str(", verifier_context");
+        }
+        if emitted.has_encryption {
+            inputs.push_str(
+                ",\n    verifier_key: pub std::embedded_curve_ops::EmbeddedCurvePoint,\n    ephemeral_key: pub std::embedded_curve_ops::EmbeddedCurvePoint,\n    ephemeral_secret: std::embedded_curve_ops::EmbeddedCurveScalar",
+            );
+            args.push_str(", verifier_key, ephemeral_key, ephemeral_secret");
+        }
+        main_nr = main_nr
+            .replace("{{d0}}", &imports)
+            .replace("{{d1}}", "")
+            .replace("{{d2}}", &inputs)
+            .replace("{{d3}}", &args);
+    } else {
+        // An in-circuit aggregate keeps its rows private.
+        let visibility = if emitted.has_in_circuit_aggregate { "" } else { "pub " };
+        main_nr = main_nr
+            .replace("{{d0}}", "")
+            .replace("{{d1}}", visibility)
+            .replace("{{d2}}", "")
+            .replace("{{d3}}", "");
+    }
+    if emitted.has_nullifier {
+        // Context binding with a nullifier (metadata `context` /
+        // `nullifier`); `checkBinding` derives the nullifier.
+        main_nr = main_nr
+            .replace(
+                "{{x0}}",
+                ",\n    context: pub Field,\n    scope: pub Field,\n    nullifier: pub Field",
+            )
+            .replace("{{x1}}", ", context, scope, nullifier")
+            .replace("{{x2}}", "");
+    } else if emitted.binds_context {
+        // The public `context` alone ties the proof to one verifier,
+        // session or purpose; nothing else reads it.
+        main_nr = main_nr
+            .replace("{{x0}}", ",\n    context: pub Field")
+            .replace("{{x1}}", "")
+            .replace("{{x2}}", "    let _ = context;\n\n");
+    } else {
+        main_nr = main_nr.replace("{{x0}}", "").replace("{{x1}}", "").replace("{{x2}}", "");
+    }
+    if emitted.has_params {
+        // Query parameters: term hashes, then the values pinned to
+        // them (metadata `parameters` / `parameterValues`).
+        main_nr = main_nr
+            .replace("{{q0}}", ", Params")
+            .replace("{{q1}}", ",\n    params: pub Params")
+            .replace("{{q2}}", ", params");
+    } else {
+        main_nr = main_nr.replace("{{q0}}", "").replace("{{q1}}", "").replace("{{q2}}", "");
+    }
+    // Derived-tree roots are verified against the message binding them
+    // to `roots[0]` (`sparql::ROOT_TAGS`); the others as signed.
+    let bound = num_roots > first_derived_root;
+    let signed_root = if bound { "root" } else { "roots[i]" };
+    if bound {
+        let bind = if emitted.root_validity { "bind_timed_root" } else { "bind_root" };
+        main_nr = main_nr.replace("{{v1}}", &format!("{}, {{{{v1}}}}", bind)).replace(
+            "{{b0}}",
+            &format!("let root = {}(roots[i], sparql::ROOT_TAGS[i], roots[0].value);\n        ", bind),
+        );
+    } else {
+        main_nr = main_nr.replace("{{b0}}", "");
+    }
+    main_nr = main_nr.replace("{{b1}}", signed_root);
+    if emitted.revocation_check {
+        // Each root's signed message is absent from the public
+        // revocation list (metadata `rootValidity.revocation`).
+        main_nr = main_nr
+            .replace("{{v0}}", "{RevocationBracket, TimedRoot}")
+            .replace("{{v1}}", "verify_not_revoked, verify_timed_signature")
+            .replace(
+                "{{v2}}",
+                &format!(
+                    "\n    revocations: [RevocationBracket; {}],\n    revocation_root: pub Field,",
+                    num_roots
+                ),
+            )
+            .replace(
+                "{{v5}}",
+                &format!("\n        verify_not_revoked({}, revocations[i], revocation_root);", signed_root),
+            );
+    } else if emitted.root_validity {
+        main_nr = main_nr
+            .replace("{{v0}}", "TimedRoot")
+            .replace("{{v1}}", "verify_timed_signature")
+            .replace("{{v2}}", "")
+            .replace("{{v5}}", "");
+    } else {
+        main_nr = main_nr
+            .replace("{{v0}}", "Root")
+            .replace("{{v1}}", "verify_signature")
+            .replace("{{v2}}", "")
+            .replace("{{v5}}", "");
+    }
+    if emitted.root_validity {
+        // The signed window is checked against the verifier's `now`.
+        main_nr = main_nr
+            .replace("{{v3}}", "verify_timed_signature")
+            .replace("{{v4}}", ", now");
+    } else {
+        main_nr = main_nr.replace("{{v3}}", "verify_signature").replace("{{v4}}", "");
+    }
+    if emitted.has_now || emitted.root_validity {
+        // `NOW()` as epoch milliseconds (UTC). Public so the verifier
+        // supplies the instant and checks it against its own clock
+        // (metadata `now.toleranceMs`). Root validity windows read the
+        // same input.
+        main_nr = main_nr.replace("{{t1}}", ",\n    now: pub i64");
+    } else {
+        main_nr = main_nr.replace("{{t1}}", "");
+    }
+    if emitted.has_now {
+        main_nr = main_nr.replace("{{t2}}", ", now");
+    } else {
+        main_nr = main_nr.replace("{{t2}}", "");
     }
     main_nr
 }
 
 /// Render `Nargo.toml` for the generated package, conditionally pulling in
 /// `ebv` / `xpath` / `types` / `utils` based on which features the circuit
-/// actually exercises.
-pub(crate) fn build_nargo_toml(skip_signing: bool, needs_ebv: bool, needs_xpath: bool) -> String {
-    let mut toml = if skip_signing {
-        r#"[package]
-name = "sparql_proof"
-type = "bin"
-authors = [""]
-
-[dependencies]
-consts = { path = "../noir/lib/consts" }
-"#
-        .to_string()
-    } else {
+/// actually exercises. The `consts` dependency is annotated with the
+/// hash and signature configuration and the digest of `consts` (the
+/// package `build_consts_package` rendered from the same options), so
+/// the manifest names the `consts` the circuit was generated against.
+pub(crate) fn build_nargo_toml(
+    options: &TransformOptions,
+    consts: &ConstsPackage,
+    needs_ebv: bool,
+    needs_lang: bool,
+    needs_xpath: bool,
+) -> String {
+    let mut toml = format!(
         r#"[package]
 name = "sparql_proof"
 type = "bin"
 authors = [""]
 
 [dependencies]
-consts = { path = "../noir/lib/consts" }
-types = { path = "../noir/lib/types" }
-utils = { path = "../noir/lib/utils" }
-"#
-        .to_string()
-    };
+# field_hash = "{}", string_hash = "{}", signature = "{}", merkle_depth = {}
+# consts digest: {}
+consts = {{ path = "../noir/lib/consts" }}
+"#,
+        options.field_hash.name(),
+        options.string_hash.name(),
+        options.signature.name(),
+        options.merkle_depth,
+        consts.digest()
+    );
+    if !options.skip_signing {
+        toml.push_str("types = { path = \"../noir/lib/types\" }\n");
+        toml.push_str("utils = { path = \"../noir/lib/utils\" }\n");
+    }
     if needs_ebv {
         toml.push_str("ebv = { path = \"../noir/lib/ebv\" }\n");
     }
+    if needs_lang {
+        toml.push_str("lang = { path = \"../noir/lib/lang\" }\n");
+    }
     if needs_xpath {
         toml.push_str("xpath = { path = \"../noir/lib/xpath\" }\n");
     }
@@ -0,0 +1,392 @@
+//! Transform-time term encoding.
+//!
+//! Mirrors the string layer of `src/encode.ts`: `consts::encode_string(s)`
+//! is `Field::from_le_bytes(hash_string(s.as_bytes()))`, with
+//! `hash_string` the string hash `consts` was set up with. When
+//! [`crate::TransformOptions::static_string_hash`] names that hash,
+//! `fold_static_encodings` replaces every constant `encode_string` call
+//! in the emitted Noir with the `Field` literal it evaluates to, so the
+//! circuit no longer re-hashes strings known at compile time.
+//!
+//! The field-hash layer (`consts::hash2` / `consts::hash4`) is folded in
+//! the same pass when `consts` uses Poseidon: `noir-lang/poseidon`'s
+//! `hash_2` / `hash_4` are the circom instances, which `light-poseidon`
+//! implements. Pedersen, Poseidon2 and MiMC stay in-circuit -- Noir's
+//! Pedersen generators, its width-4 Poseidon2 sponge and `mimc_bn254`
+//! have no audited Rust counterpart to fold with, and a hand-rolled one
+//! would be a second, unreviewed definition of the dataset encoding.
+
+use ark_bn254::Fr;
+use ark_ff::{BigInt, BigInteger, PrimeField};
+use blake2::Blake2s256;
+use light_poseidon::{Poseidon, PoseidonHasher};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+use crate::consts::FieldHash;
+
+/// String hash `consts::hash_string` is instantiated with -- the
+/// `stringHash` entry of the TypeScript setup config (`src/config.ts`).
+/// One variant per `mappings.ts` `stringHash` entry; `Sha384` and
+/// `Sha512` are rejected at transform time, as their digests do not
+/// fit a `Field` and `src/config.ts` leaves them out of `stringHashes`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StringHash {
+    Sha256,
+    Blake2s,
+    Blake3,
+    Sha384,
+    Sha512,
+    Keccak256,
+}
+
+impl StringHash {
+    /// Name as written in the TypeScript config and in metadata.
+    pub fn name(self) -> &'static str {
+        match self {
+            StringHash::Sha256 => "sha256",
+            StringHash::Blake2s => "blake2s",
+            StringHash::Blake3 => "blake3",
+            StringHash::Sha384 => "sha384",
+            StringHash::Sha512 => "sha512",
+            StringHash::Keccak256 => "keccak256",
+        }
+    }
+
+    /// Digest length in bytes, the `consts::hash_string` output size.
+    pub fn output_size(self) -> usize {
+        match self {
+            StringHash::Sha384 => 48,
+            StringHash::Sha512 => 64,
+            _ => 32,
+        }
+    }
+
+    fn digest(self, message: &[u8]) -> [u8; 32] {
+        match self {
+            StringHash::Sha256 => sha256(message),
+            StringHash::Blake2s => blake2s(message),
+            StringHash::Blake3 => blake3(message),
+            StringHash::Keccak256 => keccak256(message),
+            StringHash::Sha384 | StringHash::Sha512 => {
+                unreachable!("{} is rejected before any string is folded", self.name())
+            }
+        }
+    }
+}
+
+/// `consts::encode_string(s)` evaluated at transform time, as a Noir
+/// `Field` literal.
+pub(crate) fn encode_string(s: &str, hash: StringHash) -> String {
+    field_literal(field_from_le_bytes(hash.digest(s.as_bytes())))
+}
+
+/// Replace each `consts::encode_string("…")` in generated Noir with its
+/// value under `string_hash`, then -- under Poseidon -- each
+/// `consts::hash2` / `consts::hash4` whose inputs are all literals, until
+/// none is left. The emitter only ever escapes `\\` and `\"` in string
+/// literals (`expr::encode_string_expr`); a call in any other shape is
+/// left for the circuit to evaluate.
+pub(crate) fn fold_static_encodings(code: &str, string_hash: StringHash, field_hash: FieldHash) -> String {
+    let mut code = fold_static_strings(code, string_hash);
+    if field_hash == FieldHash::Poseidon {
+        while let Some(folded) = fold_static_field_hashes(&code) {
+            code = folded;
+        }
+    }
+    code
+}
+
+fn fold_static_strings(code: &str, hash: StringHash) -> String {
+    const CALL: &str = "consts::encode_string(\"";
+    let mut out = String::with_capacity(code.len());
+    let mut rest = code;
+    while let Some(start) = rest.find(CALL) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + CALL.len()..];
+        match unescape_noir_string(after) {
+            Some((value, consumed)) if after[consumed..].starts_with(')') => {
+                out.push_str(&encode_string(&value, hash));
+                rest = &after[consumed + 1..];
+            }
+            _ => {
+                out.push_str(CALL);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// One left-to-right pass folding every `consts::hash2([..])` /
+/// `consts::hash4([..])` over literal inputs with circom Poseidon.
+/// `None` when nothing was folded; a nested call becomes foldable on
+/// the next pass, once its arguments have been.
+fn fold_static_field_hashes(code: &str) -> Option<String> {
+    const CALL: &str = "consts::hash";
+    let mut out = String::with_capacity(code.len());
+    let mut rest = code;
+    let mut changed = false;
+    while let Some(start) = rest.find(CALL) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + CALL.len()..];
+        let arity = match after.get(..3) {
+            Some("2([") => 2,
+            Some("4([") => 4,
+            _ => 0,
+        };
+        let folded = (arity != 0)
+            .then(|| after[3..].find("])").map(|end| (&after[3..3 + end], 3 + end + 2)))
+            .flatten()
+            .and_then(|(args, consumed)| {
+                let inputs = args.split(',').map(|arg| parse_field_literal(arg.trim())).collect::<Option<Vec<_>>>()?;
+                (inputs.len() == arity).then_some(())?;
+                let hash = Poseidon::<Fr>::new_circom(arity).ok()?.hash(&inputs).ok()?;
+                Some((field_literal(hash), consumed))
+            });
+        match folded {
+            Some((literal, consumed)) => {
+                out.push_str(&literal);
+                rest = &after[consumed..];
+                changed = true;
+            }
+            None => {
+                out.push_str(CALL);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    changed.then_some(out)
+}
+
+/// A Noir integer literal as a field element: decimal (optionally
+/// negated, as the emitter writes negative integer specials) or `0x`
+/// hex below the modulus.
+fn parse_field_literal(token: &str) -> Option<Fr> {
+    if let Some(hex) = token.strip_prefix("0x") {
+        if hex.is_empty() || hex.len() > 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let padded = format!("{:0>64}", hex);
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let digits = &padded[64 - 16 * (i + 1)..64 - 16 * i];
+            *limb = u64::from_str_radix(digits, 16).ok()?;
+        }
+        return Fr::from_bigint(BigInt::new(limbs));
+    }
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value = Fr::from(digits.parse::<u128>().ok()?);
+    Some(if negative { -value } else { value })
+}
+
+/// Read a Noir string literal body up to its closing quote. Returns the
+/// unescaped value and the number of bytes consumed, closing quote
+/// included.
+fn unescape_noir_string(s: &str) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, i + 1)),
+            '\\' => match chars.next()? {
+                (_, e @ ('\\' | '"')) => value.push(e),
+                _ => return None,
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Noir's `Field::from_le_bytes` on a 32-byte digest: the little-endian
+/// integer reduced modulo the BN254 scalar field.
+fn field_from_le_bytes(bytes: [u8; 32]) -> Fr {
+    Fr::from_le_bytes_mod_order(&bytes)
+}
+
+/// A field element as the `0x`-prefixed, 64-digit hex literal the
+/// emitter writes.
+fn field_literal(field: Fr) -> String {
+    let hex: String = field.into_bigint().to_bytes_be().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", hex)
+}
+
+// =============================================================================
+// STRING HASHES
+// =============================================================================
+//
+// Thin wrappers over the RustCrypto / BLAKE3 reference crates, fixed to
+// the 32-byte digests `consts::hash_string` produces.
+
+pub(crate) fn sha256(message: &[u8]) -> [u8; 32] {
+    Sha256::digest(message).into()
+}
+
+fn blake2s(message: &[u8]) -> [u8; 32] {
+    Blake2s256::digest(message).into()
+}
+
+fn blake3(message: &[u8]) -> [u8; 32] {
+    blake3::hash(message).into()
+}
+
+fn keccak256(message: &[u8]) -> [u8; 32] {
+    Keccak256::digest(message).into()
+}
+
+#[cfg(test)]
+mod hash_tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn sha256_test_vectors() {
+        assert_eq!(
+            hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        // Spans several blocks, with the length in a padding-only block.
+        assert_eq!(
+            hex(&sha256(&[b'a'; 1000])),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn blake2s_test_vectors() {
+        assert_eq!(
+            hex(&blake2s(b"")),
+            "69217a3079908094e11121d042354a7c1f55b6482ca1a51e1b250dfd1ed0eef9"
+        );
+        assert_eq!(
+            hex(&blake2s(b"abc")),
+            "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982"
+        );
+        assert_eq!(
+            hex(&blake2s(&[b'a'; 1000])),
+            "a4691c2bf852334ece63c024234338fc6c150bdf04fa3f6e0e4c5209b326438d"
+        );
+    }
+
+    #[test]
+    fn blake3_test_vectors() {
+        // The official vectors hash bytes `i % 251`; lengths 1024 and
+        // 2048 fill whole chunks, 1025 spills into a parent node.
+        let input = |len: usize| (0..len).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+        assert_eq!(
+            hex(&blake3(&input(0))),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(
+            hex(&blake3(&input(1))),
+            "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213"
+        );
+        assert_eq!(
+            hex(&blake3(&input(1024))),
+            "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7"
+        );
+        assert_eq!(
+            hex(&blake3(&input(1025))),
+            "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444"
+        );
+        assert_eq!(
+            hex(&blake3(&input(2048))),
+            "e776b6028c7cd22a4d0ba182a8bf62205d2ef576467e838ed6f2529b85fba24a"
+        );
+        assert_eq!(
+            hex(&blake3(b"abc")),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+
+    #[test]
+    fn keccak256_test_vectors() {
+        assert_eq!(
+            hex(&keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        assert_eq!(
+            hex(&keccak256(b"abc")),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+        assert_eq!(
+            hex(&keccak256(&[b'a'; 1000])),
+            "b6a4ac1f51884d71f30fa397a5e155de3099e11fc0edef5d08b646e621e19de9"
+        );
+    }
+
+    #[test]
+    fn encode_string_reduces_into_the_field() {
+        assert_eq!(
+            encode_string("http://example.org/name", StringHash::Sha256),
+            "0x2a1db5fd13b30226833f09fce1108767d0d81777e025488a7b91a081789e0eb4"
+        );
+        assert_eq!(
+            encode_string("", StringHash::Sha256),
+            "0x255404053a67f57a944355ae62c055c9fc8587514f3b8b08d03b070452c4b0e2"
+        );
+        assert_eq!(
+            encode_string("http://example.org/name", StringHash::Blake2s),
+            "0x021c321c6b761f4511464f667a95efe7dcdd7144a744244f5c1b77c51928926d"
+        );
+    }
+
+    #[test]
+    fn fold_static_encodings_rewrites_only_well_formed_calls() {
+        let code = "assert(x == consts::hash2([0, consts::encode_string(\"a\\\"b\")]));\n\
+                    let y = consts::encode_string(s);";
+        let folded = fold_static_encodings(code, StringHash::Sha256, FieldHash::Pedersen);
+        assert_eq!(
+            folded,
+            format!(
+                "assert(x == consts::hash2([0, {}]));\nlet y = consts::encode_string(s);",
+                encode_string("a\"b", StringHash::Sha256)
+            )
+        );
+    }
+
+    #[test]
+    fn poseidon_folds_nested_literal_hashes() {
+        // circomlib's `poseidon([1, 2])`.
+        assert_eq!(
+            fold_static_encodings("let h = consts::hash2([1, 2]);", StringHash::Sha256, FieldHash::Poseidon),
+            "let h = 0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a;"
+        );
+        let code = "assert(x == consts::hash2([2, consts::hash4([consts::encode_string(\"a\"), -5, consts::encode_string(\"\"), 0x01])]));";
+        let folded = fold_static_encodings(code, StringHash::Sha256, FieldHash::Poseidon);
+        let inner = [
+            parse_field_literal(&encode_string("a", StringHash::Sha256)).unwrap(),
+            -Fr::from(5u64),
+            parse_field_literal(&encode_string("", StringHash::Sha256)).unwrap(),
+            Fr::from(1u64),
+        ];
+        let inner = Poseidon::<Fr>::new_circom(4).unwrap().hash(&inner).unwrap();
+        let outer = Poseidon::<Fr>::new_circom(2).unwrap().hash(&[Fr::from(2u64), inner]).unwrap();
+        assert_eq!(folded, format!("assert(x == {});", field_literal(outer)));
+        // A variable input keeps the call, and other field hashes are not folded.
+        let code = "consts::hash2([0, x])";
+        assert_eq!(fold_static_encodings(code, StringHash::Sha256, FieldHash::Poseidon), code);
+        let code = "consts::hash2([1, 2])";
+        assert_eq!(fold_static_encodings(code, StringHash::Sha256, FieldHash::Poseidon2), code);
+    }
+}
@@ -334,8 +334,14 @@ fn encode_string_expr(s: &str) -> String {
 pub(crate) fn serialize_term(term: &Term, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> String {
     match term {
         Term::Variable(name) => {
-            if query.variables.contains(name) {
+            if let Some(position) = parameter_position(query, name) {
+                format!("params[{}]", position)
+            } else if query.variables.contains(name) {
                 format!("variables.{}", name)
+            } else if let Some(Term::Computed(_)) = bindings.get(name) {
+                // String-valued BIND -- the emitter binds the computed
+                // witness to a `bind_<var>` local (see `emit.rs`).
+                format!("{}.hash", computed_binding_local(name))
             } else if let Some(bound) = bindings.get(name) {
                 serialize_term(bound, query, bindings)
             } else {
@@ -361,9 +367,18 @@ pub(crate) fn serialize_term(term: &Term, query: &QueryInfo, bindings: &BTreeMap
         // while the signer's leaf hash uses tag 4. The empty-string
         // payload mirrors RDF.js's `DefaultGraph.value === ""`.
         Term::DefaultGraph => format!("consts::hash2([4, {}])", encode_string_expr("")),
+        // Only reachable as a `Binding` right-hand side, which the
+        // emitter resolves through `computed_binding_local` itself.
+        Term::Computed(_) => unreachable!("Term::Computed is only serialised through its BIND variable"),
     }
 }
 
+/// Index of query parameter `name` in the public `params` array, whose
+/// first slots hold the parameters' term hashes.
+pub(crate) fn parameter_position(query: &QueryInfo, name: &str) -> Option<usize> {
+    query.parameters.iter().position(|p| p.name == name)
+}
+
 /// Compute the special literal encoding for the second field of hash4.
 /// This must match the TypeScript specialLiteralHandling function in encode.ts.
 /// Uses oxsdatatypes for robust parsing of XSD typed literals.
@@ -448,6 +463,18 @@ fn serialize_ground_term(gt: &GroundTerm) -> String {
 
 /// Handle XSD type casting functions like xsd:integer(?v), xsd:float(?v), etc.
 /// These map to xpath casting functions from noir_xpath library.
+///
+/// The SPARQL 1.1 §17.5 cast matrix is lowered in three tiers:
+/// - a constant argument is cast at transform time (`fold_xsd_cast`) and
+///   the result lowered like any other literal;
+/// - a string computed in-circuit (a string-valued function or BIND) is
+///   parsed over its bound byte witness (`cast_from_lexical`);
+/// - anything else is a value-to-value conversion on its `Field`
+///   representation, below. Numeric -> `xsd:string` allocates a string
+///   witness checked against the canonical lexical form (`string_cast`).
+///
+/// Casts to a date, time or duration type are temporal values; see
+/// `temporal_value`.
 fn handle_xsd_cast(
     target_type: &str,
     args: &[Expression],
@@ -458,43 +485,70 @@ fn handle_xsd_cast(
     if args.len() != 1 {
         return Err(format!("xsd:{} cast requires exactly 1 argument", target_type));
     }
+
+    if let Some(ty) = TemporalType::of_datatype(target_type) {
+        let call = Expression::FunctionCall(
+            Function::Custom(spargebra::term::NamedNode::new_unchecked(format!("{}{}", XSD, target_type))),
+            args.to_vec(),
+        );
+        return Ok(format!("({} as Field)", temporal_value(&call, ty, query, bindings, hidden)?));
+    }
+    if let Some(literal) = fold_xsd_cast(target_type, &args[0])? {
+        // Boolean casts evaluate to a Noir `bool`, constant or not.
+        if target_type == "boolean" {
+            return Ok(literal.value().to_string());
+        }
+        return expr_to_noir_code(&Expression::Literal(literal), query, bindings, hidden);
+    }
+    if !is_string_cast_target(target_type) && is_lexical_source(&args[0], query, bindings) {
+        return cast_from_lexical(target_type, &args[0], query, bindings, hidden);
+    }
     
     // Get the source expression value
     let arg_code = expr_to_noir_code(&args[0], query, bindings, hidden)?;
     
     // Determine source type if we can (for choosing the right cast function)
     let source_type = infer_expression_type(&args[0]);
+    let source_kind = cast_source_kind(&args[0]);
     
     match target_type {
-        // Cast to xsd:integer
-        "integer" | "int" | "long" | "short" | "byte" |
-        "unsignedInt" | "unsignedLong" | "unsignedShort" | "unsignedByte" |
-        "positiveInteger" | "negativeInteger" | "nonPositiveInteger" | "nonNegativeInteger" => {
-            match source_type {
+        // Cast to xsd:integer (and its derived types, range-checked)
+        _ if integer_cast_range(target_type).is_some() => {
+            let code = match source_type {
+                _ if source_kind == Some(CastSourceKind::Boolean) => {
+                    format!("(if {} {{ 1 }} else {{ 0 }})", arg_code)
+                }
                 Some(NumericSourceType::Float) => {
                     // cast_float_to_integer returns Option<i64>; assert success before unwrap
-                    Ok(format!(
+                    format!(
                         "{{ let tmp = xpath::cast_float_to_integer(xpath::XsdFloat::from_bits({} as u32)); assert(tmp.is_some()); tmp.unwrap() as Field }}",
                         arg_code
-                    ))
+                    )
                 }
                 Some(NumericSourceType::Double) => {
                     // cast_double_to_integer returns Option<i64>; assert success before unwrap
-                    Ok(format!(
+                    format!(
                         "{{ let tmp = xpath::cast_double_to_integer(xpath::XsdDouble::from_bits({} as u64)); assert(tmp.is_some()); tmp.unwrap() as Field }}",
                         arg_code
-                    ))
+                    )
+                }
+                Some(NumericSourceType::Decimal) => {
+                    format!("xpath::decimal_to_integer(({}) as i64) as Field", arg_code)
                 }
                 Some(NumericSourceType::Integer) | None => {
                     // Already integer or unknown - just pass through as Field
-                    Ok(format!("{} as Field", arg_code))
+                    format!("{} as Field", arg_code)
                 }
-            }
+            };
+            Ok(integer_range_checked(target_type, code))
         }
         
         // Cast to xsd:float
         "float" => {
             match source_type {
+                _ if source_kind == Some(CastSourceKind::Boolean) => {
+                    Ok(format!("(if {} {{ 0x3f800000 }} else {{ 0 }})", arg_code))
+                }
                 Some(NumericSourceType::Integer) => {
                     // cast_integer_to_float takes a signed integer (use i64 to avoid truncation)
                     // Convert to bits for Field representation
@@ -504,9 +558,12 @@ fn handle_xsd_cast(
                     // cast_double_to_float
                     Ok(format!("xpath::cast_double_to_float(xpath::XsdDouble::from_bits({} as u64)).to_bits() as Field", arg_code))
                 }
+                Some(NumericSourceType::Decimal) => {
+                    Ok(format!("xpath::decimal_to_float(({}) as i64).to_bits() as Field", arg_code))
+                }
                 Some(NumericSourceType::Float) | None => {
                     // Already float or unknown - pass through
-                    Ok(format!("{}", arg_code))
+                    Ok(arg_code.to_string())
                 }
             }
         }
@@ -514,6 +571,9 @@ fn handle_xsd_cast(
         // Cast to xsd:double
         "double" => {
             match source_type {
+                _ if source_kind == Some(CastSourceKind::Boolean) => {
+                    Ok(format!("(if {} {{ 0x3ff0000000000000 }} else {{ 0 }})", arg_code))
+                }
                 Some(NumericSourceType::Integer) => {
                     // cast_integer_to_double takes i64 to avoid truncation
                     Ok(format!("xpath::cast_integer_to_double(({}) as i64).to_bits() as Field", arg_code))
@@ -522,72 +582,668 @@ fn handle_xsd_cast(
                     // XsdDouble::from_float for float to double
                     Ok(format!("xpath::XsdDouble::from_float(xpath::XsdFloat::from_bits({} as u32)).to_bits() as Field", arg_code))
                 }
+                Some(NumericSourceType::Decimal) => {
+                    Ok(format!("xpath::decimal_to_double(({}) as i64).to_bits() as Field", arg_code))
+                }
                 Some(NumericSourceType::Double) | None => {
                     // Already double or unknown - pass through
-                    Ok(format!("{}", arg_code))
+                    Ok(arg_code.to_string())
                 }
             }
         }
         
-        // Cast to xsd:decimal - treat like double for now
+        // Cast to xsd:decimal - the fixed-point value of `xpath::decimal`.
+        // NaN and the infinities have no decimal value, so casting them
+        // is an error.
         "decimal" => {
             match source_type {
+                _ if source_kind == Some(CastSourceKind::Boolean) => {
+                    Ok(format!("(if {} {{ xpath::DECIMAL_ONE }} else {{ 0 }}) as Field", arg_code))
+                }
                 Some(NumericSourceType::Integer) => {
-                    Ok(format!("xpath::cast_integer_to_double(({}) as i64).to_bits() as Field", arg_code))
+                    Ok(format!("xpath::decimal_from_integer(({}) as i64) as Field", arg_code))
+                }
+                Some(NumericSourceType::Float) => {
+                    Ok(format!("xpath::float_to_decimal({} as u32) as Field", arg_code))
                 }
-                _ => Ok(format!("{}", arg_code))
+                Some(NumericSourceType::Double) => {
+                    Ok(format!("xpath::double_to_decimal({} as u64) as Field", arg_code))
+                }
+                Some(NumericSourceType::Decimal) | None => Ok(arg_code.to_string()),
             }
         }
         
-        // Cast to xsd:boolean
+        // Cast to xsd:boolean - XPath: numeric zero and NaN are false,
+        // every other number is true.
         "boolean" => {
-            // XSD boolean cast from numerics only permits 0 and 1:
-            // 0 -> false, 1 -> true. Other numeric values are invalid.
-            // Generate Noir code that enforces this at runtime via an assertion.
-            Ok(format!("{{ let v = {}; assert(v == 0 || v == 1); v == 1 }}", arg_code))
+            match source_kind {
+                Some(CastSourceKind::Boolean) => Ok(arg_code),
+                Some(CastSourceKind::Float) => Ok(format!("xpath::float_to_boolean(({}) as u32)", arg_code)),
+                Some(CastSourceKind::Double) => Ok(format!("xpath::double_to_boolean(({}) as u64)", arg_code)),
+                Some(CastSourceKind::Integer) | Some(CastSourceKind::Decimal) | None => {
+                    Ok(format!("({} != 0)", arg_code))
+                }
+            }
         }
         
-        // Cast to xsd:string - returns the encoded string representation
+        // Cast to xsd:string - the canonical lexical form of a numeric
+        // or boolean value, as a fresh string witness.
         "string" | "normalizedString" | "token" => {
-            // String cast preserves the lexical value.
-            // For numeric types, proper lexical conversion (e.g., 42 -> "42")
-            // is not implemented in the generated Noir circuits.
-            // To avoid silently incorrect behavior, we reject numeric→string casts.
-            match source_type {
-                Some(_) => Err(
-                    "Casting numeric types to xsd:string (or derived types) is not supported by the transformer"
-                        .to_string(),
-                ),
-                None => Ok(format!("{}", arg_code)),
+            match source_kind {
+                Some(_) if target_type == "string" => {
+                    let call = Expression::FunctionCall(
+                        Function::Custom(spargebra::term::NamedNode::new_unchecked(format!("{}string", XSD))),
+                        args.to_vec(),
+                    );
+                    string_valued_term_hash(&call, query, bindings, hidden)
+                }
+                Some(_) => Err(format!(
+                    "Casting a numeric or boolean value to xsd:{} is not supported by the transformer; \
+                     cast it to xsd:string instead",
+                    target_type
+                )),
+                None => Ok(arg_code.to_string()),
             }
         }
         
-        // Cast to xsd:dateTime
-        "dateTime" => {
-            // DateTime values are typically epoch milliseconds
-            // Assuming input is already in correct format
-            Ok(format!("{}", arg_code))
+        // Unsupported cast target
+        _ => Err(format!("Unsupported XSD cast target type: xsd:{}", target_type))
+    }
+}
+
+/// What an XSD cast's argument is, for choosing the conversion. Unlike
+/// [`NumericSourceType`] this tells xsd:decimal and xsd:boolean apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CastSourceKind {
+    Integer,
+    Decimal,
+    Float,
+    Double,
+    Boolean,
+}
+
+fn cast_source_kind_of_datatype(local: &str) -> Option<CastSourceKind> {
+    match local {
+        "decimal" => Some(CastSourceKind::Decimal),
+        "float" => Some(CastSourceKind::Float),
+        "double" => Some(CastSourceKind::Double),
+        "boolean" => Some(CastSourceKind::Boolean),
+        _ if integer_cast_range(local).is_some() => Some(CastSourceKind::Integer),
+        _ => None,
+    }
+}
+
+/// Static type of a cast argument, when its value is a number or a
+/// boolean. Variables carry no static type and yield `None`.
+fn cast_source_kind(expr: &Expression) -> Option<CastSourceKind> {
+    match expr {
+        Expression::Literal(l) => l.datatype().as_str().strip_prefix(XSD).and_then(cast_source_kind_of_datatype),
+        Expression::FunctionCall(Function::Custom(iri), _) => {
+            iri.as_str().strip_prefix(XSD).and_then(cast_source_kind_of_datatype)
         }
-        
-        // Cast to xsd:date
-        "date" => {
-            Ok(format!("{}", arg_code))
+        _ => match infer_expression_type(expr)? {
+            NumericSourceType::Integer => Some(CastSourceKind::Integer),
+            NumericSourceType::Decimal => Some(CastSourceKind::Decimal),
+            NumericSourceType::Float => Some(CastSourceKind::Float),
+            NumericSourceType::Double => Some(CastSourceKind::Double),
+        },
+    }
+}
+
+fn is_string_cast_target(target: &str) -> bool {
+    matches!(target, "string" | "normalizedString" | "token")
+}
+
+/// Value range of an integer cast target, `None` for non-integer
+/// targets. An unbounded side is `None`.
+fn integer_cast_range(target: &str) -> Option<(Option<i128>, Option<i128>)> {
+    let range = match target {
+        "integer" => (None, None),
+        "long" => (Some(i64::MIN as i128), Some(i64::MAX as i128)),
+        "int" => (Some(i32::MIN as i128), Some(i32::MAX as i128)),
+        "short" => (Some(i16::MIN as i128), Some(i16::MAX as i128)),
+        "byte" => (Some(i8::MIN as i128), Some(i8::MAX as i128)),
+        "unsignedLong" => (Some(0), Some(u64::MAX as i128)),
+        "unsignedInt" => (Some(0), Some(u32::MAX as i128)),
+        "unsignedShort" => (Some(0), Some(u16::MAX as i128)),
+        "unsignedByte" => (Some(0), Some(u8::MAX as i128)),
+        "positiveInteger" => (Some(1), None),
+        "nonNegativeInteger" => (Some(0), None),
+        "negativeInteger" => (None, Some(-1)),
+        "nonPositiveInteger" => (None, Some(0)),
+        _ => return None,
+    };
+    Some(range)
+}
+
+/// Wrap integer-valued `code` in the range assertions of a derived
+/// integer target. In-circuit integers are `i64`, so bounds at or beyond
+/// its range need no check.
+fn integer_range_checked(target: &str, code: String) -> String {
+    let (low, high) = integer_cast_range(target).unwrap_or((None, None));
+    let mut checks = String::new();
+    if let Some(low) = low.filter(|&b| b > i64::MIN as i128) {
+        checks.push_str(&format!("assert(v >= {}); ", low));
+    }
+    if let Some(high) = high.filter(|&b| b < i64::MAX as i128) {
+        checks.push_str(&format!("assert(v <= {}); ", high));
+    }
+    if checks.is_empty() {
+        code
+    } else {
+        format!("{{ let v = ({}) as i64; {}v as Field }}", code, checks)
+    }
+}
+
+/// True iff `expr` is a string computed in-circuit, whose bound byte
+/// witness a cast can parse.
+fn is_lexical_source(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    match expr {
+        Expression::Variable(v) => {
+            computed_binding_expr(v.as_str(), query, bindings).is_some_and(is_string_valued_function)
         }
-        
-        // Cast to xsd:time
-        "time" => {
-            Ok(format!("{}", arg_code))
+        _ => is_string_valued_function(expr),
+    }
+}
+
+/// Cast an in-circuit string to a non-string target by parsing its
+/// bound bytes. Integers, decimals and booleans are read out of the
+/// bytes; floating-point results are prover-supplied (`cast_value`) and
+/// checked to be what the bytes round to.
+fn cast_from_lexical(
+    target: &str,
+    arg: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    let operand = string_operand(arg, query, bindings, hidden)?;
+    let (c, w) = (&operand.checks, &operand.witness);
+    if integer_cast_range(target).is_some() {
+        let code = format!("{{ {}xpath::parse_integer_lexical({w}.bytes, {w}.length) as Field }}", c, w = w);
+        return Ok(integer_range_checked(target, code));
+    }
+    let (check, width) = match target {
+        "boolean" => return Ok(format!("{{ {}xpath::parse_boolean_lexical({w}.bytes, {w}.length) }}", c, w = w)),
+        "decimal" => {
+            return Ok(format!("{{ {}xpath::parse_decimal_lexical({w}.bytes, {w}.length) as Field }}", c, w = w))
         }
-        
-        // Unsupported cast target
-        _ => Err(format!("Unsupported XSD cast target type: xsd:{}", target_type))
+        "float" => ("lexical_rounds_to_float", "u32"),
+        "double" => ("lexical_rounds_to_double", "u64"),
+        _ => {
+            return Err(format!(
+                "Casting a string computed in the circuit to xsd:{} is not supported by the transformer; \
+                 only numeric and boolean targets parse in-circuit lexical forms",
+                target
+            ))
+        }
+    };
+    let idx = next_hidden_index(hidden);
+    hidden.push(serde_json::json!({
+        "type": "customComputed",
+        "computedType": "cast_value",
+        "targetType": target,
+        "input": operand.input,
+    }));
+    Ok(format!(
+        "{{ {c}let v = hidden[{i}]; assert(xpath::{f}({w}.bytes, {w}.length, v as {t})); v }}",
+        c = c,
+        i = idx,
+        f = check,
+        w = w,
+        t = width,
+    ))
+}
+
+// -----------------------------------------------------------------------------
+// Constant casts
+// -----------------------------------------------------------------------------
+
+/// A constant cast argument's value, by XSD primitive type. Decimals are
+/// exact: `digits * 10^-scale`.
+enum CastValue {
+    Integer(i128),
+    Decimal(i128, u32),
+    Float(f32),
+    Double(f64),
+    Boolean(bool),
+    String(String),
+    DateTime(String),
+    Date(String),
+    Time(String),
+    Iri(String),
+}
+
+/// Cast a constant argument at transform time. `Ok(None)` when the
+/// argument is not constant or the target is not an XSD cast; `Err`
+/// when the cast is a SPARQL error (an invalid lexical form, a value out
+/// of the target's range, or a cast §17.5 does not allow), which
+/// `is_static_error` folds.
+pub(crate) fn fold_xsd_cast(target: &str, arg: &Expression) -> Result<Option<spargebra::term::Literal>, String> {
+    if !is_string_cast_target(target)
+        && integer_cast_range(target).is_none()
+        && !matches!(target, "decimal" | "float" | "double" | "boolean" | "dateTime" | "date" | "time")
+    {
+        return Ok(None);
+    }
+    let value = match arg {
+        Expression::NamedNode(nn) => CastValue::Iri(nn.as_str().to_string()),
+        _ => match constant_cast_literal(arg)? {
+            Some(literal) => cast_value(&literal)?,
+            None => return Ok(None),
+        },
+    };
+    cast_constant(target, value).map(Some)
+}
+
+/// The literal a constant expression evaluates to: a literal, or an
+/// XSD cast of one.
+fn constant_cast_literal(expr: &Expression) -> Result<Option<spargebra::term::Literal>, String> {
+    match expr {
+        Expression::Literal(l) => Ok(Some(l.clone())),
+        Expression::FunctionCall(Function::Custom(iri), args) if args.len() == 1 => match iri.as_str().strip_prefix(XSD) {
+            Some(target) => fold_xsd_cast(target, &args[0]),
+            None => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
+
+fn cast_value(literal: &spargebra::term::Literal) -> Result<CastValue, String> {
+    let lexical = literal.value();
+    let invalid = |kind: &str| format!("{:?} is not a valid xsd:{} lexical form", lexical, kind);
+    if literal.language().is_some() {
+        return Err(format!("the language-tagged literal {} cannot be cast", literal));
+    }
+    let local = literal.datatype().as_str().strip_prefix(XSD).unwrap_or("");
+    match local {
+        "string" | "normalizedString" | "token" => Ok(CastValue::String(lexical.to_string())),
+        "decimal" => {
+            let (digits, scale) = parse_decimal_lexical(lexical).ok_or_else(|| invalid(local))?;
+            Ok(CastValue::Decimal(digits, scale))
+        }
+        "float" => Ok(CastValue::Float(parse_float_lexical(lexical).ok_or_else(|| invalid(local))?)),
+        "double" => Ok(CastValue::Double(parse_float_lexical(lexical).ok_or_else(|| invalid(local))?)),
+        "boolean" => Ok(CastValue::Boolean(parse_boolean_lexical(lexical).ok_or_else(|| invalid(local))?)),
+        "dateTime" if is_datetime_lexical(lexical) => Ok(CastValue::DateTime(lexical.to_string())),
+        "date" if is_date_lexical(lexical) => Ok(CastValue::Date(lexical.to_string())),
+        "time" if is_time_lexical(lexical) => Ok(CastValue::Time(lexical.to_string())),
+        "dateTime" | "date" | "time" => Err(invalid(local)),
+        _ if integer_cast_range(local).is_some() => {
+            Ok(CastValue::Integer(parse_integer_lexical(lexical).ok_or_else(|| invalid(local))?))
+        }
+        _ => Err(format!("literals of datatype <{}> cannot be cast", literal.datatype().as_str())),
+    }
+}
+
+/// Apply the §17.5 cast matrix to a constant value.
+fn cast_constant(target: &str, value: CastValue) -> Result<spargebra::term::Literal, String> {
+    let error = |reason: &str| Err(format!("xsd:{} cast is always an error: {}", target, reason));
+    let typed = |lexical: String| {
+        spargebra::term::Literal::new_typed_literal(
+            lexical,
+            spargebra::term::NamedNode::new_unchecked(format!("{}{}", XSD, target)),
+        )
+    };
+    if is_string_cast_target(target) {
+        let lexical = match value {
+            CastValue::Integer(n) => n.to_string(),
+            CastValue::Decimal(digits, scale) => decimal_string(digits, scale),
+            CastValue::Float(x) => xpath_float_string(x as f64, format!("{:e}", x)),
+            CastValue::Double(x) => xpath_float_string(x, format!("{:e}", x)),
+            CastValue::Boolean(b) => b.to_string(),
+            CastValue::String(s)
+            | CastValue::DateTime(s)
+            | CastValue::Date(s)
+            | CastValue::Time(s)
+            | CastValue::Iri(s) => s,
+        };
+        return Ok(if target == "string" {
+            spargebra::term::Literal::new_simple_literal(lexical)
+        } else {
+            typed(lexical)
+        });
+    }
+    if let Some((low, high)) = integer_cast_range(target) {
+        let n = match value {
+            CastValue::Integer(n) => n,
+            CastValue::Decimal(digits, scale) => 10i128.checked_pow(scale).map_or(0, |p| digits / p),
+            CastValue::Float(x) => float_to_integer(x as f64).ok_or("NaN / INF / out-of-range float")?,
+            CastValue::Double(x) => float_to_integer(x).ok_or("NaN / INF / out-of-range double")?,
+            CastValue::Boolean(b) => b as i128,
+            CastValue::String(s) => match parse_integer_lexical(&s) {
+                Some(n) => n,
+                None => return error(&format!("{:?} is not an integer", s)),
+            },
+            _ => return error("the source type cannot be cast to an integer"),
+        };
+        if low.is_some_and(|low| n < low) || high.is_some_and(|high| n > high) {
+            return error(&format!("{} is outside the range of xsd:{}", n, target));
+        }
+        return Ok(typed(n.to_string()));
+    }
+    match target {
+        "decimal" => {
+            let (digits, scale) = match value {
+                CastValue::Integer(n) => (n, 0),
+                CastValue::Decimal(digits, scale) => (digits, scale),
+                // Rounded like `xpath::double_to_decimal` does in-circuit.
+                CastValue::Float(x) if x.is_finite() => {
+                    let v = binary_to_fixed_point(x as f64).ok_or("float out of range")?;
+                    parse_decimal_lexical(&decimal_string(v as i128, DECIMAL_SCALE)).ok_or("float out of range")?
+                }
+                CastValue::Double(x) if x.is_finite() => {
+                    let v = binary_to_fixed_point(x).ok_or("double out of range")?;
+                    parse_decimal_lexical(&decimal_string(v as i128, DECIMAL_SCALE)).ok_or("double out of range")?
+                }
+                CastValue::Boolean(b) => (b as i128, 0),
+                CastValue::String(s) => match parse_decimal_lexical(&s) {
+                    Some(d) => d,
+                    None => return error(&format!("{:?} is not a decimal", s)),
+                },
+                CastValue::Float(_) | CastValue::Double(_) => return error("NaN and INF have no decimal value"),
+                _ => return error("the source type cannot be cast to a decimal"),
+            };
+            Ok(typed(decimal_string(digits, scale)))
+        }
+        "float" => {
+            // Rounded straight to binary32, not through binary64.
+            let x = match value {
+                CastValue::Integer(n) => n as f32,
+                CastValue::Decimal(digits, scale) => {
+                    decimal_string(digits, scale).parse::<f32>().map_err(|e| e.to_string())?
+                }
+                CastValue::Float(x) => x,
+                CastValue::Double(x) => x as f32,
+                CastValue::Boolean(b) => b as u8 as f32,
+                CastValue::String(s) => match parse_float_lexical::<f32>(&s) {
+                    Some(x) => x,
+                    None => return error(&format!("{:?} is not a number", s)),
+                },
+                _ => return error("the source type cannot be cast to a number"),
+            };
+            Ok(typed(xpath_float_string(x as f64, format!("{:e}", x))))
+        }
+        "double" => {
+            let x = match value {
+                CastValue::Integer(n) => n as f64,
+                CastValue::Decimal(digits, scale) => {
+                    decimal_string(digits, scale).parse::<f64>().map_err(|e| e.to_string())?
+                }
+                CastValue::Float(x) => x as f64,
+                CastValue::Double(x) => x,
+                CastValue::Boolean(b) => b as u8 as f64,
+                CastValue::String(s) => match parse_float_lexical::<f64>(&s) {
+                    Some(x) => x,
+                    None => return error(&format!("{:?} is not a number", s)),
+                },
+                _ => return error("the source type cannot be cast to a number"),
+            };
+            Ok(typed(xpath_float_string(x, format!("{:e}", x))))
+        }
+        "boolean" => {
+            let b = match value {
+                CastValue::Integer(n) => n != 0,
+                CastValue::Decimal(digits, _) => digits != 0,
+                CastValue::Float(x) => x != 0.0 && !x.is_nan(),
+                CastValue::Double(x) => x != 0.0 && !x.is_nan(),
+                CastValue::Boolean(b) => b,
+                CastValue::String(s) => match parse_boolean_lexical(&s) {
+                    Some(b) => b,
+                    None => return error(&format!("{:?} is not a boolean", s)),
+                },
+                _ => return error("the source type cannot be cast to a boolean"),
+            };
+            Ok(typed(b.to_string()))
+        }
+        "dateTime" => match value {
+            CastValue::DateTime(s) => Ok(typed(s)),
+            CastValue::Date(s) => {
+                let (body, zone) = split_timezone(&s);
+                Ok(typed(format!("{}T00:00:00{}", body, zone)))
+            }
+            CastValue::String(s) if is_datetime_lexical(&s) => Ok(typed(s)),
+            _ => error("only xsd:dateTime, xsd:date and xsd:dateTime strings cast to xsd:dateTime"),
+        },
+        "date" => match value {
+            CastValue::Date(s) => Ok(typed(s)),
+            CastValue::DateTime(s) => {
+                let (body, zone) = split_timezone(&s);
+                let date = body.split('T').next().unwrap_or(body);
+                Ok(typed(format!("{}{}", date, zone)))
+            }
+            CastValue::String(s) if is_date_lexical(&s) => Ok(typed(s)),
+            _ => error("only xsd:date, xsd:dateTime and xsd:date strings cast to xsd:date"),
+        },
+        "time" => match value {
+            CastValue::Time(s) => Ok(typed(s)),
+            CastValue::DateTime(s) => {
+                let (body, zone) = split_timezone(&s);
+                let time = body.split('T').nth(1).unwrap_or(body);
+                Ok(typed(format!("{}{}", time, zone)))
+            }
+            CastValue::String(s) if is_time_lexical(&s) => Ok(typed(s)),
+            _ => error("only xsd:time, xsd:dateTime and xsd:time strings cast to xsd:time"),
+        },
+        _ => error("unsupported target"),
+    }
+}
+
+/// XML Schema whitespace collapse, as applied before parsing a lexical
+/// form.
+fn xsd_trim(s: &str) -> &str {
+    s.trim_matches([' ', '\t', '\n', '\r'])
+}
+
+fn is_integer_syntax(s: &str) -> bool {
+    let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn is_decimal_syntax(s: &str) -> bool {
+    let body = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (int, frac) = body.split_once('.').unwrap_or((body, ""));
+    !(int.is_empty() && frac.is_empty()) && int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit())
+}
+
+fn parse_integer_lexical(s: &str) -> Option<i128> {
+    let s = xsd_trim(s);
+    if !is_integer_syntax(s) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// An xsd:decimal lexical form as `digits * 10^-scale`, without
+/// trailing fraction zeros.
+fn parse_decimal_lexical(s: &str) -> Option<(i128, u32)> {
+    let s = xsd_trim(s);
+    if !is_decimal_syntax(s) {
+        return None;
+    }
+    let negative = s.starts_with('-');
+    let body = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (int, frac) = body.split_once('.').unwrap_or((body, ""));
+    let frac = frac.trim_end_matches('0');
+    let text = format!("{}{}", int, frac);
+    let digits: i128 = if text.is_empty() { 0 } else { text.parse().ok()? };
+    Some((if negative { -digits } else { digits }, frac.len() as u32))
+}
+
+/// An xsd:float / xsd:double lexical form, rounded to `T`.
+fn parse_float_lexical<T: std::str::FromStr>(s: &str) -> Option<T> {
+    let s = xsd_trim(s);
+    let text = match s {
+        "INF" | "+INF" => "inf",
+        "-INF" => "-inf",
+        "NaN" => "NaN",
+        _ => {
+            let (mantissa, exponent) = match s.find(['e', 'E']) {
+                Some(i) => (&s[..i], Some(&s[i + 1..])),
+                None => (s, None),
+            };
+            if !is_decimal_syntax(mantissa) || !exponent.is_none_or(is_integer_syntax) {
+                return None;
+            }
+            s
+        }
+    };
+    text.parse().ok()
+}
+
+fn parse_boolean_lexical(s: &str) -> Option<bool> {
+    match xsd_trim(s) {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// The fixed-point decimal nearest a finite `x`, ties toward zero (XPath
+/// §19.1.2.4); `None` outside the 64-bit range.
+fn binary_to_fixed_point(x: f64) -> Option<i64> {
+    let bits = x.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = (bits & 0xf_ffff_ffff_ffff) as i128;
+    let (mantissa, exponent) = if biased == 0 {
+        (fraction, -1074)
+    } else {
+        (fraction | (1 << 52), biased - 1075)
+    };
+    let scaled = mantissa * 10i128.pow(DECIMAL_SCALE);
+    let magnitude = if exponent >= 0 {
+        scaled.checked_mul(1i128.checked_shl(exponent as u32)?)?
+    } else if exponent < -120 {
+        0
+    } else {
+        let shift = (-exponent) as u32;
+        let (q, r) = (scaled >> shift, scaled & ((1 << shift) - 1));
+        if 2 * r > 1 << shift { q + 1 } else { q }
+    };
+    let magnitude = i64::try_from(magnitude).ok()?;
+    Some(if x.is_sign_negative() { -magnitude } else { magnitude })
+}
+
+/// XPath canonical string of a decimal: no exponent, no trailing
+/// fraction zeros, and no fraction at all for integral values.
+fn decimal_string(digits: i128, scale: u32) -> String {
+    let sign = if digits < 0 { "-" } else { "" };
+    let magnitude = digits.unsigned_abs().to_string();
+    let scale = scale as usize;
+    if scale == 0 {
+        return format!("{}{}", sign, magnitude);
+    }
+    let padded = format!("{:0>width$}", magnitude, width = scale + 1);
+    let (int, frac) = padded.split_at(padded.len() - scale);
+    format!("{}{}.{}", sign, int, frac)
+}
+
+/// Truncate a finite float to an integer; `None` for NaN, the
+/// infinities, and magnitudes beyond `i128`.
+fn float_to_integer(x: f64) -> Option<i128> {
+    (x.is_finite() && x.abs() < 1.0e38).then(|| x.trunc() as i128)
+}
+
+/// XPath canonical string of an xsd:float / xsd:double (F&O §19.1.2.1):
+/// `NaN`, `INF`, `-INF`, `0`, `-0`; the shortest round-tripping digits
+/// in decimal notation for 1e-6 <= |v| < 1e6; `D.DDDE<n>` otherwise.
+/// `scientific` is Rust's `{:e}` rendering of the value at its own
+/// width, which is shortest round-tripping.
+fn xpath_float_string(value: f64, scientific: String) -> String {
+    if value.is_nan() {
+        return "NaN".into();
+    }
+    if value.is_infinite() {
+        return if value < 0.0 { "-INF" } else { "INF" }.into();
+    }
+    if value == 0.0 {
+        return if value.is_sign_negative() { "-0" } else { "0" }.into();
+    }
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let k: i32 = exponent.parse().unwrap_or(0);
+    let sign = if mantissa.starts_with('-') { "-" } else { "" };
+    let digits: String = mantissa.chars().filter(|c| c.is_ascii_digit()).collect();
+    if (-6..=5).contains(&k) {
+        if k < 0 {
+            return format!("{}0.{}{}", sign, "0".repeat((-k - 1) as usize), digits);
+        }
+        let int_len = k as usize + 1;
+        if digits.len() <= int_len {
+            return format!("{}{:0<width$}", sign, digits, width = int_len);
+        }
+        let (int, frac) = digits.split_at(int_len);
+        return format!("{}{}.{}", sign, int, frac);
+    }
+    let (first, rest) = digits.split_at(1);
+    format!("{}{}.{}E{}", sign, first, if rest.is_empty() { "0" } else { rest }, k)
+}
+
+/// Split a temporal lexical form into its body and its timezone (`Z`,
+/// `+hh:mm`, `-hh:mm`, or empty).
+fn split_timezone(s: &str) -> (&str, &str) {
+    if let Some(body) = s.strip_suffix('Z') {
+        return (body, "Z");
+    }
+    let b = s.as_bytes();
+    if b.len() >= 6 && matches!(b[b.len() - 6], b'+' | b'-') && b[b.len() - 3] == b':' {
+        return s.split_at(s.len() - 6);
+    }
+    (s, "")
+}
+
+fn two_digits_in(s: &str, low: u32, high: u32) -> bool {
+    s.len() == 2 && s.bytes().all(|b| b.is_ascii_digit()) && s.parse::<u32>().is_ok_and(|n| (low..=high).contains(&n))
+}
+
+fn is_date_body(s: &str) -> bool {
+    let s = s.strip_prefix('-').unwrap_or(s);
+    let mut parts = s.split('-');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(year), Some(month), Some(day), None) => {
+            year.len() >= 4
+                && year.bytes().all(|b| b.is_ascii_digit())
+                && two_digits_in(month, 1, 12)
+                && two_digits_in(day, 1, 31)
+        }
+        _ => false,
     }
 }
 
+fn is_time_body(s: &str) -> bool {
+    let (hms, frac) = s.split_once('.').unwrap_or((s, "0"));
+    let mut parts = hms.split(':');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(hours), Some(minutes), Some(seconds), None) => {
+            two_digits_in(hours, 0, 24)
+                && two_digits_in(minutes, 0, 59)
+                && two_digits_in(seconds, 0, 59)
+                && !frac.is_empty()
+                && frac.bytes().all(|b| b.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+fn is_date_lexical(s: &str) -> bool {
+    is_date_body(split_timezone(s).0)
+}
+
+fn is_time_lexical(s: &str) -> bool {
+    is_time_body(split_timezone(s).0)
+}
+
+fn is_datetime_lexical(s: &str) -> bool {
+    split_timezone(s)
+        .0
+        .split_once('T')
+        .is_some_and(|(date, time)| is_date_body(date) && is_time_body(time))
+}
+
 /// Source type for determining which cast function to use
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum NumericSourceType {
     Integer,
+    Decimal,
     Float,
     Double,
 }
@@ -607,8 +1263,7 @@ fn infer_expression_type(expr: &Expression) -> Option<NumericSourceType> {
             } else if dt.ends_with("double") {
                 Some(NumericSourceType::Double)
             } else if dt.ends_with("decimal") {
-                // SPARQL treats decimal as double for comparisons
-                Some(NumericSourceType::Double)
+                Some(NumericSourceType::Decimal)
             } else if dt.ends_with("integer") || dt.ends_with("int") ||
                       dt.ends_with("long") || dt.ends_with("short") || dt.ends_with("byte") {
                 Some(NumericSourceType::Integer)
@@ -616,17 +1271,27 @@ fn infer_expression_type(expr: &Expression) -> Option<NumericSourceType> {
                 None
             }
         }
+        // Date, time and duration arithmetic is not numeric.
+        Expression::Add(a, b) | Expression::Subtract(a, b)
+            if temporal_type_with(a, &|_| None).is_some() || temporal_type_with(b, &|_| None).is_some() =>
+        {
+            None
+        }
         // Arithmetic — result is the wider of the operand types
         // (SPARQL 1.1 §17.3 promotion).
         Expression::Add(a, b)
         | Expression::Subtract(a, b)
-        | Expression::Multiply(a, b)
-        | Expression::Divide(a, b) => {
+        | Expression::Multiply(a, b) => {
             Some(promote_numeric_types(
                 infer_expression_type(a),
                 infer_expression_type(b),
             ))
         }
+        // Integer division yields xsd:decimal (XPath op:numeric-divide).
+        Expression::Divide(a, b) => Some(promote_numeric_types(
+            Some(promote_numeric_types(infer_expression_type(a), infer_expression_type(b))),
+            Some(NumericSourceType::Decimal),
+        )),
         Expression::UnaryPlus(a) | Expression::UnaryMinus(a) => infer_expression_type(a),
         // SPARQL numeric functions (ABS / ROUND / CEIL / FLOOR) preserve
         // the operand type per SPARQL 1.1 §17.4.
@@ -638,14 +1303,20 @@ fn infer_expression_type(expr: &Expression) -> Option<NumericSourceType> {
         }
         // Round 2 -- STRLEN returns xsd:integer per SPARQL 1.1 §17.4.2.
         Expression::FunctionCall(Function::StrLen, _) => Some(NumericSourceType::Integer),
+        Expression::FunctionCall(
+            Function::Year | Function::Month | Function::Day | Function::Hours | Function::Minutes,
+            _,
+        ) => Some(NumericSourceType::Integer),
+        Expression::FunctionCall(Function::Seconds, _) => Some(NumericSourceType::Decimal),
         // Handle XSD cast functions - they return the target type
         Expression::FunctionCall(Function::Custom(iri), _) => {
             let iri_str = iri.as_str();
             if iri_str.starts_with(XSD) {
                 let local = &iri_str[XSD.len()..];
                 match local {
+                    "decimal" => Some(NumericSourceType::Decimal),
                     "float" => Some(NumericSourceType::Float),
-                    "double" | "decimal" => Some(NumericSourceType::Double),
+                    "double" => Some(NumericSourceType::Double),
                     "integer" | "int" | "long" | "short" | "byte" |
                     "unsignedInt" | "unsignedLong" | "unsignedShort" | "unsignedByte" |
                     "positiveInteger" | "negativeInteger" | "nonPositiveInteger" | "nonNegativeInteger" => {
@@ -689,6 +1360,15 @@ fn expr_to_noir_code(
             let idx = push_hidden(hidden, "expr_value", &term);
             Ok(format!("hidden[{}]", idx))
         }
+        // xsd:decimal constants fold to their fixed-point value.
+        Expression::Literal(l) if l.datatype().as_str().strip_prefix(XSD) == Some("decimal") => {
+            let value = decimal_fixed_point(l.value())?;
+            if value < 0 {
+                Ok(format!("((0 - {} as i64) as Field)", value.unsigned_abs()))
+            } else {
+                Ok(format!("({} as Field)", value))
+            }
+        }
         Expression::Literal(l) => {
             let term = Term::Static(GroundTerm::Literal(l.clone()));
             let idx = push_hidden(hidden, "expr_value", &term);
@@ -701,6 +1381,18 @@ fn expr_to_noir_code(
         }
         
         // Function calls
+        // Date, time and duration arithmetic (F&O §10.8).
+        Expression::Add(a, b) | Expression::Subtract(a, b)
+            if temporal_type(a, query, bindings).is_some() || temporal_type(b, query, bindings).is_some() =>
+        {
+            let ty = temporal_type(expr, query, bindings)
+                .ok_or_else(|| format!("{} is not date, time or duration arithmetic XPath defines", expr))?;
+            Ok(format!("({} as Field)", temporal_value(expr, ty, query, bindings, hidden)?))
+        }
+        Expression::UnaryPlus(_) | Expression::UnaryMinus(_) if temporal_type(expr, query, bindings).is_some() => {
+            let ty = temporal_type(expr, query, bindings).unwrap_or(TemporalType::DayTimeDuration);
+            Ok(format!("({} as Field)", temporal_value(expr, ty, query, bindings, hidden)?))
+        }
         // Numeric arithmetic — round 2 §6.2 wires these into FILTER
         // expressions via xpath::numeric_*_{int,float,double}.
         Expression::Add(a, b) => emit_numeric_binary("add", a, b, query, bindings, hidden),
@@ -713,10 +1405,9 @@ fn expr_to_noir_code(
         Expression::FunctionCall(func, args) => {
             match func {
                 // Numeric functions — round 2 §6.2 makes these type-aware:
-                // integer / decimal → xpath::*_int (decimal floor at the
-                // prover's field-element width per Q7); xsd:float →
-                // xpath::*_float (binary32); xsd:double → xpath::*_double
-                // (binary64).
+                // integer → xpath::*_int; xsd:decimal → xpath::*_decimal
+                // (fixed-point); xsd:float → xpath::*_float (binary32);
+                // xsd:double → xpath::*_double (binary64).
                 Function::Abs => {
                     if args.len() != 1 { return Err("ABS requires 1 argument".into()); }
                     emit_numeric_unary_function("abs", &args[0], query, bindings, hidden)
@@ -735,43 +1426,17 @@ fn expr_to_noir_code(
                 }
 
                 // DateTime functions
-                Function::Year => {
-                    if args.len() != 1 { return Err("YEAR requires 1 argument".into()); }
-                    let arg_code = expr_to_noir_code(&args[0], query, bindings, hidden)?;
-                    // DateTime values are stored as epoch milliseconds, convert to microseconds for xpath
-                    Ok(format!("xpath::year_from_datetime(xpath::datetime_from_epoch_microseconds(({} as i128) * 1000))", arg_code))
-                }
-                Function::Month => {
-                    if args.len() != 1 { return Err("MONTH requires 1 argument".into()); }
-                    let arg_code = expr_to_noir_code(&args[0], query, bindings, hidden)?;
-                    Ok(format!("xpath::month_from_datetime(xpath::datetime_from_epoch_microseconds(({} as i128) * 1000))", arg_code))
-                }
-                Function::Day => {
-                    if args.len() != 1 { return Err("DAY requires 1 argument".into()); }
-                    let arg_code = expr_to_noir_code(&args[0], query, bindings, hidden)?;
-                    Ok(format!("xpath::day_from_datetime(xpath::datetime_from_epoch_microseconds(({} as i128) * 1000))", arg_code))
-                }
-                Function::Hours => {
-                    if args.len() != 1 { return Err("HOURS requires 1 argument".into()); }
-                    let arg_code = expr_to_noir_code(&args[0], query, bindings, hidden)?;
-                    Ok(format!("xpath::hours_from_datetime(xpath::datetime_from_epoch_microseconds(({} as i128) * 1000))", arg_code))
-                }
-                Function::Minutes => {
-                    if args.len() != 1 { return Err("MINUTES requires 1 argument".into()); }
-                    let arg_code = expr_to_noir_code(&args[0], query, bindings, hidden)?;
-                    Ok(format!("xpath::minutes_from_datetime(xpath::datetime_from_epoch_microseconds(({} as i128) * 1000))", arg_code))
-                }
-                Function::Seconds => {
-                    if args.len() != 1 { return Err("SECONDS requires 1 argument".into()); }
-                    let arg_code = expr_to_noir_code(&args[0], query, bindings, hidden)?;
-                    Ok(format!("xpath::seconds_from_datetime(xpath::datetime_from_epoch_microseconds(({} as i128) * 1000))", arg_code))
-                }
-                Function::Timezone => {
-                    if args.len() != 1 { return Err("TIMEZONE requires 1 argument".into()); }
-                    let arg_code = expr_to_noir_code(&args[0], query, bindings, hidden)?;
-                    // Returns a duration representing the timezone offset
-                    Ok(format!("xpath::duration_to_microseconds(xpath::timezone_from_datetime(xpath::datetime_from_epoch_microseconds(({} as i128) * 1000)))", arg_code))
+                Function::Year | Function::Month | Function::Day | Function::Hours | Function::Minutes
+                | Function::Seconds => {
+                    if args.len() != 1 {
+                        return Err(format!("{} requires 1 argument", func));
+                    }
+                    temporal_component(func, &args[0], query, bindings, hidden)
                 }
+                Function::Timezone => Ok(format!(
+                    "({} as Field)",
+                    temporal_value(expr, TemporalType::DayTimeDuration, query, bindings, hidden)?
+                )),
                 
                 // XSD type casting functions (Custom functions with XSD namespace)
                 Function::Custom(iri) => {
@@ -793,30 +1458,42 @@ fn expr_to_noir_code(
                 // boolean (e.g. via `&&` -> `filter_to_noir` recursion).
                 Function::StrLen => {
                     if args.len() != 1 { return Err("STRLEN requires 1 argument".into()); }
-                    let term = expr_to_term(&args[0])?;
-                    string_op_strlen(&term, query, bindings)
+                    let operand = string_operand(&args[0], query, bindings, hidden)?;
+                    string_op_strlen(&operand)
                 }
                 Function::StrStarts => {
                     if args.len() != 2 { return Err("STRSTARTS requires 2 arguments".into()); }
-                    let str_term = expr_to_term(&args[0])?;
+                    let operand = string_operand(&args[0], query, bindings, hidden)?;
                     let prefix = match &args[1] {
                         Expression::Literal(lit) => lit.value().to_string(),
                         _ => return Err(
                             "STRSTARTS round-2 requires the second argument to be a string literal".into(),
                         ),
                     };
-                    string_op_strstarts(&str_term, &prefix, query, bindings)
+                    string_op_strstarts(&operand, &prefix)
                 }
                 Function::Contains => {
                     if args.len() != 2 { return Err("CONTAINS requires 2 arguments".into()); }
-                    let str_term = expr_to_term(&args[0])?;
+                    let operand = string_operand(&args[0], query, bindings, hidden)?;
                     let needle = match &args[1] {
                         Expression::Literal(lit) => lit.value().to_string(),
                         _ => return Err(
                             "CONTAINS round-2 requires the second argument to be a string literal".into(),
                         ),
                     };
-                    string_op_contains(&str_term, &needle, query, bindings, hidden)
+                    string_op_contains(&operand, &needle, hidden)
+                }
+                // String-valued functions evaluate to their result's
+                // term hash.
+                Function::Replace
+                | Function::EncodeForUri
+                | Function::Md5
+                | Function::Sha1
+                | Function::Sha256
+                | Function::Sha384
+                | Function::Sha512 => string_valued_term_hash(expr, query, bindings, hidden),
+                Function::Iri | Function::BNode | Function::StrDt | Function::StrLang => {
+                    term_constructor_hash(expr, query, bindings, hidden)
                 }
                 Function::StrEnds => {
                     Err(
@@ -831,6 +1508,17 @@ fn expr_to_noir_code(
             }
         }
         
+        Expression::If(cond, then, otherwise) => if_value(cond, then, otherwise, query, bindings, hidden),
+        Expression::Coalesce(args) => match coalesce_choice(args, query, bindings) {
+            Some(arg) => expr_to_noir_code(arg, query, bindings, hidden),
+            None => Err(format!(
+                "COALESCE has no argument that can evaluate without error here (every one \
+                 depends on an unbound variable): {:?}. Its value is an error in every \
+                 solution, so there is nothing to compute.",
+                expr
+            )),
+        },
+
         _ => Err(format!("Cannot convert complex expression to Noir code: {:?}", expr)),
     }
 }
@@ -840,7 +1528,6 @@ enum ComparisonType {
     Numeric,
     String,
     Boolean,
-    DateTime,
     Unknown,
 }
 
@@ -853,7 +1540,6 @@ fn datatype_to_comparison_type(datatype: &str) -> ComparisonType {
             | "unsignedInt" | "unsignedLong" | "unsignedShort" | "unsignedByte" => ComparisonType::Numeric,
             "string" | "normalizedString" | "token" | "language" | "Name" | "NCName" | "NMTOKEN" => ComparisonType::String,
             "boolean" => ComparisonType::Boolean,
-            "dateTime" | "date" | "time" => ComparisonType::DateTime,
             _ => ComparisonType::Unknown,
         }
     } else {
@@ -875,7 +1561,6 @@ fn expr_comparison_type(expr: &Expression) -> ComparisonType {
                     | "unsignedInt" | "unsignedLong" | "unsignedShort" | "unsignedByte" => ComparisonType::Numeric,
                     "string" | "normalizedString" | "token" => ComparisonType::String,
                     "boolean" => ComparisonType::Boolean,
-                    "dateTime" | "date" | "time" => ComparisonType::DateTime,
                     _ => ComparisonType::Unknown,
                 }
             } else {
@@ -901,29 +1586,33 @@ fn determine_comparison_type(a: &Expression, b: &Expression) -> ComparisonType {
 fn handle_function_equality(
     func_expr: &Expression,
     other_expr: &Expression,
-    _query: &QueryInfo,
-    _bindings: &BTreeMap<String, Term>,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
     hidden: &mut Vec<serde_json::Value>,
 ) -> Result<Option<String>, String> {
     if let Expression::FunctionCall(func, args) = func_expr {
         match func {
             Function::Lang => {
-                // LANG(?x) = "en" -> compare language tag
+                // LANG(?x) = "en" -> compare the bound language tag,
+                // ignoring case (tags are case-insensitive, BCP 47).
                 if args.len() != 1 {
                     return Err("LANG requires 1 argument".into());
                 }
-                let term = expr_to_term(&args[0])?;
-                let idx = push_hidden(hidden, "lang", &term);
-                
-                // Get the comparison value
-                let cmp_value = match other_expr {
-                    Expression::Literal(lit) => lit.value().to_string(),
+                let expected = match other_expr {
+                    Expression::Literal(lit) => lit.value().to_ascii_lowercase(),
                     _ => return Err("LANG comparison requires a string literal".into()),
                 };
-                
+                if let Expression::Literal(lit) = &args[0] {
+                    let equal = lit.language().unwrap_or("").eq_ignore_ascii_case(&expected);
+                    return Ok(Some(if equal { "true" } else { "false" }.into()));
+                }
+                let tag = language_tag_operand(&args[0], query, bindings, hidden)?;
                 Ok(Some(format!(
-                    "hidden[{}] == consts::encode_string(\"{}\")",
-                    idx, cmp_value
+                    "{{ {c}let expected: [u8; {n}] = {e}; lang::tag_equals({w}.bytes, {w}.length, expected) }}",
+                    c = tag.checks,
+                    n = expected.len(),
+                    e = format_bytes_array(expected.as_bytes()),
+                    w = tag.witness
                 )))
             }
             Function::Str => {
@@ -981,6 +1670,16 @@ pub(crate) fn filter_to_noir(
 ) -> Result<String, String> {
     match expr {
         Expression::Equal(a, b) => {
+            // Dates, times and durations compare by value, so instants
+            // written in different timezones are equal.
+            if let Some(result) = temporal_comparison(expr, a, b, query, bindings, hidden)? {
+                return Ok(result);
+            }
+            // REPLACE / ENCODE_FOR_URI results (and variables BIND-ed to
+            // them) are terms: compare by term hash.
+            if let Some(result) = computed_term_equality(a, b, query, bindings, hidden)? {
+                return Ok(result);
+            }
             // Handle function call comparisons (e.g., LANG(?x) = "en")
             if let Some(result) = handle_function_equality(a, b, query, bindings, hidden)? {
                 return Ok(result);
@@ -988,6 +1687,14 @@ pub(crate) fn filter_to_noir(
             if let Some(result) = handle_function_equality(b, a, query, bindings, hidden)? {
                 return Ok(result);
             }
+            // Decimal equality compares the fixed-point values, so
+            // `1.50 = 1.5` and `?price = 2` hold numerically.
+            let (type_a, type_b) = (infer_expression_type(a), infer_expression_type(b));
+            if promote_numeric_types(type_a, type_b) == NumericSourceType::Decimal {
+                let left = decimal_operand(a, query, bindings, hidden)?;
+                let right = decimal_operand(b, query, bindings, hidden)?;
+                return Ok(format!("{} == {}", left, right));
+            }
             
             // Try to use expr_to_noir_code for complex expressions (like function calls)
             let left_code = match expr_to_noir_code(a, query, bindings, hidden) {
@@ -1030,18 +1737,23 @@ pub(crate) fn filter_to_noir(
         // Note: spargebra doesn't have NotEqual, inequality is typically !(a = b)
         // This case handles if we manually construct such an expression
         Expression::Not(inner) => {
+            if let Expression::In(lhs, members) = inner.as_ref() {
+                return in_to_noir(lhs, members, true, query, bindings, hidden);
+            }
             let inner_expr = filter_to_noir(inner, query, bindings, hidden)?;
             Ok(format!("!({})", inner_expr))
         }
 
         Expression::Greater(a, b) | Expression::GreaterOrEqual(a, b) |
         Expression::Less(a, b) | Expression::LessOrEqual(a, b) => {
+            if let Some(result) = temporal_comparison(expr, a, b, query, bindings, hidden)? {
+                return Ok(result);
+            }
             let cmp_type = determine_comparison_type(a, b);
             match cmp_type {
                 ComparisonType::Numeric => numeric_comparison(expr, a, b, query, bindings, hidden),
                 ComparisonType::String => string_comparison(expr, a, b, query, bindings, hidden),
                 ComparisonType::Boolean => boolean_comparison(expr, a, b, query, bindings, hidden),
-                ComparisonType::DateTime => datetime_comparison(expr, a, b, query, bindings, hidden),
                 ComparisonType::Unknown => numeric_comparison(expr, a, b, query, bindings, hidden),
             }
         }
@@ -1059,15 +1771,36 @@ pub(crate) fn filter_to_noir(
         }
 
         Expression::Bound(v) => {
-            let var_name = v.as_str();
-            if query.variables.contains(&var_name.to_string()) || bindings.contains_key(var_name) {
+            if is_statically_bound(v.as_str(), query, bindings) {
                 Ok("true".into())
             } else {
                 Ok("false".into())
             }
         }
 
+        Expression::In(lhs, members) => in_to_noir(lhs, members, false, query, bindings, hidden),
+
+        Expression::If(cond, then, otherwise) => {
+            if is_static_error(cond, query, bindings) {
+                return Ok("false".into());
+            }
+            let cond = filter_to_noir(cond, query, bindings, hidden)?;
+            let then = boolean_or_error(then, query, bindings, hidden)?;
+            let otherwise = boolean_or_error(otherwise, query, bindings, hidden)?;
+            Ok(format!("(if ({}) {{ {} }} else {{ {} }})", cond, then, otherwise))
+        }
+
+        Expression::Coalesce(args) => match coalesce_choice(args, query, bindings) {
+            Some(arg) => filter_to_noir(arg, query, bindings, hidden),
+            // Every argument is an error: so is COALESCE, and a FILTER
+            // error rejects the solution.
+            None => Ok("false".into()),
+        },
+
         Expression::SameTerm(a, b) => {
+            if let Some(result) = computed_term_equality(a, b, query, bindings, hidden)? {
+                return Ok(result);
+            }
             let left = expr_to_term(a)?;
             let right = expr_to_term(b)?;
             Ok(format!(
@@ -1092,45 +1825,52 @@ pub(crate) fn filter_to_noir(
                     type_check(&args[0], 2, query, bindings, hidden)
                 }
                 Function::LangMatches => {
-                    // LANGMATCHES(LANG(?x), "en") or LANGMATCHES(?lang, "*")
+                    // LANGMATCHES(LANG(?x), "en-GB") or LANGMATCHES(LANG(?x), "*")
                     if args.len() != 2 {
                         return Err("LANGMATCHES requires 2 arguments".into());
                     }
-                    
-                    // First arg is usually LANG(?x), extract the variable
-                    let lang_term = if let Expression::FunctionCall(Function::Lang, lang_args) = &args[0] {
-                        if lang_args.len() != 1 {
-                            return Err("LANG requires 1 argument".into());
-                        }
-                        expr_to_term(&lang_args[0])?
-                    } else {
-                        expr_to_term(&args[0])?
-                    };
-                    
-                    // Second arg is the language pattern
-                    let pattern = match &args[1] {
-                        Expression::Literal(lit) => lit.value().to_string(),
+                    let range = match &args[1] {
+                        Expression::Literal(lit) => lit.value().to_ascii_lowercase(),
                         _ => return Err("LANGMATCHES requires a string pattern".into()),
                     };
-                    
-                    let idx = push_hidden(hidden, "lang", &lang_term);
-                    
-                    // Handle the "*" wildcard (matches any non-empty language tag)
-                    if pattern == "*" {
-                        // Language tag is non-empty (not the empty string)
-                        Ok(format!(
-                            "hidden[{}] != consts::encode_string(\"\")",
-                            idx
-                        ))
-                    } else {
-                        // Exact match or prefix match for primary subtag
-                        // For simplicity, we do exact match on the primary subtag
-                        let primary = pattern.split('-').next().unwrap_or(&pattern).to_lowercase();
-                        Ok(format!(
-                            "hidden[{}] == consts::encode_string(\"{}\")",
-                            idx, primary
-                        ))
+                    let lang_arg = match &args[0] {
+                        Expression::FunctionCall(Function::Lang, lang_args) => {
+                            if lang_args.len() != 1 {
+                                return Err("LANG requires 1 argument".into());
+                            }
+                            &lang_args[0]
+                        }
+                        Expression::Literal(lit) => {
+                            let matched = language_range_matches(lit.value(), &range);
+                            return Ok(if matched { "true" } else { "false" }.into());
+                        }
+                        _ => {
+                            return Err(
+                                "LANGMATCHES: the first argument must be LANG(...) or a string \
+                                 literal -- the circuit reads a language tag only from the \
+                                 literal it belongs to"
+                                    .into(),
+                            )
+                        }
+                    };
+                    if let Expression::Literal(lit) = lang_arg {
+                        let matched = language_range_matches(lit.language().unwrap_or(""), &range);
+                        return Ok(if matched { "true" } else { "false" }.into());
                     }
+                    let tag = language_tag_operand(lang_arg, query, bindings, hidden)?;
+                    if range == "*" {
+                        return Ok(format!(
+                            "{{ {}lang::matches_wildcard({}.length) }}",
+                            tag.checks, tag.witness
+                        ));
+                    }
+                    Ok(format!(
+                        "{{ {c}let range: [u8; {n}] = {r}; lang::basic_filter({w}.bytes, {w}.length, range) }}",
+                        c = tag.checks,
+                        n = range.len(),
+                        r = format_bytes_array(range.as_bytes()),
+                        w = tag.witness
+                    ))
                 }
                 
                 // Numeric functions — type-aware per round 2 §6.2 (Q1
@@ -1159,12 +1899,12 @@ pub(crate) fn filter_to_noir(
                 // round-3 follow-ups.
                 Function::StrLen => {
                     if args.len() != 1 { return Err("STRLEN requires 1 argument".into()); }
-                    let term = expr_to_term(&args[0])?;
-                    string_op_strlen(&term, query, bindings)
+                    let operand = string_operand(&args[0], query, bindings, hidden)?;
+                    string_op_strlen(&operand)
                 }
                 Function::Contains => {
                     if args.len() != 2 { return Err("CONTAINS requires 2 arguments".into()); }
-                    let str_term = expr_to_term(&args[0])?;
+                    let operand = string_operand(&args[0], query, bindings, hidden)?;
                     // The needle must be a literal (compile-time constant).
                     let needle = match &args[1] {
                         Expression::Literal(lit) => lit.value().to_string(),
@@ -1173,18 +1913,18 @@ pub(crate) fn filter_to_noir(
                              (variable needle would need a private-byte-array lowering deferred to round 3)".into(),
                         ),
                     };
-                    string_op_contains(&str_term, &needle, query, bindings, hidden)
+                    string_op_contains(&operand, &needle, hidden)
                 }
                 Function::StrStarts => {
                     if args.len() != 2 { return Err("STRSTARTS requires 2 arguments".into()); }
-                    let str_term = expr_to_term(&args[0])?;
+                    let operand = string_operand(&args[0], query, bindings, hidden)?;
                     let prefix = match &args[1] {
                         Expression::Literal(lit) => lit.value().to_string(),
                         _ => return Err(
                             "STRSTARTS round-2 requires the second argument to be a string literal".into(),
                         ),
                     };
-                    string_op_strstarts(&str_term, &prefix, query, bindings)
+                    string_op_strstarts(&operand, &prefix)
                 }
                 Function::StrEnds => {
                     // Round 2 deliberately defers STRENDS -- the round-2 brief
@@ -1342,12 +2082,26 @@ fn numeric_comparison(
         }
     }
 
-    // Try to convert to Noir code (handles function calls)
+    // Decimal comparisons are exact over the fixed-point values.
+    if promote_numeric_types(type_a, type_b) == NumericSourceType::Decimal {
+        let left = decimal_operand(a, query, bindings, hidden)?;
+        let right = decimal_operand(b, query, bindings, hidden)?;
+        let op = match expr {
+            Expression::Greater(_, _) => ">",
+            Expression::GreaterOrEqual(_, _) => ">=",
+            Expression::Less(_, _) => "<",
+            Expression::LessOrEqual(_, _) => "<=",
+            _ => return Err("Invalid comparison operator".into()),
+        };
+        return Ok(format!("{} {} {}", left, op, right));
+    }
+
+    // Try to convert to Noir code (handles function calls)
     let left_code = expr_to_noir_code(a, query, bindings, hidden)?;
     let right_code = expr_to_noir_code(b, query, bindings, hidden)?;
 
     // Determine if we need float/double comparisons
-    // Promote to the widest type: double > float > integer
+    // Promote to the widest type: double > float > decimal > integer
     let use_double = matches!(type_a, Some(NumericSourceType::Double)) || 
                      matches!(type_b, Some(NumericSourceType::Double));
     let use_float = !use_double && 
@@ -1361,6 +2115,9 @@ fn numeric_comparison(
             Some(NumericSourceType::Float) => {
                 format!("xpath::XsdDouble::from_float(xpath::XsdFloat::from_bits({} as u32))", left_code)
             }
+            Some(NumericSourceType::Decimal) => {
+                format!("xpath::decimal_to_double(({}) as i64)", left_code)
+            }
             Some(NumericSourceType::Integer) | None => {
                 format!("xpath::cast_integer_to_double(({}) as i64)", left_code)
             }
@@ -1370,6 +2127,9 @@ fn numeric_comparison(
             Some(NumericSourceType::Float) => {
                 format!("xpath::XsdDouble::from_float(xpath::XsdFloat::from_bits({} as u32))", right_code)
             }
+            Some(NumericSourceType::Decimal) => {
+                format!("xpath::decimal_to_double(({}) as i64)", right_code)
+            }
             Some(NumericSourceType::Integer) | None => {
                 format!("xpath::cast_integer_to_double(({}) as i64)", right_code)
             }
@@ -1400,6 +2160,7 @@ fn numeric_comparison(
         // Use float comparison functions
         let left_float = match type_a {
             Some(NumericSourceType::Float) => format!("xpath::XsdFloat::from_bits({} as u32)", left_code),
+            Some(NumericSourceType::Decimal) => format!("xpath::decimal_to_float(({}) as i64)", left_code),
             Some(NumericSourceType::Integer) | None => {
                 format!("xpath::cast_integer_to_float(({}) as i64)", left_code)
             }
@@ -1407,6 +2168,7 @@ fn numeric_comparison(
         };
         let right_float = match type_b {
             Some(NumericSourceType::Float) => format!("xpath::XsdFloat::from_bits({} as u32)", right_code),
+            Some(NumericSourceType::Decimal) => format!("xpath::decimal_to_float(({}) as i64)", right_code),
             Some(NumericSourceType::Integer) | None => {
                 format!("xpath::cast_integer_to_float(({}) as i64)", right_code)
             }
@@ -1506,30 +2268,6 @@ fn boolean_comparison(
     Ok(cmp)
 }
 
-fn datetime_comparison(
-    expr: &Expression,
-    a: &Expression,
-    b: &Expression,
-    _query: &QueryInfo,
-    _bindings: &BTreeMap<String, Term>,
-    hidden: &mut Vec<serde_json::Value>,
-) -> Result<String, String> {
-    let left = expr_to_term(a)?;
-    let right = expr_to_term(b)?;
-    let left_idx = push_hidden(hidden, "datetime_value", &left);
-    let right_idx = push_hidden(hidden, "datetime_value", &right);
-
-    let cmp = match expr {
-        Expression::Less(_, _) => format!("(hidden[{}] as i64) < (hidden[{}] as i64)", left_idx, right_idx),
-        Expression::LessOrEqual(_, _) => format!("(hidden[{}] as i64) <= (hidden[{}] as i64)", left_idx, right_idx),
-        Expression::Greater(_, _) => format!("(hidden[{}] as i64) > (hidden[{}] as i64)", left_idx, right_idx),
-        Expression::GreaterOrEqual(_, _) => format!("(hidden[{}] as i64) >= (hidden[{}] as i64)", left_idx, right_idx),
-        _ => return Err("Invalid comparison operator".into()),
-    };
-
-    Ok(cmp)
-}
-
 fn term_to_hidden_json(term: &Term) -> serde_json::Value {
     match term {
         Term::Variable(name) => serde_json::json!({"type": "variable", "value": name}),
@@ -1544,6 +2282,10 @@ fn term_to_hidden_json(term: &Term) -> serde_json::Value {
             "type": "static",
             "value": { "termType": "DefaultGraph" },
         }),
+        Term::Computed(expr) => serde_json::json!({
+            "type": "computed",
+            "value": expr.to_string(),
+        }),
     }
 }
 
@@ -1589,6 +2331,7 @@ fn term_to_bgp_location(term: &Term, query: &QueryInfo, bindings: &BTreeMap<Stri
         }
         Term::Static(_) => None,
         Term::DefaultGraph => None,
+        Term::Computed(_) => None,
     }
 }
 
@@ -1635,41 +2378,107 @@ fn format_bytes_array(bytes: &[u8]) -> String {
 /// needle isn't present, the prover supplies `0` and the constraint
 /// `position + needle_len <= length` will fail (along with the byte
 /// equality), surfacing the absence as a proof failure.
-fn push_contains_position(hidden: &mut Vec<serde_json::Value>, term: &Term, needle: &str) -> usize {
-    let idx = hidden.len();
-    let term_json = match term {
-        Term::Variable(name) => serde_json::json!({"type": "variable", "value": name}),
-        Term::Input(i, j) => serde_json::json!({"type": "input", "value": [i, j]}),
-        Term::Static(gt) => serde_json::json!({"type": "static", "value": ground_term_to_json(gt)}),
-        Term::DefaultGraph => serde_json::json!({"type": "static", "value": {"termType": "DefaultGraph"}}),
-    };
+fn push_contains_position(hidden: &mut Vec<serde_json::Value>, input: serde_json::Value, needle: &str) -> usize {
+    let idx = next_hidden_index(hidden);
     hidden.push(serde_json::json!({
         "type": "customComputed",
         "computedType": "contains_position",
-        "input": term_json,
+        "input": input,
         "needle": needle,
     }));
     idx
 }
 
+/// A string operand resolved to a `TermWitness` expression plus the
+/// statements that bind its bytes. `checks` is emitted verbatim (it is
+/// either empty or ends in `"; "`) ahead of any code reading `witness`,
+/// inside the same Noir block.
+struct StringOperand {
+    checks: String,
+    witness: String,
+    /// Prover-side description of the operand, in the `input` shape
+    /// used by `hiddenInputs` / `computedWitnesses` entries.
+    input: serde_json::Value,
+}
+
+/// Resolve a string operator's operand to its witness.
+///
+/// - A variable bound to a BGP slot reads `bgp[i].terms[j]` after
+///   `bind_term_bytes_plain_string_literal`.
+/// - A variable bound by a string-valued `BIND` reads the emitter's
+///   `bind_<var>` local; that witness was bound when the local was
+///   initialised, so no further checks are needed.
+/// - A nested `REPLACE` / `ENCODE_FOR_URI` call reads its fresh
+///   `computed[k]` witness, carrying the nested call's checks along; a
+///   nested hash function reads its in-circuit hex digest witness.
+fn string_operand(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<StringOperand, String> {
+    match expr {
+        Expression::Variable(v)
+            if computed_binding_expr(v.as_str(), query, bindings).is_some_and(is_term_constructor) =>
+        {
+            Err(format!(
+                "?{} is bound to an IRI() / BNODE() / STRDT() / STRLANG() result, which is not a \
+                 plain string -- string operators cannot read it",
+                v.as_str()
+            ))
+        }
+        _ if is_term_constructor(expr) => Err(
+            "IRI() / BNODE() / STRDT() / STRLANG() do not return a plain string -- string \
+             operators cannot read their result"
+                .into(),
+        ),
+        Expression::Variable(v)
+            if computed_binding_expr(v.as_str(), query, bindings).is_some_and(is_temporal_valued) =>
+        {
+            Err(format!(
+                "?{} is bound to a date, time or duration, which is not a plain string -- string \
+                 operators cannot read it",
+                v.as_str()
+            ))
+        }
+        Expression::Variable(v) if is_computed_binding(v.as_str(), query, bindings) => Ok(StringOperand {
+            checks: String::new(),
+            witness: computed_binding_local(v.as_str()),
+            input: term_to_hidden_json(&Term::Variable(v.as_str().to_string())),
+        }),
+        _ if is_string_valued_function(expr) => string_valued_function(expr, query, bindings, hidden),
+        _ => {
+            let term = expr_to_term(expr)?;
+            let witness = term_witness_ref(&term, query, bindings)?;
+            Ok(StringOperand {
+                checks: format!(
+                    "utils::bind_term_bytes_plain_string_literal({w}, utils::empty_string_lexical_hash(), utils::xsd_string_datatype_hash()); ",
+                    w = witness
+                ),
+                witness,
+                input: term_to_hidden_json(&term),
+            })
+        }
+    }
+}
+
 /// `STRLEN(?x)` -> `{ binding; bgp[i].terms[j].length as Field }`.
 /// The binding asserts `bgp[i].terms[j]` is a plain xsd:string literal
 /// whose lexical preimage is `bytes[0..length]`; `length` is then the
 /// SPARQL string length (over UTF-8 bytes -- a known limitation versus
 /// SPARQL's intended Unicode-codepoint semantics, documented in
 /// `spec/encoding.md` sec.6.6).
-fn string_op_strlen(term: &Term, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> Result<String, String> {
-    let witness = term_witness_ref(term, query, bindings)?;
+fn string_op_strlen(operand: &StringOperand) -> Result<String, String> {
     Ok(format!(
-        "{{ utils::bind_term_bytes_plain_string_literal({w}, utils::empty_string_lexical_hash(), utils::xsd_string_datatype_hash()); {w}.length as Field }}",
-        w = witness
+        "{{ {c}{w}.length as Field }}",
+        c = operand.checks,
+        w = operand.witness
     ))
 }
 
 /// `STRSTARTS(?x, "prefix")` -> `{ binding; utils::string_starts_with(witness, [bytes...], len) }`.
 /// The prefix is folded in at compile time as a byte array.
-fn string_op_strstarts(term: &Term, prefix: &str, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> Result<String, String> {
-    let witness = term_witness_ref(term, query, bindings)?;
+fn string_op_strstarts(operand: &StringOperand, prefix: &str) -> Result<String, String> {
     let prefix_bytes = prefix.as_bytes();
     let prefix_len = prefix_bytes.len();
     if prefix_len == 0 {
@@ -1680,14 +2489,12 @@ fn string_op_strstarts(term: &Term, prefix: &str, query: &QueryInfo, bindings: &
         // (NamedNode, language-tagged literal, numeric, etc.) and the
         // expression would silently pass without binding the bytes.
         // Roborev review 2026-05-04 (medium).
-        return Ok(format!(
-            "{{ utils::bind_term_bytes_plain_string_literal({w}, utils::empty_string_lexical_hash(), utils::xsd_string_datatype_hash()); true }}",
-            w = witness,
-        ));
+        return Ok(format!("{{ {c}true }}", c = operand.checks));
     }
     Ok(format!(
-        "{{ utils::bind_term_bytes_plain_string_literal({w}, utils::empty_string_lexical_hash(), utils::xsd_string_datatype_hash()); let prefix: [u8; {n}] = {arr}; utils::string_starts_with::<{n}>({w}, prefix, {n}) }}",
-        w = witness,
+        "{{ {c}let prefix: [u8; {n}] = {arr}; utils::string_starts_with::<{n}>({w}, prefix, {n}) }}",
+        c = operand.checks,
+        w = operand.witness,
         arr = format_bytes_array(prefix_bytes),
         n = prefix_len,
     ))
@@ -1696,13 +2503,10 @@ fn string_op_strstarts(term: &Term, prefix: &str, query: &QueryInfo, bindings: &
 /// `CONTAINS(?x, "needle")` -> `{ binding; utils::string_contains(witness, [bytes...], needle_len, hidden[<pos_idx>] as u32) }`.
 /// The prover supplies the matching position via `hidden[]`.
 fn string_op_contains(
-    term: &Term,
+    operand: &StringOperand,
     needle: &str,
-    query: &QueryInfo,
-    bindings: &BTreeMap<String, Term>,
     hidden: &mut Vec<serde_json::Value>,
 ) -> Result<String, String> {
-    let witness = term_witness_ref(term, query, bindings)?;
     let needle_bytes = needle.as_bytes();
     let needle_len = needle_bytes.len();
     if needle_len == 0 {
@@ -1711,215 +2515,1552 @@ fn string_op_contains(
         // fast path: keep the binding so the structural check on `?x`
         // still runs; only the substring search is short-circuited.
         // Roborev review 2026-05-04 (medium).
-        return Ok(format!(
-            "{{ utils::bind_term_bytes_plain_string_literal({w}, utils::empty_string_lexical_hash(), utils::xsd_string_datatype_hash()); true }}",
-            w = witness,
-        ));
+        return Ok(format!("{{ {c}true }}", c = operand.checks));
     }
-    let pos_idx = push_contains_position(hidden, term, needle);
+    let pos_idx = push_contains_position(hidden, operand.input.clone(), needle);
     Ok(format!(
-        "{{ utils::bind_term_bytes_plain_string_literal({w}, utils::empty_string_lexical_hash(), utils::xsd_string_datatype_hash()); let needle: [u8; {n}] = {arr}; utils::string_contains::<{n}>({w}, needle, {n}, hidden[{p}] as u32) }}",
-        w = witness,
+        "{{ {c}let needle: [u8; {n}] = {arr}; utils::string_contains::<{n}>({w}, needle, {n}, hidden[{p}] as u32) }}",
+        c = operand.checks,
+        w = operand.witness,
         arr = format_bytes_array(needle_bytes),
         n = needle_len,
         p = pos_idx,
     ))
 }
 
-fn push_hidden(hidden: &mut Vec<serde_json::Value>, kind: &str, term: &Term) -> usize {
-    let idx = hidden.len();
-    hidden.push(serde_json::json!({
-        "type": "customComputed",
-        "computedType": kind,
-        "input": term_to_hidden_json(term)
-    }));
-    idx
+// =============================================================================
+// STRING-VALUED FUNCTIONS (REPLACE / ENCODE_FOR_URI / MD5 / SHA*)
+// =============================================================================
+//
+// Functions that *produce* a string cannot point at a BGP slot for
+// their result. Each call gets a fresh `TermWitness` in the private
+// `computed` input array; the emitted code binds its hash to its bytes
+// (`utils::bind_computed_plain_string_literal`) and checks the byte
+// relation against the input witness (`utils::string_replace` /
+// `utils::encode_for_uri`). The result's identity is `computed[k].hash`
+// -- a plain xsd:string literal term hash, comparable with dataset
+// terms and query literals. Computed-witness entries share the
+// `hidden` vector with `Field` hidden inputs (so every lowering keeps
+// its single `&mut Vec` parameter) and are told apart by their `type`;
+// the emitter partitions them out.
+//
+// The hash functions need no prover input: the hex digest is recomputed
+// in-circuit from the bound input (`utils::sha256_hex` etc.), compared
+// by `utils::hex_string_term_hash`, and wrapped in a witness by
+// `utils::hex_string_witness` only when a further string operator or a
+// BIND reads its bytes.
+
+/// `type` of the `hidden` entries that describe a computed witness
+/// rather than a `Field` hidden input.
+pub(crate) const COMPUTED_WITNESS_TYPE: &str = "computedWitness";
+
+pub(crate) fn is_computed_witness(entry: &serde_json::Value) -> bool {
+    entry.get("type").and_then(|v| v.as_str()) == Some(COMPUTED_WITNESS_TYPE)
 }
 
-fn push_hidden_comparison(hidden: &mut Vec<serde_json::Value>, kind: &str, left: &Term, right: &Term) -> usize {
-    let idx = hidden.len();
-    hidden.push(serde_json::json!({
-        "type": "customComputed",
-        "computedType": kind,
-        "inputs": [term_to_hidden_json(left), term_to_hidden_json(right)]
-    }));
+/// Index the next `Field` hidden input will occupy in `hidden[]`.
+fn next_hidden_index(hidden: &[serde_json::Value]) -> usize {
+    hidden.iter().filter(|h| !is_computed_witness(h)).count()
+}
+
+/// Record a computed-witness entry and return its index in `computed[]`.
+fn push_computed_witness(hidden: &mut Vec<serde_json::Value>, entry: serde_json::Value) -> usize {
+    let idx = hidden.iter().filter(|h| is_computed_witness(h)).count();
+    hidden.push(entry);
     idx
 }
 
-// =============================================================================
-// NUMERIC ARITHMETIC — round 2 §6.2
-// =============================================================================
-//
-// SPARQL 1.1 §17.3 numeric promotion: the result type is the widest of
-// the operand types (integer < decimal < float < double). Here we:
-// 1. infer each operand's static type via `infer_expression_type`,
-// 2. promote both to the wider type,
-// 3. emit the matching `xpath::numeric_*_{int,float,double}` call.
-//
-// Per Q7 decision (2026-05-03) `xsd:decimal` shares the integer code
-// path — the precision floor is the prover's field-element width.
+/// Name of the `checkBinding` local holding a string-valued BIND's
+/// computed witness.
+pub(crate) fn computed_binding_local(variable: &str) -> String {
+    format!("bind_{}", variable)
+}
 
-fn promote_numeric_types(
-    a: Option<NumericSourceType>,
-    b: Option<NumericSourceType>,
-) -> NumericSourceType {
-    use NumericSourceType::*;
-    match (a, b) {
-        (Some(Double), _) | (_, Some(Double)) => Double,
-        (Some(Float), _) | (_, Some(Float)) => Float,
-        _ => Integer,
-    }
+/// True iff `expr` is a function call whose result is a fresh computed
+/// string witness.
+pub(crate) fn is_string_valued_function(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::FunctionCall(Function::Replace | Function::EncodeForUri | Function::Tz, _)
+    ) || hash_function_name(expr).is_some()
+        || is_string_cast(expr)
 }
 
-/// Coerce an operand to the result type's IEEE 754 wrapper. Integer
-/// operands are routed through `xpath::cast_integer_to_*`; float
-/// operands are widened to double when the result type is double.
-fn coerce_numeric_operand(
-    code: &str,
-    from: Option<NumericSourceType>,
-    to: NumericSourceType,
-) -> String {
-    use NumericSourceType::*;
-    match (from, to) {
-        (_, Integer) => format!("({}) as i64", code),
-        (Some(Double), Double) => format!("xpath::XsdDouble::from_bits({} as u64)", code),
-        (Some(Float), Double) => format!(
-            "xpath::XsdDouble::from_float(xpath::XsdFloat::from_bits({} as u32))",
-            code
-        ),
-        (Some(Integer), Double) | (None, Double) => {
-            format!("xpath::cast_integer_to_double(({}) as i64)", code)
-        }
-        (Some(Float), Float) => format!("xpath::XsdFloat::from_bits({} as u32)", code),
-        (Some(Integer), Float) | (None, Float) => {
-            format!("xpath::cast_integer_to_float(({}) as i64)", code)
+/// True iff `expr` is `xsd:string(x)` of a number or boolean computed
+/// in-circuit. Constant arguments fold at transform time instead.
+fn is_string_cast(expr: &Expression) -> bool {
+    match expr {
+        Expression::FunctionCall(Function::Custom(iri), args) => {
+            iri.as_str().strip_prefix(XSD) == Some("string")
+                && args.len() == 1
+                && !matches!(fold_xsd_cast("string", &args[0]), Ok(Some(_)))
+                && cast_source_kind(&args[0]).is_some()
         }
-        // Double → Float is a narrowing cast we don't expect to hit
-        // because promotion always picks the wider; if it ever did we'd
-        // truncate via cast_double_to_float.
-        (Some(Double), Float) => format!(
-            "xpath::cast_double_to_float(xpath::XsdDouble::from_bits({} as u64))",
-            code
+        _ => false,
+    }
+}
+
+/// Lower `xsd:string(x)` of an in-circuit number or boolean to a fresh
+/// `computed[k]` witness holding its XPath canonical string, checked
+/// against the value by `xpath::is_canonical_*_lexical`.
+fn string_cast(
+    arg: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<StringOperand, String> {
+    let kind = cast_source_kind(arg).ok_or_else(|| format!("{} is not a number or a boolean", arg))?;
+    let (source_type, check, width) = match kind {
+        CastSourceKind::Integer => ("integer", "is_canonical_integer_lexical", " as i64"),
+        CastSourceKind::Decimal => ("decimal", "is_canonical_decimal_lexical", " as i64"),
+        CastSourceKind::Float => ("float", "is_canonical_float_lexical", " as u32"),
+        CastSourceKind::Double => ("double", "is_canonical_double_lexical", " as u64"),
+        CastSourceKind::Boolean => ("boolean", "is_canonical_boolean_lexical", ""),
+    };
+    let input = match arg {
+        Expression::Variable(v) => term_to_hidden_json(&Term::Variable(v.as_str().to_string())),
+        _ => serde_json::json!({"type": "expression", "value": arg.to_string()}),
+    };
+    let k = push_computed_witness(
+        hidden,
+        serde_json::json!({
+            "type": COMPUTED_WITNESS_TYPE,
+            "computedType": "cast_string",
+            "sourceType": source_type,
+            "input": input,
+        }),
+    );
+    let value = expr_to_noir_code(arg, query, bindings, hidden)?;
+    Ok(StringOperand {
+        checks: format!(
+            "utils::bind_computed_plain_string_literal(computed[{k}]); \
+             assert(xpath::{c}(computed[{k}].bytes, computed[{k}].length, ({v}){t})); ",
+            k = k,
+            c = check,
+            v = value,
+            t = width,
         ),
+        witness: format!("computed[{}]", k),
+        input: serde_json::json!({"type": "computed", "value": k}),
+    })
+}
+
+/// `utils::<name>_hex` helper for a SPARQL hash function call.
+fn hash_function_name(expr: &Expression) -> Option<&'static str> {
+    match expr {
+        Expression::FunctionCall(Function::Md5, _) => Some("md5"),
+        Expression::FunctionCall(Function::Sha1, _) => Some("sha1"),
+        Expression::FunctionCall(Function::Sha256, _) => Some("sha256"),
+        Expression::FunctionCall(Function::Sha384, _) => Some("sha384"),
+        Expression::FunctionCall(Function::Sha512, _) => Some("sha512"),
+        _ => None,
     }
 }
 
-/// Emit `?x ⊕ ?y` for ⊕ ∈ {add, subtract, multiply, divide}.
-fn emit_numeric_binary(
-    op: &str,
-    a: &Expression,
-    b: &Expression,
+/// Lower a hash function call's argument and return it with the
+/// `utils::<name>_hex(...)` digest expression.
+fn hash_function_digest(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<(StringOperand, String), String> {
+    let name = hash_function_name(expr).ok_or_else(|| format!("Not a hash function call: {:?}", expr))?;
+    let args = match expr {
+        Expression::FunctionCall(_, args) => args,
+        _ => unreachable!("hash_function_name only matches function calls"),
+    };
+    if args.len() != 1 {
+        return Err(format!("{} requires 1 argument", name.to_uppercase()));
+    }
+    let input = string_operand(&args[0], query, bindings, hidden)?;
+    let digest = format!("utils::{}_hex({})", name, input.witness);
+    Ok((input, digest))
+}
+
+/// Term-hash code for a string-valued function call. Digests are hashed
+/// straight from their hex bytes, so SHA384 / SHA512 compare without
+/// having to fit in `STRING_LEN_MAX`.
+fn string_valued_term_hash(
+    expr: &Expression,
     query: &QueryInfo,
     bindings: &BTreeMap<String, Term>,
     hidden: &mut Vec<serde_json::Value>,
 ) -> Result<String, String> {
-    let left_code = expr_to_noir_code(a, query, bindings, hidden)?;
-    let right_code = expr_to_noir_code(b, query, bindings, hidden)?;
+    if hash_function_name(expr).is_some() {
+        let (input, digest) = hash_function_digest(expr, query, bindings, hidden)?;
+        return Ok(format!("{{ {}utils::hex_string_term_hash({}) }}", input.checks, digest));
+    }
+    let operand = string_valued_function(expr, query, bindings, hidden)?;
+    Ok(format!("{{ {}{}.hash }}", operand.checks, operand.witness))
+}
 
-    let type_a = infer_expression_type(a);
-    let type_b = infer_expression_type(b);
-    let result = promote_numeric_types(type_a, type_b);
+/// The expression a computed BIND assigns to `name`, if any.
+fn computed_binding_expr<'a>(
+    name: &str,
+    query: &'a QueryInfo,
+    bindings: &'a BTreeMap<String, Term>,
+) -> Option<&'a Expression> {
+    if let Some(Term::Computed(expr)) = bindings.get(name) {
+        return Some(expr);
+    }
+    query.pattern.bindings.iter().find_map(|b| match &b.term {
+        Term::Computed(expr) if b.variable == name => Some(expr.as_ref()),
+        _ => None,
+    })
+}
 
-    let left = coerce_numeric_operand(&left_code, type_a, result);
-    let right = coerce_numeric_operand(&right_code, type_b, result);
+/// True iff `name` is bound by a computed BIND (string-valued function
+/// or term constructor) in this circuit.
+fn is_computed_binding(name: &str, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    computed_binding_expr(name, query, bindings).is_some()
+}
 
-    let (suffix, returns_field) = match result {
-        NumericSourceType::Integer => ("int", true),
-        NumericSourceType::Float => ("float", false),
-        NumericSourceType::Double => ("double", false),
-    };
+/// True iff `expr` evaluates to a term computed in-circuit.
+fn is_computed_valued(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    match expr {
+        Expression::Variable(v) => is_computed_binding(v.as_str(), query, bindings),
+        _ => is_computed_function(expr),
+    }
+}
 
-    let inner = format!("xpath::numeric_{}_{}({}, {})", op, suffix, left, right);
-    if returns_field {
-        Ok(format!("(({}) as Field)", inner))
-    } else {
-        // Float / double: the SPARQL value is the IEEE 754 wrapper's
-        // bit pattern, packed into a Field for downstream consumers.
-        Ok(format!("(({}).to_bits() as Field)", inner))
+/// Extract a compile-time string argument (pattern / replacement /
+/// flags). The circuit shape depends on these bytes, so they cannot be
+/// bound at proving time.
+fn string_literal_argument(func: &str, role: &str, arg: &Expression) -> Result<String, String> {
+    match arg {
+        Expression::Literal(lit) => Ok(lit.value().to_string()),
+        _ => Err(format!(
+            "{} requires its {} argument to be a string literal -- the circuit shape is \
+             compiled from it at transform time",
+            func, role
+        )),
     }
 }
 
-/// Emit `+?x` / `-?x` (unary plus / minus).
-fn emit_numeric_unary(
-    op: &str, // "plus" or "minus"
-    a: &Expression,
+/// Lower a `REPLACE` / `ENCODE_FOR_URI` call. The returned operand's
+/// `witness` is the fresh `computed[k]`; its `checks` carry the input's
+/// binding, the output binding and the byte relation. A hash function
+/// call's witness is its in-circuit `utils::hex_string_witness`.
+fn string_valued_function(
+    expr: &Expression,
     query: &QueryInfo,
     bindings: &BTreeMap<String, Term>,
     hidden: &mut Vec<serde_json::Value>,
-) -> Result<String, String> {
-    let arg_code = expr_to_noir_code(a, query, bindings, hidden)?;
-    let type_a = infer_expression_type(a);
-    let inferred = type_a.unwrap_or(NumericSourceType::Integer);
-
-    match inferred {
-        NumericSourceType::Integer => {
-            let inner = format!(
-                "xpath::numeric_unary_{}_int(({}) as i64)",
-                op, arg_code
-            );
-            Ok(format!("(({}) as Field)", inner))
-        }
-        NumericSourceType::Float | NumericSourceType::Double => {
-            // noir_xpath does not currently expose `numeric_unary_*_float`
-            // / `_double`; emulate via subtract from zero (matches the
-            // pattern used in `arith::neg`).
-            let zero_ctor = match inferred {
-                NumericSourceType::Float => "xpath::cast_integer_to_float(0 as i8)",
-                NumericSourceType::Double => "xpath::cast_integer_to_double(0 as i8)",
-                _ => unreachable!(),
+) -> Result<StringOperand, String> {
+    if let Some(name) = hash_function_name(expr) {
+        let (input, digest) = hash_function_digest(expr, query, bindings, hidden)?;
+        return Ok(StringOperand {
+            checks: input.checks,
+            witness: format!("utils::hex_string_witness({})", digest),
+            input: serde_json::json!({"type": "digest", "algorithm": name, "input": input.input}),
+        });
+    }
+    let (func, args) = match expr {
+        Expression::FunctionCall(func, args) => (func, args),
+        _ => return Err(format!("Not a string-valued function call: {:?}", expr)),
+    };
+    if is_string_cast(expr) {
+        return string_cast(&args[0], query, bindings, hidden);
+    }
+    match func {
+        Function::Replace => {
+            if args.len() != 3 && args.len() != 4 {
+                return Err("REPLACE requires 3 or 4 arguments".into());
+            }
+            let input = string_operand(&args[0], query, bindings, hidden)?;
+            let pattern = string_literal_argument("REPLACE", "pattern", &args[1])?;
+            let replacement = string_literal_argument("REPLACE", "replacement", &args[2])?;
+            let flags = match args.get(3) {
+                Some(arg) => string_literal_argument("REPLACE", "flags", arg)?,
+                None => String::new(),
             };
-            let wrap = match inferred {
-                NumericSourceType::Float => format!(
-                    "xpath::XsdFloat::from_bits({} as u32)", arg_code
+            let (literal, replacement_bytes) = compile_replace(&pattern, &replacement, &flags)?;
+            let k = push_computed_witness(
+                hidden,
+                serde_json::json!({
+                    "type": COMPUTED_WITNESS_TYPE,
+                    "computedType": "replace",
+                    "input": input.input,
+                    // Literal forms: the prover replaces every leftmost,
+                    // non-overlapping occurrence of `pattern` with
+                    // `replacement`, byte for byte.
+                    "pattern": pattern,
+                    "replacement": replacement,
+                }),
+            );
+            Ok(StringOperand {
+                checks: format!(
+                    "{c}utils::bind_computed_plain_string_literal(computed[{k}]); \
+                     let pattern: [u8; {p}] = {pa}; let replacement: [u8; {r}] = {ra}; \
+                     assert(utils::string_replace::<{p}, {r}>({w}, computed[{k}], pattern, replacement)); ",
+                    c = input.checks,
+                    w = input.witness,
+                    k = k,
+                    p = literal.len(),
+                    pa = format_bytes_array(&literal),
+                    r = replacement_bytes.len(),
+                    ra = format_bytes_array(&replacement_bytes),
                 ),
-                NumericSourceType::Double => format!(
-                    "xpath::XsdDouble::from_bits({} as u64)", arg_code
+                witness: format!("computed[{}]", k),
+                input: serde_json::json!({"type": "computed", "value": k}),
+            })
+        }
+        Function::EncodeForUri => {
+            if args.len() != 1 {
+                return Err("ENCODE_FOR_URI requires 1 argument".into());
+            }
+            let input = string_operand(&args[0], query, bindings, hidden)?;
+            let k = push_computed_witness(
+                hidden,
+                serde_json::json!({
+                    "type": COMPUTED_WITNESS_TYPE,
+                    "computedType": "encode_for_uri",
+                    "input": input.input,
+                }),
+            );
+            Ok(StringOperand {
+                checks: format!(
+                    "{c}utils::bind_computed_plain_string_literal(computed[{k}]); \
+                     assert(utils::encode_for_uri({w}, computed[{k}])); ",
+                    c = input.checks,
+                    w = input.witness,
+                    k = k,
                 ),
-                _ => unreachable!(),
-            };
-            match op {
-                "plus" => Ok(format!("({}.to_bits() as Field)", wrap)),
-                "minus" => {
-                    let suffix = if matches!(inferred, NumericSourceType::Double) {
-                        "double"
-                    } else {
-                        "float"
-                    };
-                    let inner = format!(
-                        "xpath::numeric_subtract_{}({}, {})", suffix, zero_ctor, wrap
-                    );
-                    Ok(format!("(({}).to_bits() as Field)", inner))
-                }
-                _ => Err(format!("Unsupported unary op: {}", op)),
+                witness: format!("computed[{}]", k),
+                input: serde_json::json!({"type": "computed", "value": k}),
+            })
+        }
+        Function::Tz => {
+            if args.len() != 1 {
+                return Err("TZ requires 1 argument".into());
             }
+            tz_string(&args[0], query, bindings, hidden)
         }
+        _ => Err(format!("Unsupported string-valued function: {:?}", func)),
     }
 }
 
-/// Emit a unary numeric function `ABS(?x)` / `ROUND(?x)` / `CEIL(?x)` /
-/// `FLOOR(?x)` with type-aware dispatch.
-fn emit_numeric_unary_function(
-    op: &str, // "abs" / "round" / "ceil" / "floor"
-    a: &Expression,
+/// Noir expression for a string-valued BIND: evaluates to the computed
+/// `TermWitness` after running all of its checks. The emitter assigns
+/// it to the `computed_binding_local` of the bound variable.
+pub(crate) fn computed_binding_to_noir(
+    expr: &Expression,
     query: &QueryInfo,
     bindings: &BTreeMap<String, Term>,
     hidden: &mut Vec<serde_json::Value>,
 ) -> Result<String, String> {
-    let arg_code = expr_to_noir_code(a, query, bindings, hidden)?;
-    let inferred = infer_expression_type(a).unwrap_or(NumericSourceType::Integer);
+    let operand = if is_term_constructor(expr) {
+        term_constructor(expr, query, bindings, hidden)?
+    } else if is_temporal_valued(expr) {
+        temporal_term(expr, query, bindings, hidden)?
+    } else {
+        string_valued_function(expr, query, bindings, hidden)?
+    };
+    Ok(format!("{{ {}{} }}", operand.checks, operand.witness))
+}
 
-    match inferred {
-        NumericSourceType::Integer => {
-            // integer / decimal → integer path (decimal floor is field-
-            // element width per Q7).
-            Ok(format!(
-                "(xpath::{}_int(({}) as i64) as Field)",
-                op, arg_code
-            ))
-        }
-        NumericSourceType::Float => {
-            Ok(format!(
-                "(xpath::{}_float(xpath::XsdFloat::from_bits({} as u32)).to_bits() as Field)",
+/// Term-hash code for one side of an equality whose other side is
+/// computed. Computed results are term hashes, so the other side is
+/// compared by term identity too -- the same encoding `SameTerm` and
+/// BGP matching use.
+fn term_hash_operand(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    if is_string_valued_function(expr) {
+        return string_valued_term_hash(expr, query, bindings, hidden);
+    }
+    if is_term_constructor(expr) {
+        return term_constructor_hash(expr, query, bindings, hidden);
+    }
+    if is_temporal_valued(expr) {
+        let operand = temporal_term(expr, query, bindings, hidden)?;
+        return Ok(format!("{{ {}{}.hash }}", operand.checks, operand.witness));
+    }
+    match expr {
+        Expression::Literal(l) => Ok(serialize_ground_term(&GroundTerm::Literal(l.clone()))),
+        Expression::NamedNode(nn) => Ok(serialize_ground_term(&GroundTerm::NamedNode(nn.clone()))),
+        Expression::Variable(v) => Ok(serialize_term(&Term::Variable(v.as_str().to_string()), query, bindings)),
+        _ => Err(format!(
+            "Cannot compare a computed term with {:?}; the other operand must be a \
+             variable, an IRI, a literal, a string-valued function or a term constructor",
+            expr
+        )),
+    }
+}
+
+/// `=` / `sameTerm` where either side is computed in-circuit. Returns
+/// `None` when neither side is, so the caller falls back to its usual
+/// lowering.
+fn computed_term_equality(
+    a: &Expression,
+    b: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<Option<String>, String> {
+    if !is_computed_valued(a, query, bindings) && !is_computed_valued(b, query, bindings) {
+        return Ok(None);
+    }
+    let left = term_hash_operand(a, query, bindings, hidden)?;
+    let right = term_hash_operand(b, query, bindings, hidden)?;
+    Ok(Some(format!("{} == {}", left, right)))
+}
+
+// =============================================================================
+// RDF TERM CONSTRUCTORS (IRI / BNODE / STRDT / STRLANG)
+// =============================================================================
+//
+// A constructor reads its string argument's bound bytes and recomputes
+// the term hash the dataset encoding gives a term of the target kind
+// (`utils::construct_*`), so the result joins with, and compares equal
+// to, dataset terms. Datatype and language arguments shape the hash
+// and must be constants. Constructors over constant strings fold to
+// the ground term at transform time.
+
+/// True iff `expr` is an RDF term constructor call.
+pub(crate) fn is_term_constructor(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::FunctionCall(Function::Iri | Function::BNode | Function::StrDt | Function::StrLang, _)
+    )
+}
+
+/// True iff `expr` is a call whose result is computed in-circuit -- a
+/// string-valued function, a term constructor or a date, time or
+/// duration. `BIND`ing one yields a `Term::Computed`.
+fn is_computed_function(expr: &Expression) -> bool {
+    is_string_valued_function(expr) || is_term_constructor(expr) || is_temporal_valued(expr)
+}
+
+fn constructor_name(func: &Function) -> &'static str {
+    match func {
+        Function::Iri => "IRI",
+        Function::BNode => "BNODE",
+        Function::StrDt => "STRDT",
+        _ => "STRLANG",
+    }
+}
+
+/// Datatypes whose literals carry a special (non-lexical) value
+/// encoding -- see `special_literal_handling`.
+fn strdt_constructor(datatype: &str) -> Result<&'static str, String> {
+    match datatype.strip_prefix(XSD) {
+        Some("boolean") => Ok("construct_strdt_boolean"),
+        Some(
+            "integer" | "int" | "long" | "short" | "byte" | "nonNegativeInteger" | "positiveInteger"
+            | "negativeInteger" | "nonPositiveInteger" | "unsignedInt" | "unsignedLong"
+            | "unsignedShort" | "unsignedByte",
+        ) => Ok("construct_strdt_integer"),
+        Some("dateTime") => Err(
+            "STRDT(?x, xsd:dateTime) is not supported in-circuit -- the dateTime value \
+             encoding (epoch milliseconds) would need an in-circuit date parser"
+                .into(),
+        ),
+        _ => Ok("construct_strdt"),
+    }
+}
+
+/// `(func, args)` of a constructor call after arity checks.
+fn constructor_call(expr: &Expression) -> Result<(&Function, &[Expression]), String> {
+    let (func, args) = match expr {
+        Expression::FunctionCall(func, args) => (func, args.as_slice()),
+        _ => return Err(format!("Not a term constructor call: {:?}", expr)),
+    };
+    let expected = match func {
+        Function::Iri | Function::BNode => 1,
+        _ => 2,
+    };
+    if matches!(func, Function::BNode) && args.is_empty() {
+        return Err(
+            "BNODE() without an argument mints a fresh blank node per solution, which has \
+             no deterministic identity to check in-circuit -- pass a string: BNODE(?label)"
+                .into(),
+        );
+    }
+    if args.len() != expected {
+        return Err(format!(
+            "{} requires {} argument{}",
+            constructor_name(func),
+            expected,
+            if expected == 1 { "" } else { "s" }
+        ));
+    }
+    Ok((func, args))
+}
+
+/// A constant second argument: the datatype IRI of `STRDT` or the
+/// (lower-cased) language tag of `STRLANG`.
+fn constructor_constant(func: &Function, arg: &Expression) -> Result<String, String> {
+    match (func, arg) {
+        (Function::StrDt, Expression::NamedNode(nn)) => Ok(nn.as_str().to_string()),
+        (Function::StrLang, Expression::Literal(lit)) if lit.language().is_none() => {
+            Ok(lit.value().to_ascii_lowercase())
+        }
+        _ => Err(format!(
+            "{} requires its second argument to be a constant {} -- it is folded into the \
+             term hash at transform time",
+            constructor_name(func),
+            if matches!(func, Function::StrDt) { "datatype IRI" } else { "language tag" }
+        )),
+    }
+}
+
+/// Fold `IRI` / `STRDT` / `STRLANG` over a constant string to the
+/// ground term it constructs. `None` when the argument is not constant.
+pub(crate) fn fold_term_constructor(expr: &Expression) -> Result<Option<GroundTerm>, String> {
+    let (func, args) = constructor_call(expr)?;
+    let value = match &args[0] {
+        Expression::Literal(lit) if lit.language().is_none() => lit.value(),
+        _ => return Ok(None),
+    };
+    match func {
+        Function::Iri => spargebra::term::NamedNode::new(value)
+            .map(|nn| Some(GroundTerm::NamedNode(nn)))
+            .map_err(|e| format!("IRI({:?}) is not an absolute IRI: {}", value, e)),
+        Function::StrDt => {
+            let datatype = constructor_constant(func, &args[1])?;
+            Ok(Some(GroundTerm::Literal(spargebra::term::Literal::new_typed_literal(
+                value,
+                spargebra::term::NamedNode::new_unchecked(datatype),
+            ))))
+        }
+        Function::StrLang => {
+            let lang = constructor_constant(func, &args[1])?;
+            Ok(Some(GroundTerm::Literal(
+                spargebra::term::Literal::new_language_tagged_literal_unchecked(value, lang),
+            )))
+        }
+        // Blank nodes have no `GroundTerm` form; see `term_constructor_hash`.
+        _ => Ok(None),
+    }
+}
+
+/// Lower a constructor call over a bound string operand. The returned
+/// operand's `witness` is the constructed `TermWitness`.
+fn term_constructor(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<StringOperand, String> {
+    let (func, args) = constructor_call(expr)?;
+    let input = string_operand(&args[0], query, bindings, hidden)?;
+    let witness = match func {
+        Function::Iri => format!("utils::construct_iri({})", input.witness),
+        Function::BNode => format!("utils::construct_bnode({})", input.witness),
+        Function::StrDt => {
+            let datatype = constructor_constant(func, &args[1])?;
+            format!(
+                "utils::{}({}, {})",
+                strdt_constructor(&datatype)?,
+                input.witness,
+                encode_string_expr(&datatype)
+            )
+        }
+        _ => {
+            let lang = constructor_constant(func, &args[1])?;
+            format!("utils::construct_strlang({}, {})", input.witness, encode_string_expr(&lang))
+        }
+    };
+    Ok(StringOperand {
+        checks: input.checks,
+        witness,
+        input: serde_json::json!({
+            "type": "constructed",
+            "constructor": constructor_name(func),
+            "input": input.input,
+        }),
+    })
+}
+
+/// Term-hash code for a constructor call, folding constant arguments.
+fn term_constructor_hash(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    if let Some(term) = fold_term_constructor(expr)? {
+        return Ok(serialize_ground_term(&term));
+    }
+    if let Expression::FunctionCall(Function::BNode, args) = expr
+        && let [Expression::Literal(lit)] = args.as_slice()
+    {
+        return Ok(format!("consts::hash2([1, {}])", encode_string_expr(lit.value())));
+    }
+    let operand = term_constructor(expr, query, bindings, hidden)?;
+    Ok(format!("{{ {}{}.hash }}", operand.checks, operand.witness))
+}
+
+// =============================================================================
+// IN / NOT IN, IF, COALESCE
+// =============================================================================
+//
+// These three forms are defined by how they treat errors (SPARQL 1.1
+// §17.4.1): COALESCE skips them, IF propagates only the one from the
+// branch taken, and IN reports one only when no member matched. Within a
+// circuit variant, whether a variable is bound is static (the same
+// analysis `BOUND` uses), so an expression that reads an unbound variable
+// is an error the transform can see and route around. Runtime errors
+// (e.g. a type error inside an arithmetic argument) are not modelled
+// here and fall back to the operand's usual lowering.
+
+/// Whether `var` is bound in every solution of this circuit variant.
+fn is_statically_bound(var: &str, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    query.variables.iter().any(|v| v == var) || bindings.contains_key(var) || parameter_position(query, var).is_some()
+}
+
+/// Whether `expr` evaluates to an error in every solution because it
+/// reads an unbound variable. Conservative: `false` means "may succeed".
+fn is_static_error(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    let err = |e: &Expression| is_static_error(e, query, bindings);
+    match expr {
+        Expression::Variable(v) => !is_statically_bound(v.as_str(), query, bindings),
+        Expression::NamedNode(_) | Expression::Literal(_) | Expression::Bound(_) | Expression::Exists(_) => false,
+        // `false && error` is false and `true || error` is true.
+        Expression::And(a, b) | Expression::Or(a, b) => err(a) && err(b),
+        Expression::If(cond, then, otherwise) => err(cond) || (err(then) && err(otherwise)),
+        Expression::Coalesce(args) => args.iter().all(err),
+        Expression::In(lhs, _) => err(lhs),
+        Expression::Not(a) | Expression::UnaryPlus(a) | Expression::UnaryMinus(a) => err(a),
+        Expression::Equal(a, b)
+        | Expression::SameTerm(a, b)
+        | Expression::Greater(a, b)
+        | Expression::GreaterOrEqual(a, b)
+        | Expression::Less(a, b)
+        | Expression::LessOrEqual(a, b)
+        | Expression::Add(a, b)
+        | Expression::Subtract(a, b)
+        | Expression::Multiply(a, b)
+        | Expression::Divide(a, b) => err(a) || err(b) || is_temporal_type_error(expr, query, bindings),
+        // A constant XSD cast that fails (`xsd:integer("abc")`).
+        Expression::FunctionCall(Function::Custom(iri), args) if args.len() == 1 => {
+            err(&args[0])
+                || iri
+                    .as_str()
+                    .strip_prefix(XSD)
+                    .is_some_and(|target| fold_xsd_cast(target, &args[0]).is_err())
+        }
+        Expression::FunctionCall(_, args) => args.iter().any(err),
+    }
+}
+
+/// Boolean lowering of an IF branch; a branch that is always an error
+/// makes the FILTER false whenever it is taken.
+fn boolean_or_error(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    if is_static_error(expr, query, bindings) {
+        Ok("false".into())
+    } else {
+        filter_to_noir(expr, query, bindings, hidden)
+    }
+}
+
+/// The argument COALESCE returns: the first one that is not an error.
+fn coalesce_choice<'a>(
+    args: &'a [Expression],
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+) -> Option<&'a Expression> {
+    args.iter().find(|arg| !is_static_error(arg, query, bindings))
+}
+
+/// IF in value position (e.g. `IF(?a > 1, ?b, ?c) > 5`). Both branches
+/// must lower to the same Noir type. An error branch turns into an
+/// assertion that it is not taken: the enclosing FILTER would be an
+/// error in that solution, so there is no result row to prove.
+fn if_value(
+    cond: &Expression,
+    then: &Expression,
+    otherwise: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    let then_err = is_static_error(then, query, bindings);
+    let otherwise_err = is_static_error(otherwise, query, bindings);
+    if is_static_error(cond, query, bindings) || (then_err && otherwise_err) {
+        return Err(format!(
+            "IF evaluates to an error in every solution here (its condition, or both of \
+             its branches, depend on an unbound variable): {:?}",
+            Expression::If(Box::new(cond.clone()), Box::new(then.clone()), Box::new(otherwise.clone()))
+        ));
+    }
+    let cond = filter_to_noir(cond, query, bindings, hidden)?;
+    if then_err {
+        let otherwise = expr_to_noir_code(otherwise, query, bindings, hidden)?;
+        return Ok(format!("{{ assert(!({})); {} }}", cond, otherwise));
+    }
+    let then = expr_to_noir_code(then, query, bindings, hidden)?;
+    if otherwise_err {
+        return Ok(format!("{{ assert({}); {} }}", cond, then));
+    }
+    let otherwise = expr_to_noir_code(otherwise, query, bindings, hidden)?;
+    Ok(format!("(if ({}) {{ {} }} else {{ {} }})", cond, then, otherwise))
+}
+
+/// Whether `=` against this constant is RDF term equality: IRIs, plain
+/// and language-tagged strings, and literals of non-XSD datatypes (whose
+/// values the operator-mapping table cannot compare, so only identical
+/// terms are equal).
+fn equals_by_term_identity(expr: &Expression) -> bool {
+    match expr {
+        Expression::NamedNode(_) => true,
+        Expression::Literal(l) => {
+            let datatype = l.datatype().as_str();
+            l.language().is_some() || datatype.strip_prefix(XSD).is_none_or(|local| local == "string")
+        }
+        _ => false,
+    }
+}
+
+/// A term operand whose hash the circuit already has.
+fn is_term_operand(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    matches!(expr, Expression::Variable(_) | Expression::NamedNode(_) | Expression::Literal(_))
+        || is_computed_valued(expr, query, bindings)
+}
+
+/// `lhs = member` for one IN member. Identity-compared members (IRIs,
+/// strings) match by term hash, so an IRI never equals a literal with
+/// the same spelling; typed values (numeric, boolean, dateTime) go
+/// through the `=` lowering so that e.g. `1 IN (1.0)` holds.
+fn in_member_equality(
+    lhs: &Expression,
+    member: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    if (equals_by_term_identity(member) && is_term_operand(lhs, query, bindings))
+        || (equals_by_term_identity(lhs) && is_term_operand(member, query, bindings))
+    {
+        let left = term_hash_operand(lhs, query, bindings, hidden)?;
+        let right = term_hash_operand(member, query, bindings, hidden)?;
+        return Ok(format!("{} == {}", left, right));
+    }
+    filter_to_noir(
+        &Expression::Equal(Box::new(lhs.clone()), Box::new(member.clone())),
+        query,
+        bindings,
+        hidden,
+    )
+}
+
+/// `lhs IN (members)` / `lhs NOT IN (members)` as a disjunction of
+/// member equalities. A member that is always an error cannot match; it
+/// only matters when nothing else does, where IN would be an error
+/// rather than false -- the same outcome for a FILTER. NOT IN has no
+/// such slack: with no match its result is that error, so it is false.
+fn in_to_noir(
+    lhs: &Expression,
+    members: &[Expression],
+    negated: bool,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    if is_static_error(lhs, query, bindings) {
+        return Ok("false".into());
+    }
+    let mut matches = Vec::new();
+    let mut member_error = false;
+    for member in members {
+        if is_static_error(member, query, bindings) {
+            member_error = true;
+            continue;
+        }
+        matches.push(format!("({})", in_member_equality(lhs, member, query, bindings, hidden)?));
+    }
+    let any_match = if matches.is_empty() { "false".to_string() } else { matches.join(" | ") };
+    Ok(match (negated, member_error) {
+        (false, _) => any_match,
+        (true, false) if matches.is_empty() => "true".into(),
+        (true, false) => format!("!({})", any_match),
+        (true, true) => "false".into(),
+    })
+}
+
+// =============================================================================
+// LANGUAGE TAGS (LANG / LANGMATCHES)
+// =============================================================================
+//
+// The language tag of a literal is read from a `computed[]` byte witness
+// bound to the literal's term hash (`lang::bind_language_tag`): the
+// prover supplies the other three slots of the literal's `hash4`
+// preimage as hidden inputs, and the hash pins the tag. Matching is
+// then byte-level and case-insensitive -- `lang::tag_equals` for
+// `LANG(?x) = "en"`, RFC 4647 basic filtering (`lang::basic_filter`)
+// for LANGMATCHES.
+
+/// Transform-time LANGMATCHES over a constant tag (RFC 4647 sec.3.3.1).
+/// `range` is already lower-case.
+fn language_range_matches(tag: &str, range: &str) -> bool {
+    let tag = tag.to_ascii_lowercase();
+    if range == "*" {
+        return !tag.is_empty();
+    }
+    tag == range || tag.starts_with(&format!("{}-", range))
+}
+
+/// The language-tag witness of LANG's argument, bound to the term.
+fn language_tag_operand(
+    arg: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<StringOperand, String> {
+    let term = expr_to_term(arg)?;
+    let input = term_to_hidden_json(&term);
+    let k = push_computed_witness(
+        hidden,
+        serde_json::json!({
+            "type": COMPUTED_WITNESS_TYPE,
+            "computedType": "language_tag",
+            "input": input.clone(),
+        }),
+    );
+    let mut component = |name: &str| {
+        let idx = next_hidden_index(hidden);
+        hidden.push(serde_json::json!({
+            "type": "customComputed",
+            "computedType": "literal_component",
+            "component": name,
+            "input": input.clone(),
+        }));
+        idx
+    };
+    let (lexical, special, datatype) = (component("lexical"), component("special"), component("datatype"));
+    Ok(StringOperand {
+        checks: format!(
+            "lang::bind_language_tag({h}, computed[{k}].bytes, computed[{k}].length, hidden[{l}], hidden[{s}], hidden[{d}]); ",
+            h = serialize_term(&term, query, bindings),
+            k = k,
+            l = lexical,
+            s = special,
+            d = datatype
+        ),
+        witness: format!("computed[{}]", k),
+        input: serde_json::json!({"type": "computed", "value": k}),
+    })
+}
+
+// =============================================================================
+// THREE-VALUED FILTER LOGIC (SPARQL 1.1 §17.2)
+// =============================================================================
+//
+// SPARQL evaluates a FILTER to true, false or a type error, and the
+// logical operators treat errors specially: `error || true` is true,
+// `error && false` is false and `!error` is an error. `filter_to_noir`
+// lowers to plain booleans, which only agrees with that when no operand
+// can error or when nothing between the operand and the FILTER root can
+// tell an error from false (conjunctions). Every other filter is lowered
+// to `ebv::EBVResult` (`success` = not an error) and combined with its
+// SPARQL truth tables; the solution is kept iff the result is true.
+//
+// Errors come from two places. Reading an unbound variable is static
+// (`is_static_error`). Everything else -- an IRI compared with `>`, a
+// failed cast, a string operator over a non-string -- depends on the
+// solution, so each leaf that can raise one is guarded by its error
+// condition, computed in-circuit from the operands' opened term kinds
+// and datatypes (`leaf_error_condition`), and only evaluated when that
+// is false: the assertions inside the leaf are predicated on that
+// branch, so an erroring operand no longer fails the circuit.
+
+const TV_ERROR: &str = "ebv::EBVResult::type_error()";
+
+/// Lower one FILTER condition: booleans when they suffice, the
+/// three-valued lowering otherwise.
+pub(crate) fn filter_condition(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    if is_static_error(expr, query, bindings) {
+        Ok("false".into())
+    } else if needs_three_valued(expr, query, bindings) {
+        // A root conjunction cannot tell an error from false, so only
+        // the conjuncts that need it are lowered three-valued.
+        if let Expression::And(a, b) = expr {
+            let left = filter_condition(a, query, bindings, hidden)?;
+            let right = filter_condition(b, query, bindings, hidden)?;
+            return Ok(format!("({}) & ({})", left, right));
+        }
+        Ok(format!("{}.unwrap_or(false)", filter_to_tv(expr, query, bindings, hidden)?))
+    } else {
+        filter_to_noir(expr, query, bindings, hidden)
+    }
+}
+
+/// Whether an error-sensitive operator (`!`, `||`, IF, COALESCE, IN)
+/// has an operand that can error. Conjunctions at the root are
+/// transparent: there an error and false both reject the solution.
+fn needs_three_valued(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    match expr {
+        Expression::And(a, b) => needs_three_valued(a, query, bindings) || needs_three_valued(b, query, bindings),
+        Expression::Not(_)
+        | Expression::Or(_, _)
+        | Expression::If(_, _, _)
+        | Expression::Coalesce(_)
+        | Expression::In(_, _) => may_error(expr, query, bindings),
+        _ => false,
+    }
+}
+
+/// Whether any part of `expr` can evaluate to an error.
+fn may_error(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    if is_static_error(expr, query, bindings) {
+        return true;
+    }
+    let may = |e: &Expression| may_error(e, query, bindings);
+    match expr {
+        Expression::Not(a) => may(a),
+        Expression::And(a, b) | Expression::Or(a, b) => may(a) || may(b),
+        Expression::If(cond, then, otherwise) => may(cond) || may(then) || may(otherwise),
+        Expression::Coalesce(args) => args.iter().any(may),
+        Expression::In(lhs, members) => members.iter().any(|member| {
+            may(member) || leaf_may_error(&in_member(lhs, member), query, bindings)
+        }),
+        _ => leaf_may_error(expr, query, bindings),
+    }
+}
+
+/// Whether a non-logical leaf can raise a type error in some solution.
+/// Term tests and term identity never do; `=` against an IRI is false,
+/// not an error, whatever the other operand is; a leaf over constants
+/// is folded or evaluated the same way in every solution.
+fn leaf_may_error(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    match expr {
+        Expression::Bound(_) | Expression::Exists(_) | Expression::SameTerm(_, _) => false,
+        Expression::FunctionCall(
+            Function::IsIri | Function::IsBlank | Function::IsLiteral | Function::IsNumeric,
+            _,
+        ) => false,
+        Expression::Equal(a, b)
+            if matches!(a.as_ref(), Expression::NamedNode(_)) || matches!(b.as_ref(), Expression::NamedNode(_)) =>
+        {
+            false
+        }
+        _ => reads_bound_variable(expr, query, bindings),
+    }
+}
+
+/// Whether `expr` reads a variable that is bound in this circuit variant.
+fn reads_bound_variable(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    let reads = |e: &Expression| reads_bound_variable(e, query, bindings);
+    match expr {
+        Expression::Variable(v) => is_statically_bound(v.as_str(), query, bindings),
+        Expression::NamedNode(_) | Expression::Literal(_) | Expression::Bound(_) | Expression::Exists(_) => false,
+        Expression::Not(a) | Expression::UnaryPlus(a) | Expression::UnaryMinus(a) => reads(a),
+        Expression::And(a, b)
+        | Expression::Or(a, b)
+        | Expression::Equal(a, b)
+        | Expression::SameTerm(a, b)
+        | Expression::Greater(a, b)
+        | Expression::GreaterOrEqual(a, b)
+        | Expression::Less(a, b)
+        | Expression::LessOrEqual(a, b)
+        | Expression::Add(a, b)
+        | Expression::Subtract(a, b)
+        | Expression::Multiply(a, b)
+        | Expression::Divide(a, b) => reads(a) || reads(b),
+        Expression::If(a, b, c) => reads(a) || reads(b) || reads(c),
+        Expression::In(a, args) => reads(a) || args.iter().any(reads),
+        Expression::Coalesce(args) | Expression::FunctionCall(_, args) => args.iter().any(reads),
+    }
+}
+
+/// The `lhs = member` comparison an IN member stands for.
+fn in_member(lhs: &Expression, member: &Expression) -> Expression {
+    Expression::Equal(Box::new(lhs.clone()), Box::new(member.clone()))
+}
+
+/// Wrap a leaf's boolean code as an `EBVResult`. A leaf that can error
+/// is guarded by its error condition (`leaf_error_condition`).
+fn leaf_tv(
+    leaf: &Expression,
+    code: String,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    if !leaf_may_error(leaf, query, bindings) {
+        return Ok(format!("ebv::EBVResult::ok({})", code));
+    }
+    let error = leaf_error_condition(leaf, query, bindings, hidden)?;
+    Ok(format!(
+        "(if ({}) {{ {} }} else {{ ebv::EBVResult::ok({}) }})",
+        error, TV_ERROR, code
+    ))
+}
+
+/// One side of a comparison leaf, as far as its type error goes.
+enum ComparisonOperand<'a> {
+    Iri,
+    Literal(&'a spargebra::term::Literal),
+    /// A bound term, by its term hash.
+    Term(String),
+}
+
+fn comparison_operand<'a>(
+    expr: &'a Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+) -> Option<ComparisonOperand<'a>> {
+    match expr {
+        Expression::NamedNode(_) => Some(ComparisonOperand::Iri),
+        Expression::Literal(l) => Some(ComparisonOperand::Literal(l)),
+        // A computed BIND value has no term encoding to open.
+        Expression::Variable(v) if computed_binding_expr(v.as_str(), query, bindings).is_none() => Some(
+            ComparisonOperand::Term(serialize_term(&Term::Variable(v.as_str().to_string()), query, bindings)),
+        ),
+        _ => None,
+    }
+}
+
+/// `ebv::open_term_type` over a bound term: its kind and literal
+/// components are `literal_component` hidden inputs, bound by the hash.
+fn open_term_type(
+    hash: &str,
+    variable: &Expression,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    let input = term_to_hidden_json(&expr_to_term(variable)?);
+    let mut component = |name: &str| {
+        let idx = next_hidden_index(hidden);
+        hidden.push(serde_json::json!({
+            "type": "customComputed",
+            "computedType": "literal_component",
+            "component": name,
+            "input": input.clone(),
+        }));
+        idx
+    };
+    let (kind, inner) = (component("kind"), component("inner"));
+    let (lexical, special, language, datatype) =
+        (component("lexical"), component("special"), component("language"), component("datatype"));
+    Ok(format!(
+        "ebv::open_term_type({}, hidden[{}], hidden[{}], [hidden[{}], hidden[{}], hidden[{}], hidden[{}]])",
+        hash, kind, inner, lexical, special, language, datatype
+    ))
+}
+
+/// `ebv::TermType` predicate for "of a type that compares with `lit`":
+/// the numeric, string and boolean types by class, any other datatype
+/// exactly.
+fn comparable_with_literal(lit: &spargebra::term::Literal, term_type: &str) -> String {
+    let datatype = lit.datatype().as_str();
+    match datatype_to_comparison_type(datatype) {
+        ComparisonType::Numeric => format!("{}.is_numeric()", term_type),
+        ComparisonType::Boolean => format!("{}.is_boolean()", term_type),
+        _ if datatype.strip_prefix(XSD) == Some("string") => format!("{}.is_string()", term_type),
+        _ => format!(
+            "{}.has_datatype(ebv::encode_datatype_iri(\"{}\"))",
+            term_type,
+            datatype.replace('\\', "\\\\").replace('"', "\\\"")
+        ),
+    }
+}
+
+/// Noir condition that holds exactly when `leaf` raises a type error
+/// (SPARQL 1.1 sec.17.3), derived in-circuit from its operands' term
+/// kinds and datatypes (`ebv::open_term_type`), so the prover cannot
+/// pick it. Bound bits need no witness: a variant's unbound variables
+/// are static errors (`is_static_error`). Only comparisons between
+/// terms and constants have a condition this can derive; a filter that
+/// needs another leaf's error is rejected.
+fn leaf_error_condition(
+    leaf: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    let unsupported = || {
+        format!(
+            "FILTER operand {} can raise a type error where ||, !, IF, COALESCE or IN would observe it, \
+             and the circuit can only decide that for comparisons between variables and constants",
+            leaf
+        )
+    };
+    let (equality, a, b) = match leaf {
+        Expression::Equal(a, b) => (true, a, b),
+        Expression::Greater(a, b)
+        | Expression::GreaterOrEqual(a, b)
+        | Expression::Less(a, b)
+        | Expression::LessOrEqual(a, b) => (false, a, b),
+        _ => return Err(unsupported()),
+    };
+    let (Some(left), Some(right)) = (comparison_operand(a, query, bindings), comparison_operand(b, query, bindings))
+    else {
+        return Err(unsupported());
+    };
+    use ComparisonOperand::*;
+    match (left, right) {
+        // `<` and friends are not defined on IRIs (`=` against one is
+        // never a leaf that can error).
+        (Iri, _) | (_, Iri) => Ok("true".into()),
+        (Literal(lit), Term(hash)) | (Term(hash), Literal(lit)) => {
+            let variable = if matches!(a.as_ref(), Expression::Variable(_)) { a } else { b };
+            let term_type = open_term_type(&hash, variable, hidden)?;
+            let comparable = comparable_with_literal(lit, "t");
+            // `=` between a literal and a non-literal is false, not an
+            // error; an ordering is an error for any incomparable term.
+            if equality {
+                Ok(format!("{{ let t = {}; t.literal & !{} }}", term_type, comparable))
+            } else {
+                Ok(format!("{{ let t = {}; !{} }}", term_type, comparable))
+            }
+        }
+        (Term(left_hash), Term(right_hash)) => {
+            let left_type = open_term_type(&left_hash, a, hidden)?;
+            let right_type = open_term_type(&right_hash, b, hidden)?;
+            if equality {
+                Ok(format!(
+                    "ebv::equality_error({}, {}, {} == {})",
+                    left_type, right_type, left_hash, right_hash
+                ))
+            } else if temporal_type(a, query, bindings).is_some() || temporal_type(b, query, bindings).is_some() {
+                Err(unsupported())
+            } else {
+                // Two variables compare numerically (`determine_comparison_type`).
+                Ok(format!("!({}.is_numeric() & {}.is_numeric())", left_type, right_type))
+            }
+        }
+        (Literal(_), Literal(_)) => Err(unsupported()),
+    }
+}
+
+/// Three-valued lowering of a filter expression to `ebv::EBVResult`.
+fn filter_to_tv(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    if is_static_error(expr, query, bindings) {
+        return Ok(TV_ERROR.into());
+    }
+    match expr {
+        Expression::And(a, b) => Ok(format!(
+            "{}.and({})",
+            filter_to_tv(a, query, bindings, hidden)?,
+            filter_to_tv(b, query, bindings, hidden)?
+        )),
+        Expression::Or(a, b) => Ok(format!(
+            "{}.or({})",
+            filter_to_tv(a, query, bindings, hidden)?,
+            filter_to_tv(b, query, bindings, hidden)?
+        )),
+        Expression::Not(inner) => Ok(format!("{}.not()", filter_to_tv(inner, query, bindings, hidden)?)),
+
+        // `x IN (e1, ..., en)` is `(x = e1) || ... || (x = en)` and
+        // `x NOT IN (...)` its negation (SPARQL 1.1 §17.4.1.9-10).
+        Expression::In(lhs, members) => {
+            let mut any_match = "ebv::EBVResult::ok(false)".to_string();
+            for (i, member) in members.iter().enumerate() {
+                let member_tv = if is_static_error(member, query, bindings) {
+                    TV_ERROR.to_string()
+                } else {
+                    let code = in_member_equality(lhs, member, query, bindings, hidden)?;
+                    leaf_tv(&in_member(lhs, member), code, query, bindings, hidden)?
+                };
+                any_match = if i == 0 { member_tv } else { format!("{}.or({})", any_match, member_tv) };
+            }
+            Ok(any_match)
+        }
+
+        Expression::If(cond, then, otherwise) => Ok(format!(
+            "({{ let cond = {}; if cond.success & cond.value {{ {} }} else if cond.success {{ {} }} else {{ {} }} }})",
+            filter_to_tv(cond, query, bindings, hidden)?,
+            filter_to_tv(then, query, bindings, hidden)?,
+            filter_to_tv(otherwise, query, bindings, hidden)?,
+            TV_ERROR
+        )),
+
+        // The first argument that is not an error, tried in order at
+        // run time; statically erroring arguments are dropped up front.
+        Expression::Coalesce(args) => {
+            let candidates: Vec<&Expression> =
+                args.iter().filter(|arg| !is_static_error(arg, query, bindings)).collect();
+            let mut result = TV_ERROR.to_string();
+            for (i, arg) in candidates.iter().enumerate().rev() {
+                let arg_tv = filter_to_tv(arg, query, bindings, hidden)?;
+                result = if i + 1 == candidates.len() {
+                    arg_tv
+                } else {
+                    format!("({{ let arg = {}; if arg.success {{ arg }} else {{ {} }} }})", arg_tv, result)
+                };
+            }
+            Ok(result)
+        }
+
+        // A variable's effective boolean value is itself three-valued.
+        Expression::Variable(v) => {
+            let term = Term::Variable(v.as_str().to_string());
+            let value_idx = push_hidden(hidden, "ebv_value", &term);
+            let datatype_idx = push_hidden(hidden, "ebv_datatype", &term);
+            Ok(format!("ebv::ebv(hidden[{}], hidden[{}])", value_idx, datatype_idx))
+        }
+
+        _ => {
+            let code = filter_to_noir(expr, query, bindings, hidden)?;
+            leaf_tv(expr, code, query, bindings, hidden)
+        }
+    }
+}
+
+// =============================================================================
+// REGEX COMPILATION (transform-time)
+// =============================================================================
+//
+// SPARQL's REGEX / REPLACE take XPath regular expressions, but the
+// circuit has no regex engine: it only matches a fixed byte sequence.
+// A pattern therefore compiles, at transform time, only when its text
+// *is* those bytes -- a plain literal with no metacharacters (escaped
+// or not), or any pattern under the `q` flag, which makes XPath read
+// it literally. Character classes, quantifiers, alternation and
+// anchors need a bounded NFA (SPARQL_ROADMAP.md sec.3); they and the
+// other flags are rejected with an explicit error instead of being
+// approximated.
+
+/// Outside `q` mode, any of these makes a pattern non-literal.
+const REGEX_METACHARACTERS: &str = ".^$*+?()[]{}|\\";
+
+/// Compile a `REPLACE` pattern and replacement to the literal bytes the
+/// circuit matches and substitutes.
+fn compile_replace(pattern: &str, replacement: &str, flags: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let mut literal_flag = false;
+    for flag in flags.chars() {
+        match flag {
+            'q' => literal_flag = true,
+            'i' => {
+                return Err("regex flag 'i' is not supported in-circuit -- case-insensitive matching would \
+                            need case-folded byte comparison, and the circuit matches bytes exactly"
+                    .into())
+            }
+            other => {
+                return Err(format!(
+                    "regex flag '{}' is not supported in-circuit -- it changes how metacharacters and \
+                     whitespace in the pattern are read, and only the 'q' (literal) flag compiles",
+                    other
+                ))
+            }
+        }
+    }
+    if pattern.is_empty() {
+        return Err(format!(
+            "regex pattern {:?} matches the empty string (XPath error FORX0003)",
+            pattern
+        ));
+    }
+    if !literal_flag {
+        if let Some(c) = pattern.chars().find(|c| REGEX_METACHARACTERS.contains(*c)) {
+            return Err(format!(
+                "regex metacharacter '{}' in pattern {:?} is not supported in-circuit -- only literal \
+                 patterns compile, so escapes are rejected too. Pass the 'q' flag to match it \
+                 literally; general patterns need a bounded NFA (SPARQL_ROADMAP.md sec.3)",
+                c, pattern
+            ));
+        }
+        // Without `q`, XPath reads `$N` as a group reference and `\` as
+        // an escape in the replacement; pass `q` for literal text.
+        if let Some(c) = replacement.chars().find(|c| *c == '$' || *c == '\\') {
+            return Err(format!(
+                "replacement {:?} uses '{}', which XPath reads as a group reference or escape; only \
+                 literal replacements compile. Pass the 'q' flag to substitute it literally",
+                replacement, c
+            ));
+        }
+    }
+    Ok((pattern.as_bytes().to_vec(), replacement.as_bytes().to_vec()))
+}
+
+fn push_hidden(hidden: &mut Vec<serde_json::Value>, kind: &str, term: &Term) -> usize {
+    let idx = next_hidden_index(hidden);
+    hidden.push(serde_json::json!({
+        "type": "customComputed",
+        "computedType": kind,
+        "input": term_to_hidden_json(term)
+    }));
+    idx
+}
+
+fn push_hidden_comparison(hidden: &mut Vec<serde_json::Value>, kind: &str, left: &Term, right: &Term) -> usize {
+    let idx = next_hidden_index(hidden);
+    hidden.push(serde_json::json!({
+        "type": "customComputed",
+        "computedType": kind,
+        "inputs": [term_to_hidden_json(left), term_to_hidden_json(right)]
+    }));
+    idx
+}
+
+// =============================================================================
+// NUMERIC ARITHMETIC — round 2 §6.2
+// =============================================================================
+//
+// SPARQL 1.1 §17.3 numeric promotion: the result type is the widest of
+// the operand types (integer < decimal < float < double). Here we:
+// 1. infer each operand's static type via `infer_expression_type`,
+// 2. promote both to the wider type,
+// 3. emit the matching `xpath::numeric_*_{int,decimal,float,double}`
+//    call.
+//
+// `xsd:decimal` is fixed-point: an `i64` holding the value times
+// `10^DECIMAL_SCALE` (`noir/lib/xpath/src/decimal.nr`). Decimal literals
+// are folded to that constant at transform time; variables read in a
+// decimal context are prover-supplied `decimal_value` hidden inputs
+// carrying the scale they are expressed in.
+
+/// Fractional digits of the fixed-point xsd:decimal representation.
+/// Must match `xpath::DECIMAL_SCALE`; recorded in the metadata as
+/// `decimalScale`.
+pub(crate) const DECIMAL_SCALE: u32 = 6;
+
+fn promote_numeric_types(
+    a: Option<NumericSourceType>,
+    b: Option<NumericSourceType>,
+) -> NumericSourceType {
+    use NumericSourceType::*;
+    match (a, b) {
+        (Some(Double), _) | (_, Some(Double)) => Double,
+        (Some(Float), _) | (_, Some(Float)) => Float,
+        (Some(Decimal), _) | (_, Some(Decimal)) => Decimal,
+        _ => Integer,
+    }
+}
+
+/// The fixed-point value of a decimal or integer lexical form. Fails
+/// when it has more than `DECIMAL_SCALE` fractional digits or leaves
+/// the 64-bit range, rather than round.
+fn decimal_fixed_point(lexical: &str) -> Result<i64, String> {
+    let (digits, scale) = parse_decimal_lexical(lexical)
+        .ok_or_else(|| format!("{:?} is not a valid xsd:decimal lexical form", lexical))?;
+    let scaled = if scale <= DECIMAL_SCALE {
+        10i128.checked_pow(DECIMAL_SCALE - scale).and_then(|f| digits.checked_mul(f))
+    } else {
+        None
+    };
+    scaled.and_then(|v| i64::try_from(v).ok()).ok_or_else(|| {
+        format!(
+            "The xsd:decimal {} is not representable in the circuit's fixed-point decimals \
+             ({} fractional digits, magnitude below 9.2e12)",
+            lexical, DECIMAL_SCALE
+        )
+    })
+}
+
+/// Lower an operand of a decimal-typed operation to its fixed-point
+/// `i64`. A variable becomes a `decimal_value` hidden input (the prover
+/// supplies its value times `10^DECIMAL_SCALE`); anything else is
+/// lowered as usual and widened from its own type.
+fn decimal_operand(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    if let Expression::Variable(v) = expr {
+        let idx = next_hidden_index(hidden);
+        hidden.push(serde_json::json!({
+            "type": "customComputed",
+            "computedType": "decimal_value",
+            "scale": DECIMAL_SCALE,
+            "input": term_to_hidden_json(&Term::Variable(v.as_str().to_string())),
+        }));
+        return Ok(format!("(hidden[{}] as i64)", idx));
+    }
+    let code = expr_to_noir_code(expr, query, bindings, hidden)?;
+    Ok(coerce_numeric_operand(&code, infer_expression_type(expr), NumericSourceType::Decimal))
+}
+
+/// Coerce an operand to the result type's representation. Integer
+/// operands are routed through `xpath::cast_integer_to_*` (or scaled by
+/// `xpath::decimal_from_integer`); narrower operands are widened when
+/// the result type is wider.
+fn coerce_numeric_operand(
+    code: &str,
+    from: Option<NumericSourceType>,
+    to: NumericSourceType,
+) -> String {
+    use NumericSourceType::*;
+    match (from, to) {
+        (_, Integer) => format!("({}) as i64", code),
+        (Some(Decimal), Decimal) => format!("(({}) as i64)", code),
+        (_, Decimal) => format!("xpath::decimal_from_integer(({}) as i64)", code),
+        (Some(Decimal), Double) => format!("xpath::decimal_to_double(({}) as i64)", code),
+        (Some(Decimal), Float) => format!("xpath::decimal_to_float(({}) as i64)", code),
+        (Some(Double), Double) => format!("xpath::XsdDouble::from_bits({} as u64)", code),
+        (Some(Float), Double) => format!(
+            "xpath::XsdDouble::from_float(xpath::XsdFloat::from_bits({} as u32))",
+            code
+        ),
+        (Some(Integer), Double) | (None, Double) => {
+            format!("xpath::cast_integer_to_double(({}) as i64)", code)
+        }
+        (Some(Float), Float) => format!("xpath::XsdFloat::from_bits({} as u32)", code),
+        (Some(Integer), Float) | (None, Float) => {
+            format!("xpath::cast_integer_to_float(({}) as i64)", code)
+        }
+        // Double → Float is a narrowing cast we don't expect to hit
+        // because promotion always picks the wider; if it ever did we'd
+        // truncate via cast_double_to_float.
+        (Some(Double), Float) => format!(
+            "xpath::cast_double_to_float(xpath::XsdDouble::from_bits({} as u64))",
+            code
+        ),
+    }
+}
+
+/// Emit `?x ⊕ ?y` for ⊕ ∈ {add, subtract, multiply, divide}.
+fn emit_numeric_binary(
+    op: &str,
+    a: &Expression,
+    b: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    let type_a = infer_expression_type(a);
+    let type_b = infer_expression_type(b);
+    let mut result = promote_numeric_types(type_a, type_b);
+    // Integer division yields xsd:decimal (XPath op:numeric-divide).
+    if op == "divide" && result == NumericSourceType::Integer {
+        result = NumericSourceType::Decimal;
+    }
+
+    let (left, right) = if result == NumericSourceType::Decimal {
+        (
+            decimal_operand(a, query, bindings, hidden)?,
+            decimal_operand(b, query, bindings, hidden)?,
+        )
+    } else {
+        let left_code = expr_to_noir_code(a, query, bindings, hidden)?;
+        let right_code = expr_to_noir_code(b, query, bindings, hidden)?;
+        (
+            coerce_numeric_operand(&left_code, type_a, result),
+            coerce_numeric_operand(&right_code, type_b, result),
+        )
+    };
+
+    let (suffix, returns_field) = match result {
+        NumericSourceType::Integer => ("int", true),
+        NumericSourceType::Decimal => ("decimal", true),
+        NumericSourceType::Float => ("float", false),
+        NumericSourceType::Double => ("double", false),
+    };
+
+    let inner = format!("xpath::numeric_{}_{}({}, {})", op, suffix, left, right);
+    if returns_field {
+        Ok(format!("(({}) as Field)", inner))
+    } else {
+        // Float / double: the SPARQL value is the IEEE 754 wrapper's
+        // bit pattern, packed into a Field for downstream consumers.
+        Ok(format!("(({}).to_bits() as Field)", inner))
+    }
+}
+
+/// Emit `+?x` / `-?x` (unary plus / minus).
+fn emit_numeric_unary(
+    op: &str, // "plus" or "minus"
+    a: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    let arg_code = expr_to_noir_code(a, query, bindings, hidden)?;
+    let type_a = infer_expression_type(a);
+    let inferred = type_a.unwrap_or(NumericSourceType::Integer);
+
+    match inferred {
+        NumericSourceType::Integer => {
+            let inner = format!(
+                "xpath::numeric_unary_{}_int(({}) as i64)",
+                op, arg_code
+            );
+            Ok(format!("(({}) as Field)", inner))
+        }
+        NumericSourceType::Decimal => match op {
+            "plus" => Ok(arg_code),
+            "minus" => Ok(format!(
+                "(xpath::numeric_unary_minus_decimal(({}) as i64) as Field)",
+                arg_code
+            )),
+            _ => Err(format!("Unsupported unary op: {}", op)),
+        },
+        NumericSourceType::Float | NumericSourceType::Double => {
+            // noir_xpath does not currently expose `numeric_unary_*_float`
+            // / `_double`; emulate via subtract from zero (matches the
+            // pattern used in `arith::neg`).
+            let zero_ctor = match inferred {
+                NumericSourceType::Float => "xpath::cast_integer_to_float(0 as i8)",
+                NumericSourceType::Double => "xpath::cast_integer_to_double(0 as i8)",
+                _ => unreachable!(),
+            };
+            let wrap = match inferred {
+                NumericSourceType::Float => format!(
+                    "xpath::XsdFloat::from_bits({} as u32)", arg_code
+                ),
+                NumericSourceType::Double => format!(
+                    "xpath::XsdDouble::from_bits({} as u64)", arg_code
+                ),
+                _ => unreachable!(),
+            };
+            match op {
+                "plus" => Ok(format!("({}.to_bits() as Field)", wrap)),
+                "minus" => {
+                    let suffix = if matches!(inferred, NumericSourceType::Double) {
+                        "double"
+                    } else {
+                        "float"
+                    };
+                    let inner = format!(
+                        "xpath::numeric_subtract_{}({}, {})", suffix, zero_ctor, wrap
+                    );
+                    Ok(format!("(({}).to_bits() as Field)", inner))
+                }
+                _ => Err(format!("Unsupported unary op: {}", op)),
+            }
+        }
+    }
+}
+
+/// Emit a unary numeric function `ABS(?x)` / `ROUND(?x)` / `CEIL(?x)` /
+/// `FLOOR(?x)` with type-aware dispatch.
+fn emit_numeric_unary_function(
+    op: &str, // "abs" / "round" / "ceil" / "floor"
+    a: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    let arg_code = expr_to_noir_code(a, query, bindings, hidden)?;
+    let inferred = infer_expression_type(a).unwrap_or(NumericSourceType::Integer);
+
+    match inferred {
+        NumericSourceType::Integer => {
+            Ok(format!(
+                "(xpath::{}_int(({}) as i64) as Field)",
+                op, arg_code
+            ))
+        }
+        NumericSourceType::Decimal => {
+            Ok(format!(
+                "(xpath::{}_decimal(({}) as i64) as Field)",
+                op, arg_code
+            ))
+        }
+        NumericSourceType::Float => {
+            Ok(format!(
+                "(xpath::{}_float(xpath::XsdFloat::from_bits({} as u32)).to_bits() as Field)",
                 op, arg_code
             ))
         }
@@ -1931,3 +4072,588 @@ fn emit_numeric_unary_function(
         }
     }
 }
+
+// =============================================================================
+// TEMPORAL VALUES — XPath F&O §9, §10
+// =============================================================================
+//
+// Dates, times and durations are `i64`s in the representation of
+// `noir/lib/xpath/src/temporal.nr`: instants as epoch milliseconds
+// (xsd:time anchored to one reference day), dayTimeDurations as
+// milliseconds and yearMonthDurations as months. A value without a
+// timezone is read in the implicit timezone, UTC, so comparison and
+// subtraction need only the value. Component accessors, calendar
+// arithmetic and canonical lexical forms also need the timezone the
+// value was written in: minutes east of UTC, or `NO_TIMEZONE`.
+//
+// Literals fold to both at transform time. A variable in a temporal
+// context is a `temporal_value` hidden input (its value in the
+// representation of the context's datatype) and, where needed, a
+// `timezone_value` hidden input. A variable that no operand types --
+// `?a - ?b`, `YEAR(?d)` -- is read as xsd:dateTime.
+//
+// `NOW()` is the public `now` input of `checkBinding`: an xsd:dateTime
+// in UTC that the verifier supplies and checks against its own clock.
+
+/// Timezone of a value written without one. Must match
+/// `xpath::NO_TIMEZONE`.
+const NO_TIMEZONE: i64 = 1440;
+
+const MS_PER_DAY: i64 = 86_400_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TemporalType {
+    DateTime,
+    Date,
+    Time,
+    GYearMonth,
+    GYear,
+    DayTimeDuration,
+    YearMonthDuration,
+}
+
+impl TemporalType {
+    fn of_datatype(local: &str) -> Option<Self> {
+        match local {
+            "dateTime" | "dateTimeStamp" => Some(TemporalType::DateTime),
+            "date" => Some(TemporalType::Date),
+            "time" => Some(TemporalType::Time),
+            "gYearMonth" => Some(TemporalType::GYearMonth),
+            "gYear" => Some(TemporalType::GYear),
+            "dayTimeDuration" => Some(TemporalType::DayTimeDuration),
+            "yearMonthDuration" => Some(TemporalType::YearMonthDuration),
+            _ => None,
+        }
+    }
+
+    fn local_name(self) -> &'static str {
+        match self {
+            TemporalType::DateTime => "dateTime",
+            TemporalType::Date => "date",
+            TemporalType::Time => "time",
+            TemporalType::GYearMonth => "gYearMonth",
+            TemporalType::GYear => "gYear",
+            TemporalType::DayTimeDuration => "dayTimeDuration",
+            TemporalType::YearMonthDuration => "yearMonthDuration",
+        }
+    }
+
+    fn is_duration(self) -> bool {
+        matches!(self, TemporalType::DayTimeDuration | TemporalType::YearMonthDuration)
+    }
+
+    /// XPath orders these; gYear and gYearMonth only compare for
+    /// equality (F&O §9.5).
+    fn is_ordered(self) -> bool {
+        !matches!(self, TemporalType::GYear | TemporalType::GYearMonth)
+    }
+}
+
+/// Whether XPath defines `point + duration` (F&O §10.8).
+fn adds_duration(point: TemporalType, duration: TemporalType) -> bool {
+    use TemporalType::*;
+    matches!(
+        (point, duration),
+        (DateTime | Date, DayTimeDuration | YearMonthDuration) | (Time, DayTimeDuration)
+    )
+}
+
+/// Static temporal type of `expr`. `variable` types a variable, given
+/// its name.
+fn temporal_type_with(expr: &Expression, variable: &dyn Fn(&str) -> Option<TemporalType>) -> Option<TemporalType> {
+    let of = |e: &Expression| temporal_type_with(e, variable);
+    match expr {
+        Expression::Literal(l) => l.datatype().as_str().strip_prefix(XSD).and_then(TemporalType::of_datatype),
+        Expression::Variable(v) => variable(v.as_str()),
+        Expression::FunctionCall(Function::Custom(iri), args) if args.len() == 1 => {
+            iri.as_str().strip_prefix(XSD).and_then(TemporalType::of_datatype)
+        }
+        Expression::FunctionCall(Function::Timezone, _) => Some(TemporalType::DayTimeDuration),
+        Expression::FunctionCall(Function::Now, _) => Some(TemporalType::DateTime),
+        Expression::UnaryPlus(a) | Expression::UnaryMinus(a) => of(a).filter(|t| t.is_duration()),
+        Expression::Add(a, b) | Expression::Subtract(a, b) => {
+            let subtract = matches!(expr, Expression::Subtract(_, _));
+            let (ta, tb) = (of(a), of(b));
+            if ta.is_none() && tb.is_none() {
+                return None;
+            }
+            // Preference order resolves untyped operands: an untyped
+            // point is a dateTime, `point - ?x` a difference.
+            use TemporalType::*;
+            [DateTime, Date, Time, DayTimeDuration, YearMonthDuration]
+                .into_iter()
+                .find(|&result| temporal_operand_types(subtract, ta, tb, result).is_ok())
+        }
+        _ => None,
+    }
+}
+
+/// Static temporal type of `expr`, looking through computed BINDs.
+fn temporal_type(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> Option<TemporalType> {
+    temporal_type_with(expr, &|v| {
+        computed_binding_expr(v, query, bindings).and_then(|e| temporal_type(e, query, bindings))
+    })
+}
+
+/// True iff `expr` computes a date, time or duration in-circuit: a
+/// `BIND` of it yields a `Term::Computed`.
+pub(crate) fn is_temporal_valued(expr: &Expression) -> bool {
+    !matches!(expr, Expression::Literal(_) | Expression::Variable(_)) && temporal_type_with(expr, &|_| None).is_some()
+}
+
+/// Operand types of `a + b` / `a - b` with a result of type `result`.
+/// An untyped operand takes the type that makes the operation
+/// well-typed, if exactly one does.
+fn temporal_operand_types(
+    subtract: bool,
+    a: Option<TemporalType>,
+    b: Option<TemporalType>,
+    result: TemporalType,
+) -> Result<(TemporalType, TemporalType), String> {
+    use TemporalType::*;
+    let (ta, tb) = match (a, b) {
+        (Some(ta), Some(tb)) => (ta, tb),
+        // A difference of two points.
+        (Some(p), None) | (None, Some(p)) if subtract && !p.is_duration() && result == DayTimeDuration => (p, p),
+        (None, None) if subtract && result == DayTimeDuration => (DateTime, DateTime),
+        // A point moved by a duration.
+        (None, Some(d)) if d.is_duration() && !result.is_duration() => (result, d),
+        (Some(d), None) if !subtract && d.is_duration() && !result.is_duration() => (d, result),
+        // Two durations of the same kind.
+        (Some(d), None) | (None, Some(d)) if d == result && d.is_duration() => (d, d),
+        (None, None) if result.is_duration() => (result, result),
+        _ => return Err("the duration operand needs a datatype".into()),
+    };
+    let point_difference = subtract && ta == tb && matches!(ta, DateTime | Date | Time) && result == DayTimeDuration;
+    let moved = (adds_duration(ta, tb) && result == ta) || (!subtract && adds_duration(tb, ta) && result == tb);
+    let durations = ta == tb && ta == result && result.is_duration();
+    if point_difference || moved || durations {
+        Ok((ta, tb))
+    } else {
+        Err(format!(
+            "xsd:{} {} xsd:{} is not an xsd:{}",
+            ta.local_name(),
+            if subtract { "-" } else { "+" },
+            tb.local_name(),
+            result.local_name()
+        ))
+    }
+}
+
+/// Whether `expr` is a comparison or arithmetic whose operands have
+/// temporal types XPath does not combine -- a type error in every
+/// solution.
+fn is_temporal_type_error(expr: &Expression, query: &QueryInfo, bindings: &BTreeMap<String, Term>) -> bool {
+    let ty = |e: &Expression| temporal_type(e, query, bindings);
+    match expr {
+        Expression::Equal(a, b) => matches!((ty(a), ty(b)), (Some(ta), Some(tb)) if ta != tb),
+        Expression::Greater(a, b)
+        | Expression::GreaterOrEqual(a, b)
+        | Expression::Less(a, b)
+        | Expression::LessOrEqual(a, b) => match (ty(a), ty(b)) {
+            (Some(ta), Some(tb)) => ta != tb || !ta.is_ordered(),
+            (Some(t), None) | (None, Some(t)) => !t.is_ordered(),
+            (None, None) => false,
+        },
+        Expression::Add(a, b) | Expression::Subtract(a, b) => {
+            matches!((ty(a), ty(b)), (Some(_), Some(_))) && ty(expr).is_none()
+        }
+        _ => false,
+    }
+}
+
+/// A `temporal_value` / `timezone_value` hidden input for a variable.
+fn push_temporal_hidden(hidden: &mut Vec<serde_json::Value>, kind: &str, var: &str, ty: TemporalType) -> usize {
+    let idx = next_hidden_index(hidden);
+    hidden.push(serde_json::json!({
+        "type": "customComputed",
+        "computedType": kind,
+        "datatype": format!("{}{}", XSD, ty.local_name()),
+        "input": term_to_hidden_json(&Term::Variable(var.to_string())),
+    }));
+    idx
+}
+
+fn i64_code(value: i64) -> String {
+    if value < 0 {
+        format!("(0 - {} as i64)", value.unsigned_abs())
+    } else {
+        format!("({} as i64)", value)
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (same algorithm
+/// as `xpath::days_from_civil`); `None` when the year is out of `i64`
+/// range.
+fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    let y = if month <= 2 { year.checked_sub(1)? } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era.checked_mul(146097)?.checked_add(doe - 719468)
+}
+
+/// Milliseconds in an `oxsdatatypes` seconds value; fails below
+/// millisecond precision rather than round.
+fn milliseconds(seconds: oxsdatatypes::Decimal) -> Result<i64, String> {
+    let lexical = seconds.to_string();
+    let (digits, scale) =
+        parse_decimal_lexical(&lexical).ok_or_else(|| format!("{:?} is not a number of seconds", lexical))?;
+    let scaled = if scale <= 3 { digits.checked_mul(10i128.pow(3 - scale)) } else { None };
+    scaled.and_then(|ms| i64::try_from(ms).ok()).ok_or_else(|| {
+        format!("{} seconds is not representable in the circuit's millisecond temporal values", lexical)
+    })
+}
+
+/// Timezone offset in minutes of an `oxsdatatypes` timezone.
+fn timezone_minutes(timezone: Option<oxsdatatypes::DayTimeDuration>) -> Result<i64, String> {
+    match timezone {
+        Some(offset) => Ok(milliseconds(offset.as_seconds())? / 60_000),
+        None => Ok(NO_TIMEZONE),
+    }
+}
+
+/// Value and timezone of a temporal literal of type `ty`.
+fn temporal_literal(lit: &spargebra::term::Literal, ty: TemporalType) -> Result<(i64, i64), String> {
+    use oxsdatatypes::{Date, DateTime, DayTimeDuration, GYear, GYearMonth, Time, YearMonthDuration};
+    let lexical = lit.value();
+    let invalid = || format!("{:?} is not a valid xsd:{} lexical form", lexical, ty.local_name());
+    // Years far enough from 1970 overflow the circuit's i64
+    // millisecond values; they are rejected rather than wrapped.
+    let day_start = |year: i64, month: u8, day: u8| -> Result<i64, String> {
+        days_from_civil(year, i64::from(month), i64::from(day))
+            .and_then(|days| days.checked_mul(MS_PER_DAY))
+            .ok_or_else(invalid)
+    };
+    let time_of_day = |hour: u8, minute: u8, second| -> Result<i64, String> {
+        Ok(i64::from(hour) * 3_600_000 + i64::from(minute) * 60_000 + milliseconds(second)?)
+    };
+    let (local, tz) = match ty {
+        TemporalType::DateTime => {
+            let v = lexical.parse::<DateTime>().map_err(|_| invalid())?;
+            let local = day_start(v.year(), v.month(), v.day())?
+                .checked_add(time_of_day(v.hour(), v.minute(), v.second())?)
+                .ok_or_else(invalid)?;
+            (local, timezone_minutes(v.timezone())?)
+        }
+        TemporalType::Date => {
+            let v = lexical.parse::<Date>().map_err(|_| invalid())?;
+            (day_start(v.year(), v.month(), v.day())?, timezone_minutes(v.timezone())?)
+        }
+        TemporalType::Time => {
+            let v = lexical.parse::<Time>().map_err(|_| invalid())?;
+            (time_of_day(v.hour(), v.minute(), v.second())?, timezone_minutes(v.timezone())?)
+        }
+        TemporalType::GYearMonth => {
+            let v = lexical.parse::<GYearMonth>().map_err(|_| invalid())?;
+            (day_start(v.year(), v.month(), 1)?, timezone_minutes(v.timezone())?)
+        }
+        TemporalType::GYear => {
+            let v = lexical.parse::<GYear>().map_err(|_| invalid())?;
+            (day_start(v.year(), 1, 1)?, timezone_minutes(v.timezone())?)
+        }
+        TemporalType::DayTimeDuration => {
+            let v = lexical.parse::<DayTimeDuration>().map_err(|_| invalid())?;
+            return Ok((milliseconds(v.as_seconds())?, NO_TIMEZONE));
+        }
+        TemporalType::YearMonthDuration => {
+            let v = lexical.parse::<YearMonthDuration>().map_err(|_| invalid())?;
+            let months = v.years().checked_mul(12).and_then(|m| m.checked_add(v.months()));
+            return Ok((months.ok_or_else(invalid)?, NO_TIMEZONE));
+        }
+    };
+    let offset = if tz == NO_TIMEZONE { 0 } else { tz * 60_000 };
+    Ok((local.checked_sub(offset).ok_or_else(invalid)?, tz))
+}
+
+/// Source type of a cast to a temporal type. An untyped argument is
+/// read as xsd:dateTime.
+fn temporal_cast_source(
+    arg: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+) -> Result<TemporalType, String> {
+    if is_lexical_source(arg, query, bindings) {
+        return Err(
+            "Casting a string computed in the circuit to a date, time or duration is not \
+             supported by the transformer"
+                .into(),
+        );
+    }
+    Ok(temporal_type(arg, query, bindings).unwrap_or(TemporalType::DateTime))
+}
+
+/// Lower `expr` to the `i64` value of a temporal of type `ty`.
+fn temporal_value(
+    expr: &Expression,
+    ty: TemporalType,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    use TemporalType::*;
+    match expr {
+        Expression::Literal(l) => Ok(i64_code(temporal_literal(l, ty)?.0)),
+        Expression::Variable(v) => match computed_binding_expr(v.as_str(), query, bindings) {
+            Some(bound) => temporal_value(bound, ty, query, bindings, hidden),
+            None => Ok(format!("(hidden[{}] as i64)", push_temporal_hidden(hidden, "temporal_value", v.as_str(), ty))),
+        },
+        Expression::FunctionCall(Function::Custom(_), args) if args.len() == 1 => {
+            if let Some(folded) = fold_xsd_cast(ty.local_name(), &args[0])? {
+                return Ok(i64_code(temporal_literal(&folded, ty)?.0));
+            }
+            let source = temporal_cast_source(&args[0], query, bindings)?;
+            let value = temporal_value(&args[0], source, query, bindings, hidden)?;
+            match (source, ty) {
+                _ if source == ty => Ok(value),
+                (Date, DateTime) => Ok(value),
+                (DateTime, Date) | (DateTime, Time) => {
+                    let tz = temporal_timezone(&args[0], source, query, bindings, hidden)?;
+                    let f = if ty == Date { "start_of_day" } else { "time_of_day" };
+                    Ok(format!("xpath::{}({}, {})", f, value, tz))
+                }
+                _ => Err(format!(
+                    "Casting xsd:{} to xsd:{} is not supported by the transformer",
+                    source.local_name(),
+                    ty.local_name()
+                )),
+            }
+        }
+        Expression::FunctionCall(Function::Timezone, args) if ty == DayTimeDuration => {
+            if args.len() != 1 {
+                return Err("TIMEZONE requires 1 argument".into());
+            }
+            let source = temporal_type(&args[0], query, bindings).unwrap_or(DateTime);
+            let tz = temporal_timezone(&args[0], source, query, bindings, hidden)?;
+            Ok(format!("xpath::timezone_duration({})", tz))
+        }
+        Expression::FunctionCall(Function::Now, _) if ty == DateTime => Ok("now".into()),
+        Expression::UnaryPlus(a) if ty.is_duration() => temporal_value(a, ty, query, bindings, hidden),
+        Expression::UnaryMinus(a) if ty.is_duration() => {
+            Ok(format!("(0 - {})", temporal_value(a, ty, query, bindings, hidden)?))
+        }
+        Expression::Add(a, b) | Expression::Subtract(a, b) => {
+            let subtract = matches!(expr, Expression::Subtract(_, _));
+            let (ta, tb) = temporal_operand_types(
+                subtract,
+                temporal_type(a, query, bindings),
+                temporal_type(b, query, bindings),
+                ty,
+            )
+            .map_err(|e| format!("Cannot evaluate {} as an xsd:{}: {}", expr, ty.local_name(), e))?;
+            // `duration + point` commutes.
+            let (point, pt, duration, dt) =
+                if ta.is_duration() && !tb.is_duration() { (b, tb, a, ta) } else { (a, ta, b, tb) };
+            let left = temporal_value(point, pt, query, bindings, hidden)?;
+            let right = temporal_value(duration, dt, query, bindings, hidden)?;
+            if pt.is_duration() || (subtract && !dt.is_duration()) {
+                // Durations, or a difference of two points.
+                return Ok(format!("({} {} {})", left, if subtract { "-" } else { "+" }, right));
+            }
+            let right = if subtract { format!("(0 - {})", right) } else { right };
+            let tz = temporal_timezone(point, pt, query, bindings, hidden)?;
+            match (pt, dt) {
+                (_, YearMonthDuration) => Ok(format!("xpath::add_months({}, {}, {})", left, tz, right)),
+                (DateTime, _) => Ok(format!("({} + {})", left, right)),
+                (Date, _) => Ok(format!("xpath::date_add_duration({}, {}, {})", left, tz, right)),
+                _ => Ok(format!("xpath::time_add_duration({}, {}, {})", left, tz, right)),
+            }
+        }
+        _ => Err(format!("Cannot evaluate {} as an xsd:{}", expr, ty.local_name())),
+    }
+}
+
+/// Lower the timezone of `expr`, a date or time of type `ty`: its
+/// offset in minutes, or `NO_TIMEZONE`.
+fn temporal_timezone(
+    expr: &Expression,
+    ty: TemporalType,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    match expr {
+        Expression::Literal(l) => Ok(i64_code(temporal_literal(l, ty)?.1)),
+        Expression::Variable(v) => match computed_binding_expr(v.as_str(), query, bindings) {
+            Some(bound) => temporal_timezone(bound, ty, query, bindings, hidden),
+            None => Ok(format!("(hidden[{}] as i64)", push_temporal_hidden(hidden, "timezone_value", v.as_str(), ty))),
+        },
+        Expression::FunctionCall(Function::Custom(_), args) if args.len() == 1 => {
+            if let Some(folded) = fold_xsd_cast(ty.local_name(), &args[0])? {
+                return Ok(i64_code(temporal_literal(&folded, ty)?.1));
+            }
+            let source = temporal_cast_source(&args[0], query, bindings)?;
+            temporal_timezone(&args[0], source, query, bindings, hidden)
+        }
+        Expression::FunctionCall(Function::Now, _) => Ok(i64_code(0)),
+        // The result of moving a point keeps the point's timezone.
+        Expression::Add(a, b) | Expression::Subtract(a, b) => {
+            let point = if temporal_type(a, query, bindings).is_some_and(|t| t.is_duration()) { b } else { a };
+            temporal_timezone(point, ty, query, bindings, hidden)
+        }
+        _ => Err(format!("{} has no timezone", expr)),
+    }
+}
+
+/// `<`, `<=`, `>`, `>=` and `=` over dates, times and durations
+/// (op:dateTime-less-than etc.): both sides compare as values of the
+/// type of whichever is typed. `None` when neither side is temporal.
+fn temporal_comparison(
+    expr: &Expression,
+    a: &Expression,
+    b: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<Option<String>, String> {
+    let Some(ty) = temporal_type(a, query, bindings).or_else(|| temporal_type(b, query, bindings)) else {
+        return Ok(None);
+    };
+    if is_temporal_type_error(expr, query, bindings) {
+        return Ok(Some("false".into()));
+    }
+    let op = match expr {
+        Expression::Equal(_, _) => "==",
+        Expression::Greater(_, _) => ">",
+        Expression::GreaterOrEqual(_, _) => ">=",
+        Expression::Less(_, _) => "<",
+        Expression::LessOrEqual(_, _) => "<=",
+        _ => return Err("Invalid comparison operator".into()),
+    };
+    let left = temporal_value(a, ty, query, bindings, hidden)?;
+    let right = temporal_value(b, ty, query, bindings, hidden)?;
+    Ok(Some(format!("{} {} {}", left, op, right)))
+}
+
+/// `YEAR` … `SECONDS` (fn:year-from-dateTime etc.), in the value's own
+/// timezone. `SECONDS` is an xsd:decimal.
+fn temporal_component(
+    func: &Function,
+    arg: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<String, String> {
+    let (name, accessor, types): (_, _, &[TemporalType]) = match func {
+        Function::Year => ("YEAR", "year_from_instant", &[TemporalType::DateTime, TemporalType::Date]),
+        Function::Month => ("MONTH", "month_from_instant", &[TemporalType::DateTime, TemporalType::Date]),
+        Function::Day => ("DAY", "day_from_instant", &[TemporalType::DateTime, TemporalType::Date]),
+        Function::Hours => ("HOURS", "hours_from_instant", &[TemporalType::DateTime, TemporalType::Time]),
+        Function::Minutes => ("MINUTES", "minutes_from_instant", &[TemporalType::DateTime, TemporalType::Time]),
+        _ => ("SECONDS", "seconds_from_instant", &[TemporalType::DateTime, TemporalType::Time]),
+    };
+    let ty = temporal_type(arg, query, bindings).unwrap_or(TemporalType::DateTime);
+    if !types.contains(&ty) {
+        return Err(format!("{} is not defined on xsd:{}", name, ty.local_name()));
+    }
+    let value = temporal_value(arg, ty, query, bindings, hidden)?;
+    let tz = temporal_timezone(arg, ty, query, bindings, hidden)?;
+    Ok(format!("(xpath::{}({}, {}) as Field)", accessor, value, tz))
+}
+
+/// Lower a temporal computed in-circuit to a fresh `computed[k]`
+/// witness holding its canonical lexical form, checked against the
+/// value, and the literal term built over it. xsd:dateTime carries its
+/// instant in the special slot (`utils::construct_datetime`); the other
+/// types hash like any `STRDT` literal.
+fn temporal_term(
+    expr: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<StringOperand, String> {
+    let ty = temporal_type(expr, query, bindings)
+        .ok_or_else(|| format!("Cannot evaluate {} as a date, time or duration", expr))?;
+    if matches!(ty, TemporalType::GYear | TemporalType::GYearMonth) {
+        return Err(format!("Computing an xsd:{} in the circuit is not supported", ty.local_name()));
+    }
+    let value = temporal_value(expr, ty, query, bindings, hidden)?;
+    let k = push_computed_witness(
+        hidden,
+        serde_json::json!({
+            "type": COMPUTED_WITNESS_TYPE,
+            "computedType": "temporal_lexical",
+            "datatype": format!("{}{}", XSD, ty.local_name()),
+            "input": {"type": "expression", "value": expr.to_string()},
+        }),
+    );
+    let datatype = encode_string_expr(&format!("{}{}", XSD, ty.local_name()));
+    let (checks, witness) = if ty.is_duration() {
+        let check = if ty == TemporalType::DayTimeDuration {
+            "is_canonical_day_time_duration_lexical"
+        } else {
+            "is_canonical_year_month_duration_lexical"
+        };
+        (
+            format!("assert(xpath::{c}(computed[{k}].bytes, computed[{k}].length, {v})); ", c = check, k = k, v = value),
+            format!("utils::construct_strdt(computed[{}], {})", k, datatype),
+        )
+    } else {
+        let tz = temporal_timezone(expr, ty, query, bindings, hidden)?;
+        let check = match ty {
+            TemporalType::DateTime => "is_canonical_datetime_lexical",
+            TemporalType::Date => "is_canonical_date_lexical",
+            _ => "is_canonical_time_lexical",
+        };
+        let witness = if ty == TemporalType::DateTime {
+            format!(
+                "utils::construct_datetime(computed[{k}], temporal_{k}, timezone_{k} != xpath::NO_TIMEZONE, {d})",
+                k = k,
+                d = datatype
+            )
+        } else {
+            format!("utils::construct_strdt(computed[{}], {})", k, datatype)
+        };
+        (
+            format!(
+                "let temporal_{k} = {v}; let timezone_{k} = {tz}; \
+                 assert(xpath::{c}(computed[{k}].bytes, computed[{k}].length, temporal_{k}, timezone_{k})); ",
+                k = k,
+                v = value,
+                tz = tz,
+                c = check
+            ),
+            witness,
+        )
+    };
+    Ok(StringOperand { checks, witness, input: serde_json::json!({"type": "computed", "value": k}) })
+}
+
+/// `TZ(x)`: a fresh plain-string witness checked to be the timezone of
+/// `x` as written -- empty, `Z`, or `±hh:mm`.
+fn tz_string(
+    arg: &Expression,
+    query: &QueryInfo,
+    bindings: &BTreeMap<String, Term>,
+    hidden: &mut Vec<serde_json::Value>,
+) -> Result<StringOperand, String> {
+    let ty = temporal_type(arg, query, bindings).unwrap_or(TemporalType::DateTime);
+    if ty.is_duration() {
+        return Err(format!("TZ is not defined on xsd:{}", ty.local_name()));
+    }
+    let tz = temporal_timezone(arg, ty, query, bindings, hidden)?;
+    let input = match arg {
+        Expression::Variable(v) => term_to_hidden_json(&Term::Variable(v.as_str().to_string())),
+        _ => serde_json::json!({"type": "expression", "value": arg.to_string()}),
+    };
+    let k = push_computed_witness(
+        hidden,
+        serde_json::json!({
+            "type": COMPUTED_WITNESS_TYPE,
+            "computedType": "timezone_string",
+            "input": input,
+        }),
+    );
+    Ok(StringOperand {
+        checks: format!(
+            "utils::bind_computed_plain_string_literal(computed[{k}]); \
+             assert(xpath::is_timezone_lexical(computed[{k}].bytes, computed[{k}].length, {tz})); ",
+            k = k,
+            tz = tz
+        ),
+        witness: format!("computed[{}]", k),
+        input: serde_json::json!({"type": "computed", "value": k}),
+    })
+}
@@ -8,6 +8,8 @@
 use spargebra::algebra::Expression;
 use spargebra::term::{GroundTerm, TriplePattern};
 
+use crate::{DisclosureMode, QueryParameter};
+
 #[derive(Clone, Debug)]
 pub enum Term {
     Variable(String),
@@ -20,6 +22,13 @@ pub enum Term {
     /// hash with term-type tag `0` and create the
     /// signer/transform mismatch flagged by roborev (2026-05-04).
     DefaultGraph,
+    /// The result of a `BIND` expression computed in-circuit -- a
+    /// string-valued function (`REPLACE`, `ENCODE_FOR_URI`, ...) or a
+    /// term constructor (`IRI`, `STRDT`, ...). Only ever appears as the
+    /// right-hand side of a [`Binding`]; the emitter materialises it as
+    /// a `bind_<var>` local holding the computed `TermWitness`, and
+    /// references to the variable resolve to that local's `hash`.
+    Computed(Box<Expression>),
 }
 
 #[derive(Clone, Debug)]
@@ -36,6 +45,18 @@ pub enum GraphContext {
     Default,
     NamedNode(String),
     Variable(String),
+    /// The default graph of a query with `FROM` clauses: the merge of
+    /// the listed graphs, so each triple may come from any of them.
+    Merged(Vec<String>),
+}
+
+/// A query's `FROM` / `FROM NAMED` clauses. The default graph is the
+/// merge of `default_graphs`, and `GRAPH` patterns range over
+/// `named_graphs` only.
+#[derive(Clone, Debug)]
+pub struct DatasetClause {
+    pub(crate) default_graphs: Vec<String>,
+    pub(crate) named_graphs: Vec<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -199,6 +220,90 @@ pub struct PrefixNonExistenceConstraint {
     pub absent_terms: [Term; 4],
 }
 
+/// A completeness run (`TransformOptions::completeness`). The query is
+/// a single triple pattern whose `prefix_kind` fixed positions are
+/// ground; the circuit discloses every binding of the free position by
+/// proving the quads behind them are the whole run under that prefix
+/// in the signer's prefix-run commitment, bracketed by leaves under
+/// other prefixes or by sentinels.
+///
+/// Witness shape: the private `run: CompleteRun` (rows plus the two
+/// bracket leaves), the public `run_len`, and the public
+/// `boundary_cases_run` pair, whose tags follow [`BoundaryCase`]: the
+/// left bracket is `Lower` (low sentinel) or `Middle`, the right one
+/// `Middle` or `Upper` (high sentinel).
+///
+/// See `spec/prefix-tree-commitment.md` Sec.9.
+#[derive(Clone, Debug)]
+pub struct CompleteRun {
+    /// Which prefix keys the run. Only `Prefix3SpG` ships.
+    pub prefix_kind: PrefixKind,
+    /// Subject / predicate / object / graph of the triple pattern. The
+    /// fixed positions are constants; the free one is
+    /// `Term::Variable(variable)`.
+    pub(crate) terms: [Term; 4],
+    /// The projected variable at the free position, disclosed as the
+    /// public `variables.<variable>` array.
+    pub(crate) variable: String,
+    /// Row capacity: the length of that array and of `run.rows`.
+    pub(crate) capacity: usize,
+}
+
+/// A proven extremum (`TransformOptions::extremum`). The query is a
+/// `MIN` or `MAX` over the free position of a single triple pattern
+/// whose `prefix_kind` fixed positions are ground; the circuit discloses
+/// the one binding holding the extremum and proves it is the first
+/// (`MIN`) or last (`MAX`) leaf under that prefix in the signer's
+/// value-sorted prefix-run commitment.
+///
+/// Witness shape: the private `extremum: Extremum` (the row, its
+/// opened object value and the neighbouring leaf) and the public
+/// `boundary_case_extremum` tag, which follows [`BoundaryCase`]: a MIN's
+/// neighbour is `Lower` (low sentinel) or `Middle`, a MAX's `Middle`
+/// or `Upper` (high sentinel).
+///
+/// See `spec/prefix-tree-commitment.md` Sec.10.
+#[derive(Clone, Debug)]
+pub struct Extremum {
+    /// Which prefix keys the run. Only `Prefix3SpG` ships.
+    pub prefix_kind: PrefixKind,
+    /// Subject / predicate / object / graph of the triple pattern, as
+    /// in [`CompleteRun::terms`].
+    pub(crate) terms: [Term; 4],
+    /// The aggregate's source variable at the free position, disclosed
+    /// as `variables.<variable>`.
+    pub(crate) variable: String,
+    /// `MAX` if true, `MIN` otherwise.
+    pub(crate) maximum: bool,
+}
+
+/// An aggregate computed in-circuit over private rows
+/// (`TransformOptions::aggregate_rows`). The query's body is checked
+/// once per active row, the rows are proven distinct by sorting them on
+/// `key` and asserting adjacent keys strictly increase, and only the
+/// `COUNT` / `SUM` (or whether it reaches a public threshold) is public.
+///
+/// The rows are the solutions the prover chose to witness, so the
+/// aggregate is a lower bound: at least this many distinct solutions,
+/// or at least this sum of non-negative integers.
+///
+/// See `spec/disclosure.md` Sec.8.
+#[derive(Clone, Debug)]
+pub struct InCircuitAggregate {
+    /// `Count`, `CountDistinct`, `CountSolutions` or `Sum`.
+    pub kind: AggregateKind,
+    /// The aggregate's source variable; `None` for `COUNT(*)`.
+    pub(crate) source: Option<String>,
+    /// Row bound: the number of body copies the circuit checks.
+    pub(crate) rows: usize,
+    /// Publish `aggregate >= threshold` rather than the aggregate.
+    pub(crate) threshold: bool,
+    /// Variables whose bindings key a row for distinctness: the source
+    /// for a `DISTINCT` aggregate, else every top-level solution
+    /// variable. Distinct keys imply distinct solutions.
+    pub(crate) key: Vec<String>,
+}
+
 /// One non-membership obligation, lowered from a `FILTER(NOT EXISTS { t })`
 /// block (or, equivalently, from `MINUS` after the algebra rewrite). The
 /// circuit asserts:
@@ -456,4 +561,33 @@ pub struct QueryInfo {
     /// `OFFSET n` — propagated for completeness; the verifier slices
     /// after sorting.
     pub(crate) offset: Option<usize>,
+    /// The query calls `NOW()`. Every circuit variant then takes the
+    /// verifier-supplied public `now` input, so they share one ABI.
+    pub(crate) uses_now: bool,
+    /// `TransformOptions::parameters`, in public `params` order.
+    pub(crate) parameters: Vec<QueryParameter>,
+    /// Projected variables published other than by revealing them, in
+    /// projection order. Decided for the whole query so that OPTIONAL
+    /// variants share the `salts` / `verifier_context` inputs.
+    pub(crate) disclosure: Vec<(String, DisclosureMode)>,
+    /// The query's `FROM` / `FROM NAMED` clauses, if it has any.
+    pub(crate) dataset: Option<DatasetClause>,
+    /// Every circuit variant takes the public `context` input.
+    pub(crate) binds_context: bool,
+    /// The non-projected variable keying the public `nullifier`.
+    pub(crate) nullifier_secret: Option<String>,
+    /// The circuit takes the public `answer` input, which selects the
+    /// matched or unmatched arm of the query's single `EasyOptional`.
+    pub(crate) answer_bit: bool,
+    /// The circuit proves its rows are every answer
+    /// (`TransformOptions::completeness`). The query's pattern is then
+    /// empty: the run stands in for the BGP.
+    pub(crate) complete_run: Option<CompleteRun>,
+    /// The circuit proves its one row holds the `MIN` / `MAX` over every
+    /// match (`TransformOptions::extremum`). As with `complete_run`, the
+    /// query's pattern is then empty.
+    pub(crate) extremum: Option<Extremum>,
+    /// The circuit computes the query's aggregate over private rows
+    /// (`TransformOptions::aggregate_rows`).
+    pub(crate) in_circuit_aggregate: Option<InCircuitAggregate>,
 }
@@ -10,7 +10,9 @@
 //!
 //! ```text
 //! parse   → spargebra parsing & query-form dispatch
+//! consts  → options → the `noir/lib/consts` package
 //! ir      → algebra-level data types
+//! normalize → canonical renaming / ordering / folding, fingerprint
 //! lower   → GraphPattern → IR
 //! expr    → Expression → Noir code strings
 //! emit    → IR → sparql.nr / main.nr / Nargo.toml
@@ -20,26 +22,37 @@
 //! `transform_query` orchestrates these layers; everything else is
 //! deliberately private to the crate.
 
+mod consts;
 mod emit;
+mod encode;
 mod expr;
 mod ir;
 mod lower;
 mod metadata;
+mod normalize;
 mod parse;
 
+pub use crate::consts::{ConstsPackage, FieldHash, SignatureScheme};
+pub use crate::encode::StringHash;
 pub use crate::expr::{ieee754_equal, ieee754_less_than, FloatSpecial};
 pub use crate::ir::{
-    Aggregate, AggregateKind, Assertion, Binding, BoundaryCase, ContextualizedTriple, EasyOptional,
-    GraphContext, NonExistenceConstraint, OptionalBlock, OrderDirection, OrderKey, PatternInfo,
+    Aggregate, AggregateKind, Assertion, Binding, BoundaryCase, CompleteRun, ContextualizedTriple,
+    DatasetClause, EasyOptional, Extremum, GraphContext, InCircuitAggregate, NonExistenceConstraint, OptionalBlock, OrderDirection, OrderKey, PatternInfo,
     PrefixKind, PrefixNonExistenceConstraint, QueryInfo, Term,
 };
 
+use crate::consts::build_consts_package;
 use crate::emit::{
     build_nargo_toml, collect_all_optional_blocks, fill_main_nr_template,
     generate_circuit_for_optional_combination,
 };
 use crate::lower::process_query_with_options_and_form;
-use crate::metadata::{build_base_metadata, build_variant_metadata};
+use crate::metadata::{
+    attach_datasets, attach_encoding, attach_fingerprint, attach_public_inputs, build_base_metadata, build_variant_metadata,
+};
+use crate::normalize::{fingerprint, normalize_pattern};
+
+use std::collections::BTreeMap;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::wasm_bindgen;
@@ -50,7 +63,14 @@ pub struct TransformResult {
     pub sparql_nr: String,
     pub main_nr: String,
     pub nargo_toml: String,
+    /// The `noir/lib/consts` package the circuit is to be compiled
+    /// against, rendered for the configured hashes and signature scheme.
+    pub consts: ConstsPackage,
     pub metadata: serde_json::Value,
+    /// `sha256:<hex>` content hash of the lowered query and the
+    /// options it was transformed under; equal fingerprints mean equal
+    /// circuits. Also surfaced as `metadata.fingerprint`.
+    pub fingerprint: String,
     /// Additional circuits for OPTIONAL combinations (if any)
     /// Each entry represents a different combination of optional patterns being matched
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -100,6 +120,71 @@ pub const DEFAULT_PATH_SEGMENT_MAX: usize = 4;
 /// substituted at setup time.
 pub const DEFAULT_STRING_LEN_MAX: usize = 64;
 
+/// Default Merkle tree depth (`consts::MERKLE_DEPTH`), matching the
+/// TypeScript setup default.
+pub const DEFAULT_MERKLE_DEPTH: usize = 11;
+
+/// Merkle tree depths the TypeScript setup builds circuits for
+/// (`merkleDepths` in `src/config.ts`).
+pub const MERKLE_DEPTHS: [usize; 12] = [9, 11, 13, 15, 17, 19, 21, 23, 25, 27, 29, 31];
+
+/// Default clock skew, in milliseconds, a verifier accepts between the
+/// public `now` input of a `NOW()` query and its own clock. Surfaced as
+/// `metadata.now.toleranceMs`; configurable via
+/// [`TransformOptions::now_tolerance_ms`].
+pub const DEFAULT_NOW_TOLERANCE_MS: u64 = 300_000;
+
+/// A query variable whose value the verifier supplies, conventionally
+/// written `$name`. Its term hash becomes a public input of the circuit
+/// instead of being matched or disclosed, so one circuit (and
+/// verification key) serves every value of the parameter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryParameter {
+    /// Variable name, without the `$` / `?` sigil.
+    pub name: String,
+    /// Datatype IRI when the parameter is a literal; `None` for an IRI.
+    /// Tells the verifier how to encode the value it supplies.
+    pub datatype: Option<String>,
+}
+
+/// How a projected variable is published in the circuit's public
+/// `disclosed` output. Variables without an entry in
+/// [`TransformOptions::disclosure`] are revealed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisclosureMode {
+    /// Publish the term hash itself.
+    Reveal,
+    /// Publish `hash2([term, salt])` for a private, prover-chosen salt.
+    /// The prover can later open the commitment by handing over the
+    /// term and salt.
+    Commit,
+    /// Publish `hash2([term, verifier_context])` for the public
+    /// `verifier_context` input, so two proofs given to the same
+    /// context link without revealing the term. A low-entropy term
+    /// can be recovered by hashing candidates, so reserve this for
+    /// identifiers.
+    Pseudonym,
+    /// Publish the term hash encrypted to the public `verifier_key`
+    /// (a Grumpkin point): `term + hash2([shared.x, slot])`, where
+    /// `shared` is the ECDH secret of a private ephemeral scalar and
+    /// the verifier key. The ephemeral public key is a public input, so
+    /// only the holder of the verifier's secret key can decrypt, while
+    /// anyone can check the proof.
+    Encrypt,
+}
+
+impl DisclosureMode {
+    /// Tag used in `metadata.disclosure[].mode`.
+    pub fn metadata_tag(self) -> &'static str {
+        match self {
+            DisclosureMode::Reveal => "reveal",
+            DisclosureMode::Commit => "commit",
+            DisclosureMode::Pseudonym => "pseudonym",
+            DisclosureMode::Encrypt => "encrypt",
+        }
+    }
+}
+
 /// Options for the transform operation
 #[derive(Clone, Debug)]
 pub struct TransformOptions {
@@ -119,6 +204,113 @@ pub struct TransformOptions {
     /// Defaults to [`DEFAULT_STRING_LEN_MAX`] (64). See
     /// `spec/encoding.md` sec.6.5.
     pub string_len_max: usize,
+    /// How far the public `now` input may drift from the verifier's
+    /// clock. Defaults to [`DEFAULT_NOW_TOLERANCE_MS`] (five minutes).
+    pub now_tolerance_ms: u64,
+    /// Query variables lowered to public `params` inputs rather than
+    /// inlined or bound by the prover. Empty by default.
+    pub parameters: Vec<QueryParameter>,
+    /// Canonicalise the query before lowering -- rename variables to
+    /// `v0`, `v1`, …, sort BGP triples and fold constant casts -- so
+    /// alpha-equivalent queries share a circuit and a fingerprint.
+    /// `metadata.variableMap` maps the canonical names back. Off by
+    /// default.
+    pub normalize: bool,
+    /// The string hash the `consts` package was set up with. When set,
+    /// every constant `consts::encode_string("…")` is evaluated at
+    /// transform time and emitted as a `Field` literal, saving the
+    /// in-circuit string hash; the circuit is then only valid against
+    /// a `consts` built with this hash. Under `FieldHash::Poseidon` the
+    /// `hash2` / `hash4` calls over such literals are folded as well;
+    /// the other field hashes stay in-circuit. `None` (the default)
+    /// leaves the calls in the circuit.
+    pub static_string_hash: Option<StringHash>,
+    /// Per-variable disclosure mode, keyed by projected variable name.
+    /// Unlisted variables are revealed. Empty by default.
+    pub disclosure: BTreeMap<String, DisclosureMode>,
+    /// Number of signed datasets the proof draws on, each with its own
+    /// issuer key and root. Defaults to 1.
+    pub datasets: usize,
+    /// Dataset index for the triple patterns under each `GRAPH <iri>`.
+    /// Other patterns are checked against dataset 0; `GRAPH ?g` is
+    /// rejected when `datasets` is above 1. Empty by default.
+    pub dataset_graphs: BTreeMap<String, usize>,
+    /// Depth of a Merkle registry of authorised issuer keys. When set,
+    /// `main` takes the registry root as the public `issuer_registry`
+    /// input and each issuer key privately with its inclusion path, so
+    /// a proof shows the data was signed by some accredited issuer
+    /// without revealing which. `None` (the default) takes the keys
+    /// themselves.
+    pub issuer_registry_depth: Option<usize>,
+    /// Add a public `context` input -- a verifier-chosen nonce or
+    /// domain -- so a proof only verifies for the context it was made
+    /// for. Off by default.
+    pub bind_context: bool,
+    /// Query variable whose bound term is the holder secret behind a
+    /// public `nullifier = hash2([hash2([secret, context]), scope])`,
+    /// where `scope` is a further public input. A verifier that keeps
+    /// the nullifiers it has seen detects a credential used twice in
+    /// the same context and scope. Implies `bind_context`; the variable
+    /// must be bound outside OPTIONAL / UNION and not projected. `None`
+    /// by default.
+    pub nullifier_secret: Option<String>,
+    /// Sign roots together with an `issued_at` / `expires_at` window
+    /// (`types::TimedRoot`) and check the window against a public
+    /// `now` input, shared with `NOW()` when the query uses it. Off by
+    /// default.
+    pub root_validity: bool,
+    /// Also prove each root's signed message absent from a revocation
+    /// list committed by the public `revocation_root` input. Requires
+    /// `root_validity`. Off by default.
+    pub revocation_check: bool,
+    /// Field hash behind `consts::hash2` / `consts::hash4`. Defaults to
+    /// Pedersen.
+    pub field_hash: FieldHash,
+    /// String hash behind `consts::hash_string`. Defaults to sha256,
+    /// the only one with the variable-length API round-2 byte binding
+    /// needs.
+    pub string_hash: StringHash,
+    /// Issuer signature scheme. Defaults to `BabyJubJubOpt`.
+    pub signature: SignatureScheme,
+    /// Depth of each dataset's Merkle tree, so at most
+    /// `2^(merkle_depth - 1) - 2` triples besides the sentinels.
+    /// One of [`MERKLE_DEPTHS`]; defaults to [`DEFAULT_MERKLE_DEPTH`] (11).
+    pub merkle_depth: usize,
+    /// Prove the query's answer either way: `main` takes a public
+    /// `answer` bit, true when a solution exists and false when
+    /// non-membership brackets show there is none. For `ASK` and plain
+    /// `SELECT` over a single triple pattern that is ground or has a
+    /// free object; other shapes are rejected. Off by default.
+    pub answer_bit: bool,
+    /// Prove the disclosed rows are all the answers: for a `SELECT` over
+    /// one triple pattern with ground subject, predicate and graph,
+    /// `main` discloses up to this many bindings of the object and
+    /// proves they are the whole run of quads under that prefix in the
+    /// signer's prefix-run commitment. `None` (the default) proves one
+    /// row per proof.
+    pub completeness: Option<usize>,
+    /// Prove a `MIN` / `MAX` is over every match: for a query whose only
+    /// projection is `MIN(?o)` or `MAX(?o)` over one triple pattern with
+    /// ground subject, predicate and graph, `main` discloses the one
+    /// binding holding the extremum and proves no other quad under that
+    /// prefix has a lesser / greater value, in the signer's value-sorted
+    /// prefix-run commitment. The signer only commits prefixes whose
+    /// objects all have an integer, boolean or dateTime value, so no
+    /// proof exists for a prefix with any other object. Off by default.
+    pub extremum: bool,
+    /// Compute the query's `COUNT` / `SUM` in-circuit over up to this
+    /// many private rows: `main` takes the rows as private witnesses,
+    /// proves them distinct solutions, and makes only the aggregate
+    /// public. The rows witness a lower bound -- "at least this many /
+    /// this much" -- and metadata marks it `bound: "lowerBound"`. A
+    /// `SUM` adds `xsd:integer` values only; one whose source is never
+    /// such a literal is rejected. `None` (the default) leaves
+    /// aggregates to the verifier over disclosed rows.
+    pub aggregate_rows: Option<usize>,
+    /// With `aggregate_rows`, publish only whether the aggregate reaches
+    /// the public `threshold` input instead of the aggregate itself.
+    /// Off by default.
+    pub aggregate_threshold: bool,
 }
 
 impl Default for TransformOptions {
@@ -128,6 +320,27 @@ impl Default for TransformOptions {
             optional_cap: DEFAULT_OPTIONAL_CAP,
             path_segment_max: DEFAULT_PATH_SEGMENT_MAX,
             string_len_max: DEFAULT_STRING_LEN_MAX,
+            now_tolerance_ms: DEFAULT_NOW_TOLERANCE_MS,
+            parameters: Vec::new(),
+            normalize: false,
+            static_string_hash: None,
+            disclosure: BTreeMap::new(),
+            datasets: 1,
+            dataset_graphs: BTreeMap::new(),
+            issuer_registry_depth: None,
+            bind_context: false,
+            nullifier_secret: None,
+            root_validity: false,
+            revocation_check: false,
+            field_hash: FieldHash::Pedersen,
+            string_hash: StringHash::Sha256,
+            signature: SignatureScheme::BabyJubJubOpt,
+            merkle_depth: DEFAULT_MERKLE_DEPTH,
+            answer_bit: false,
+            completeness: None,
+            extremum: false,
+            aggregate_rows: None,
+            aggregate_threshold: false,
         }
     }
 }
@@ -150,11 +363,140 @@ pub fn transform_query_with_options(query_str: &str, options: TransformOptions)
     // to reset. See audit item 9 in
     // `notes/research/pr-review-audit-2026-05-03.md` (sparql_noir
     // #37 row, generalised by #42's regression).
+    if options.datasets == 0 {
+        return Err("TransformOptions::datasets must be at least 1".into());
+    }
+    if let Some((iri, index)) = options.dataset_graphs.iter().find(|(_, i)| **i >= options.datasets) {
+        return Err(format!(
+            "GRAPH <{}> is mapped to dataset {}, but only {} dataset(s) are configured",
+            iri, index, options.datasets
+        ));
+    }
+
+    if let Some(depth) = options.issuer_registry_depth {
+        if depth == 0 {
+            return Err("TransformOptions::issuer_registry_depth must be at least 1".into());
+        }
+        if options.skip_signing {
+            return Err("An issuer registry needs signature verification; drop skip_signing".into());
+        }
+    }
+
+    if !MERKLE_DEPTHS.contains(&options.merkle_depth) {
+        return Err(format!(
+            "TransformOptions::merkle_depth must be one of {:?}, as in src/config.ts merkleDepths, got {}",
+            MERKLE_DEPTHS, options.merkle_depth
+        ));
+    }
+    if matches!(options.string_hash, StringHash::Sha384 | StringHash::Sha512) {
+        return Err(format!(
+            "TransformOptions::string_hash {} is unsupported: its {}-byte digest does not fit a Field, \
+             and src/config.ts leaves it out of stringHashes",
+            options.string_hash.name(),
+            options.string_hash.output_size()
+        ));
+    }
+    if options.signature == SignatureScheme::BabyJubJub {
+        return Err(
+            "TransformOptions::signature babyjubjub is unsupported: sign.ts has no signer for it, \
+             and src/config.ts leaves it out of signatures; use BabyJubJubOpt"
+                .into(),
+        );
+    }
+    if let Some(hash) = options.static_string_hash
+        && hash != options.string_hash
+    {
+        return Err(format!(
+            "static_string_hash ({}) disagrees with string_hash ({}); folded constants would not match consts",
+            hash.name(),
+            options.string_hash.name()
+        ));
+    }
+    if options.answer_bit && options.skip_signing {
+        return Err("A false answer is proven against the signed sentinels; drop skip_signing".into());
+    }
+    if let Some(capacity) = options.completeness {
+        let tree_capacity = (1usize << (options.merkle_depth - 1)).saturating_sub(2);
+        if capacity == 0 || capacity > tree_capacity {
+            return Err(format!(
+                "TransformOptions::completeness must be between 1 and the tree capacity {}, got {}",
+                tree_capacity, capacity
+            ));
+        }
+        if options.skip_signing {
+            return Err("Completeness is proven against the signed prefix-run tree; drop skip_signing".into());
+        }
+        if options.answer_bit {
+            return Err("TransformOptions::completeness and answer_bit are exclusive".into());
+        }
+        if options.disclosure.values().any(|mode| *mode != DisclosureMode::Reveal) {
+            return Err("TransformOptions::completeness reveals the whole run; drop the disclosure modes".into());
+        }
+    }
+    if options.extremum {
+        if options.skip_signing {
+            return Err("An extremum is proven against the signed value-sorted tree; drop skip_signing".into());
+        }
+        if options.answer_bit || options.completeness.is_some() {
+            return Err("TransformOptions::extremum is exclusive with answer_bit and completeness".into());
+        }
+    }
+    if options.aggregate_threshold && options.aggregate_rows.is_none() {
+        return Err("TransformOptions::aggregate_threshold requires aggregate_rows".into());
+    }
+    if let Some(rows) = options.aggregate_rows {
+        if rows == 0 {
+            return Err("TransformOptions::aggregate_rows must be at least 1".into());
+        }
+        if options.skip_signing {
+            return Err("In-circuit aggregates count signed rows; drop skip_signing".into());
+        }
+        if options.answer_bit || options.completeness.is_some() || options.extremum {
+            return Err(
+                "TransformOptions::aggregate_rows is exclusive with answer_bit, completeness and extremum".into(),
+            );
+        }
+        if !options.disclosure.is_empty() || options.nullifier_secret.is_some() {
+            return Err("TransformOptions::aggregate_rows keeps every row private; drop the disclosure modes and nullifier".into());
+        }
+    }
+    if options.root_validity && options.skip_signing {
+        return Err("Root validity windows need signature verification; drop skip_signing".into());
+    }
+    if options.revocation_check && !options.root_validity {
+        return Err("TransformOptions::revocation_check requires root_validity".into());
+    }
+
     let query = crate::parse::parse_query(query_str)?;
     let root = crate::parse::root_pattern(&query);
     let form = crate::parse::query_form(&query);
 
-    let info = process_query_with_options_and_form(root, &options, form)?;
+    let normalized = options
+        .normalize
+        .then(|| normalize_pattern(root, &options.parameters));
+    let root = normalized.as_ref().map_or(root, |n| &n.pattern);
+    let variable_map = normalized.as_ref().map(|n| &n.variable_map);
+    // Options name variables as the query spells them; the lowered
+    // query (and so the fingerprint) uses the canonical names.
+    let mut options = options;
+    if let Some(map) = variable_map {
+        let canonical = |name: String| {
+            map.iter()
+                .find(|(_, original)| **original == name)
+                .map_or(name, |(c, _)| c.clone())
+        };
+        options.disclosure = std::mem::take(&mut options.disclosure)
+            .into_iter()
+            .map(|(name, mode)| (canonical(name), mode))
+            .collect();
+        options.nullifier_secret = options.nullifier_secret.take().map(canonical);
+    }
+
+    let dataset = crate::parse::query_dataset(&query);
+    let info = process_query_with_options_and_form(root, &options, form, dataset.as_ref())?;
+    let fingerprint = fingerprint(&info, &options);
+    let consts = build_consts_package(&options);
+    let consts_digest = consts.digest();
 
     // Collect all optional blocks (flatten nested optionals for now).
     // Easy-case OPTIONALs don't show up here — they bypass the
@@ -190,36 +532,30 @@ pub fn transform_query_with_options(query_str: &str, options: TransformOptions)
     )?;
 
     let num_not_exists = info.pattern.not_exists.len();
-    let main_nr = fill_main_nr_template(
-        options.skip_signing,
-        base.has_hidden,
-        base.has_not_exists,
-        num_not_exists,
-        base.has_prefix3,
-        base.bgp_prefix3_len,
-        base.num_prefix3_dispatches,
-    );
+    let main_nr = fill_main_nr_template(options.skip_signing, &base, num_not_exists);
 
-    // EBV pulls in `dep::ebv`; that detection lives at the same layer as
-    // the `Nargo.toml` shape, so they share a derivation step.
-    let needs_ebv = base.hidden.iter().any(|h| {
-        h.get("computedType").and_then(|v| v.as_str())
-            .map(|t| t == "ebv_value" || t == "ebv_datatype")
-            .unwrap_or(false)
-    });
-    let nargo_toml = build_nargo_toml(options.skip_signing, needs_ebv, base.needs_xpath);
 
-    let metadata = build_base_metadata(
+    let mut metadata = build_base_metadata(
         &info,
         &all_optionals,
         options.skip_signing,
         &base.hidden,
+        &base.computed,
         options.string_len_max,
     );
+    attach_public_inputs(&mut metadata, &info, &base.parameter_values, options.now_tolerance_ms);
+    attach_datasets(&mut metadata, &info, &options, &base.bgp_datasets);
+    attach_fingerprint(&mut metadata, &fingerprint, variable_map);
+    attach_encoding(&mut metadata, &options, &consts_digest);
 
     // Power-set of OPTIONAL bitmasks, minus the all-matched case (that's
     // the base circuit). For n=0 this loop runs zero times.
     let mut optional_circuits = Vec::new();
+    // Variants share the base `Nargo.toml`; one whose unmatched
+    // OPTIONAL leaves a FILTER variable unbound can need the
+    // three-valued `ebv` lowering even when the base does not.
+    let mut needs_ebv = base.needs_ebv;
+    let mut needs_lang = base.needs_lang;
     if num_optionals > 0 {
         let num_combinations = 1usize << num_optionals;
         for combo in 0..(num_combinations - 1) {
@@ -234,14 +570,21 @@ pub fn transform_query_with_options(query_str: &str, options: TransformOptions)
                 &options,
             )?;
 
-            let circuit_metadata = build_variant_metadata(
+            needs_ebv |= circuit.needs_ebv;
+            needs_lang |= circuit.needs_lang;
+            let mut circuit_metadata = build_variant_metadata(
                 &info,
                 &all_optionals,
                 &matched_indices,
                 options.skip_signing,
                 &circuit.hidden,
+                &circuit.computed,
                 options.string_len_max,
             );
+            attach_public_inputs(&mut circuit_metadata, &info, &circuit.parameter_values, options.now_tolerance_ms);
+            attach_datasets(&mut circuit_metadata, &info, &options, &circuit.bgp_datasets);
+            attach_fingerprint(&mut circuit_metadata, &fingerprint, variable_map);
+            attach_encoding(&mut circuit_metadata, &options, &consts_digest);
 
             optional_circuits.push(OptionalCircuit {
                 matched_optionals: matched_indices,
@@ -251,11 +594,15 @@ pub fn transform_query_with_options(query_str: &str, options: TransformOptions)
         }
     }
 
+    let nargo_toml = build_nargo_toml(&options, &consts, needs_ebv, needs_lang, base.needs_xpath);
+
     Ok(TransformResult {
         sparql_nr: base.sparql_nr,
         main_nr,
         nargo_toml,
+        consts,
         metadata,
+        fingerprint,
         optional_circuits,
     })
 }
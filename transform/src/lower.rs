@@ -9,15 +9,16 @@
 use std::collections::BTreeSet;
 
 use spargebra::algebra::{
-    AggregateExpression, AggregateFunction, Expression, GraphPattern, OrderExpression,
+    AggregateExpression, AggregateFunction, Expression, Function, GraphPattern, OrderExpression,
     PropertyPathExpression,
 };
 use spargebra::term::{GroundTerm, Literal, NamedNode, NamedNodePattern, TermPattern, TriplePattern, Variable};
 
 use crate::parse::QueryForm;
+use crate::emit::pattern_has_not_exists;
 use crate::{
-    Aggregate, AggregateKind, Assertion, Binding, ContextualizedTriple, EasyOptional, GraphContext,
-    OptionalBlock, OrderDirection, OrderKey, PatternInfo, QueryInfo, Term, TransformOptions,
+    Aggregate, AggregateKind, Assertion, Binding, CompleteRun, ContextualizedTriple, DatasetClause, DisclosureMode,
+    EasyOptional, Extremum, GraphContext, InCircuitAggregate, OptionalBlock, OrderDirection, OrderKey, PatternInfo, QueryInfo, QueryParameter, Term, TransformOptions,
 };
 
 /// Per-query source of fresh identifiers. Threaded through the
@@ -960,6 +961,9 @@ fn absent_terms_from_pattern(ct: &ContextualizedTriple) -> Result<[Term; 4], Str
             Term::Static(GroundTerm::NamedNode(NamedNode::new_unchecked(iri.clone())))
         }
         GraphContext::Variable(name) => Term::Variable(name.clone()),
+        GraphContext::Merged(_) => {
+            return Err("A FROM-merged default graph cannot be proven absent".into());
+        }
     };
     Ok([subj, pred, obj, graph])
 }
@@ -1117,12 +1121,17 @@ pub(crate) fn expression_references_variable(expr: &Expression, var_name: &str)
     }
 }
 
-/// True iff `term` is a `Term::Variable(var_name)`. Used by the
+/// True iff `term` is a `Term::Variable(var_name)`, or a string-valued
+/// BIND (`Term::Computed`) whose expression mentions it. Used by the
 /// prefix-3 OPTIONAL collapse soundness scope check to detect outer
 /// BIND / ORDER BY references through the IR's `Term` representation
 /// (BIND lowers to a `Binding { variable, term: Term::Variable(...) }`).
 pub(crate) fn term_references_variable(term: &Term, var_name: &str) -> bool {
-    matches!(term, Term::Variable(v) if v == var_name)
+    match term {
+        Term::Variable(v) => v == var_name,
+        Term::Computed(expr) => expression_references_variable(expr, var_name),
+        _ => false,
+    }
 }
 
 /// True iff `pattern` references the variable `var_name` anywhere in
@@ -1991,6 +2000,16 @@ fn process_graph_pattern_inner(
                 Expression::Variable(v) => Term::Variable(v.as_str().to_string()),
                 Expression::NamedNode(nn) => Term::Static(GroundTerm::NamedNode(nn.clone())),
                 Expression::Literal(l) => Term::Static(GroundTerm::Literal(l.clone())),
+                // String-valued functions, term constructors and date /
+                // time / duration expressions bind a term computed
+                // in-circuit; see `Term::Computed`. A
+                // constructor over a constant folds to the term itself.
+                e if crate::expr::is_term_constructor(e) => match crate::expr::fold_term_constructor(e)? {
+                    Some(term) => Term::Static(term),
+                    None => Term::Computed(Box::new(e.clone())),
+                },
+                e if crate::expr::is_string_valued_function(e) => Term::Computed(Box::new(e.clone())),
+                e if crate::expr::is_temporal_valued(e) => Term::Computed(Box::new(e.clone())),
                 _ => return Err("Unsupported BIND expression".into()),
             };
             info.bindings.push(Binding {
@@ -2773,7 +2792,13 @@ fn variable_is_post_join_bound(name: &str, info: &PatternInfo) -> bool {
 
 fn validate_easy_optional_var_bindings(info: &PatternInfo) -> Result<(), String> {
     for eo in &info.easy_optionals {
-        for term in &eo.inner_terms {
+        // A prefix-tree collapse's free position is inner-only by
+        // construction: neither arm reads it.
+        let free_position = eo.prefix_kind.map(|k| k.free_position());
+        for (position, term) in eo.inner_terms.iter().enumerate() {
+            if Some(position) == free_position {
+                continue;
+            }
             if let Term::Variable(name) = term {
                 if !variable_is_post_join_bound(name, info) {
                     return Err(format!(
@@ -2794,15 +2819,538 @@ fn validate_easy_optional_var_bindings(info: &PatternInfo) -> Result<(), String>
     Ok(())
 }
 
+/// Lower the body of an answer-bit query (`TransformOptions::answer_bit`)
+/// as `OPTIONAL { body }` over the empty pattern. A body whose
+/// emptiness can be witnessed collapses to a single `EasyOptional`: the
+/// public `answer` then selects its matched arm (a solution exists) or
+/// its unmatched arm (non-membership brackets show none does). Any
+/// other shape falls through to the power-set path and is rejected.
+fn lower_answer_pattern(body: &GraphPattern, options: &TransformOptions) -> Result<PatternInfo, String> {
+    let wrapped = GraphPattern::LeftJoin {
+        left: Box::new(GraphPattern::Bgp { patterns: Vec::new() }),
+        right: Box::new(body.clone()),
+        expression: None,
+    };
+    let pattern = process_graph_pattern_with_options(&wrapped, options)?;
+    let witnessable = pattern.easy_optionals.len() == 1
+        && pattern.optional_blocks.is_empty()
+        && pattern.filters.is_empty()
+        && pattern.not_exists.is_empty()
+        && pattern.prefix_not_exists.is_empty()
+        && pattern.union_branches.is_none();
+    if !witnessable {
+        return Err(
+            "TransformOptions::answer_bit needs a query body whose emptiness can be witnessed: \
+             a single triple pattern that is ground, or whose only variable is the object, \
+             optionally under GRAPH <iri>. See spec/exists.md."
+                .into(),
+        );
+    }
+    Ok(pattern)
+}
+
+/// The one triple pattern of a single-prefix query, as its terms and
+/// the variable at the free position. The body must be that pattern
+/// alone, with a constant subject and predicate, in the default graph
+/// or under `GRAPH <iri>`: its answers are then exactly the quads under
+/// one `(s, p, g)` prefix, which the prefix-run commitments keep
+/// contiguous. `shape_error` names the option asking for it.
+fn single_prefix_pattern(
+    pattern: &PatternInfo,
+    shape_error: &dyn Fn() -> String,
+) -> Result<(crate::ir::PrefixKind, [Term; 4], String), String> {
+    let single = pattern.patterns.len() == 1
+        && pattern.filters.is_empty()
+        && pattern.union_branches.is_none()
+        && pattern.optional_blocks.is_empty()
+        && pattern.not_exists.is_empty()
+        && pattern.prefix_not_exists.is_empty()
+        && pattern.easy_optionals.is_empty()
+        && matches!(
+            pattern.patterns[0].graph,
+            GraphContext::Default | GraphContext::NamedNode(_)
+        );
+    if !single {
+        return Err(shape_error());
+    }
+    let prefix_kind = crate::ir::PrefixKind::Prefix3SpG;
+    let terms = absent_terms_from_pattern(&pattern.patterns[0])?;
+    let ground = prefix_kind
+        .fixed_positions()
+        .iter()
+        .all(|&i| matches!(terms[i], Term::Static(_) | Term::DefaultGraph));
+    let variable = match &terms[prefix_kind.free_position()] {
+        Term::Variable(name) if ground && !name.starts_with("__") => name.clone(),
+        _ => return Err(shape_error()),
+    };
+    if pattern.bindings.iter().any(|b| b.variable != variable) {
+        return Err(shape_error());
+    }
+    Ok((prefix_kind, terms, variable))
+}
+
+/// Lower the body of a completeness query (`TransformOptions::completeness`)
+/// to a [`CompleteRun`]: a [`single_prefix_pattern`] whose object
+/// variable is the only projected one. The run stands in for the BGP,
+/// so `pattern` is cleared.
+fn lower_complete_run(
+    pattern: &mut PatternInfo,
+    projected: &[String],
+    capacity: usize,
+) -> Result<CompleteRun, String> {
+    let shape_error = || {
+        "TransformOptions::completeness needs a single triple pattern with a constant subject \
+         and predicate, in the default graph or under GRAPH <iri>, projecting only its object \
+         variable. See spec/prefix-tree-commitment.md Sec.9."
+            .to_string()
+    };
+    let (prefix_kind, terms, variable) = single_prefix_pattern(pattern, &shape_error)?;
+    if projected != [variable.clone()] {
+        return Err(shape_error());
+    }
+    *pattern = PatternInfo::new();
+    Ok(CompleteRun { prefix_kind, terms, variable, capacity })
+}
+
+/// Lower the body of an extremum query (`TransformOptions::extremum`)
+/// to an [`Extremum`]: a [`single_prefix_pattern`] whose object
+/// variable is the source of the query's only aggregate, a `MIN` or
+/// `MAX`. The witness row stands in for the BGP, so `pattern` is
+/// cleared.
+fn lower_extremum(
+    pattern: &mut PatternInfo,
+    projected: &[String],
+    aggregates: &[Aggregate],
+) -> Result<Extremum, String> {
+    let shape_error = || {
+        "TransformOptions::extremum needs a query projecting only MIN(?o) or MAX(?o) over a \
+         single triple pattern with a constant subject and predicate, in the default graph or \
+         under GRAPH <iri>. See spec/prefix-tree-commitment.md Sec.10."
+            .to_string()
+    };
+    let (prefix_kind, terms, variable) = single_prefix_pattern(pattern, &shape_error)?;
+    let maximum = match aggregates {
+        [Aggregate { kind: AggregateKind::Max { .. }, source: Some(source), .. }] if *source == variable => true,
+        [Aggregate { kind: AggregateKind::Min { .. }, source: Some(source), .. }] if *source == variable => false,
+        _ => return Err(shape_error()),
+    };
+    if projected != [variable.clone()] {
+        return Err(shape_error());
+    }
+    *pattern = PatternInfo::new();
+    Ok(Extremum { prefix_kind, terms, variable, maximum })
+}
+
+/// Lower an in-circuit aggregate (`TransformOptions::aggregate_rows`).
+/// The query must have one `COUNT` or `SUM` aggregate and a body the
+/// circuit can check once per row: no OPTIONAL (each variant would
+/// count only its own rows), no UNION (a row's key would miss the
+/// branch's variables) and no NOT EXISTS / MINUS (each row would need
+/// its own brackets).
+fn lower_in_circuit_aggregate(
+    pattern: &PatternInfo,
+    aggregates: &[Aggregate],
+    parameters: &[QueryParameter],
+    rows: usize,
+    threshold: bool,
+) -> Result<InCircuitAggregate, String> {
+    let aggregate = match aggregates {
+        [aggregate] => aggregate,
+        _ => {
+            return Err(
+                "TransformOptions::aggregate_rows needs a query with exactly one aggregate".into(),
+            );
+        }
+    };
+    let distinct = match aggregate.kind {
+        AggregateKind::CountDistinct | AggregateKind::Sum { distinct: true } => true,
+        AggregateKind::Count
+        | AggregateKind::CountSolutions { .. }
+        | AggregateKind::Sum { distinct: false } => false,
+        AggregateKind::Min { .. } | AggregateKind::Max { .. } => {
+            return Err(
+                "TransformOptions::aggregate_rows computes COUNT / SUM; prove MIN / MAX with \
+                 TransformOptions::extremum"
+                    .into(),
+            );
+        }
+        AggregateKind::Avg { .. } => {
+            return Err("TransformOptions::aggregate_rows computes COUNT / SUM, not AVG".into());
+        }
+    };
+    if !pattern.optional_blocks.is_empty()
+        || pattern.union_branches.is_some()
+        || pattern_has_not_exists(pattern)
+    {
+        return Err(
+            "TransformOptions::aggregate_rows checks the query body once per row, which OPTIONAL, \
+             UNION and NOT EXISTS / MINUS do not support yet"
+                .into(),
+        );
+    }
+    if let (AggregateKind::Sum { .. }, Some(source)) = (&aggregate.kind, &aggregate.source) {
+        check_sum_source(pattern, source)?;
+    }
+    let key = match (&aggregate.source, distinct) {
+        (Some(source), true) => vec![source.clone()],
+        _ => {
+            let mut key: Vec<String> = pattern
+                .bindings
+                .iter()
+                .map(|b| b.variable.clone())
+                .filter(|v| !v.starts_with("__") && !parameters.iter().any(|p| &p.name == v))
+                .collect();
+            key.sort();
+            key.dedup();
+            key
+        }
+    };
+    Ok(InCircuitAggregate {
+        kind: aggregate.kind.clone(),
+        source: aggregate.source.clone(),
+        rows,
+        threshold,
+        key,
+    })
+}
+
+/// Each in-circuit `SUM` row opens its source as an `xsd:integer`
+/// literal. Reject a source that is never one: a variable in a subject,
+/// predicate or graph position, a computed `BIND`, or a constant of
+/// another datatype. A source in object position may still be bound to
+/// a non-integer in some solution; that row cannot be witnessed.
+fn check_sum_source(pattern: &PatternInfo, source: &str) -> Result<(), String> {
+    let reject = |why: &str| {
+        Err(format!(
+            "TransformOptions::aggregate_rows sums xsd:integer values, but SUM(?{}) {}",
+            source, why
+        ))
+    };
+    let bound = pattern.bindings.iter().filter(|b| b.variable == source).map(|b| &b.term);
+    let asserted = pattern.assertions.iter().filter_map(|Assertion(left, right)| match (left, right) {
+        (Term::Variable(v), other) | (other, Term::Variable(v)) if v == source => Some(other),
+        _ => None,
+    });
+    let terms: Vec<&Term> = bound.chain(asserted).collect();
+    if terms.is_empty() {
+        return reject("is not bound by the query body");
+    }
+    for term in terms {
+        match term {
+            Term::Input(_, 2) | Term::Variable(_) => {}
+            Term::Input(_, _) => return reject("is bound in a subject, predicate or graph position"),
+            Term::Static(GroundTerm::Literal(l)) if l.datatype().as_str() == "http://www.w3.org/2001/XMLSchema#integer" => {}
+            Term::Static(ground) => return reject(&format!("is bound to the constant {}", ground)),
+            Term::Computed(_) => return reject("is bound to a computed value"),
+            Term::DefaultGraph => return reject("is bound to the default graph"),
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 pub(crate) fn process_query(gp: &GraphPattern) -> Result<QueryInfo, String> {
-    process_query_with_options_and_form(gp, &TransformOptions::default(), QueryForm::Select)
+    process_query_with_options_and_form(gp, &TransformOptions::default(), QueryForm::Select, None)
+}
+
+/// Scope a lowered pattern to the query's `FROM` / `FROM NAMED`
+/// clauses. A default-graph triple must sit in one of the `FROM`
+/// graphs -- pinned outright for a single graph, otherwise through a
+/// hidden `__from_<slot>` binding and an `IN` filter -- and each
+/// `GRAPH ?g` variable is filtered to the `FROM NAMED` set. A pattern
+/// that can never match under the clause is an error rather than an
+/// unsatisfiable circuit.
+fn apply_dataset_clause(pattern: &mut PatternInfo, dataset: &DatasetClause) -> Result<(), String> {
+    if pattern_has_not_exists(pattern) {
+        return Err(
+            "NOT EXISTS / MINUS / collapsed OPTIONAL cannot be combined with FROM / FROM NAMED \
+             yet -- absence would have to be proven in every listed graph"
+                .into(),
+        );
+    }
+    scope_triples(
+        &mut pattern.patterns,
+        0,
+        &mut pattern.bindings,
+        &mut pattern.assertions,
+        &mut pattern.filters,
+        dataset,
+    )?;
+    for block in &mut pattern.optional_blocks {
+        scope_optional_block(block, dataset)?;
+    }
+    Ok(())
+}
+
+/// [`apply_dataset_clause`] for an OPTIONAL block. Its triples occupy
+/// consecutive `bgp` slots from the lowest slot its bindings and
+/// assertions read -- every triple position yields one or the other.
+fn scope_optional_block(block: &mut OptionalBlock, dataset: &DatasetClause) -> Result<(), String> {
+    let first_slot = block
+        .bindings
+        .iter()
+        .map(|b| &b.term)
+        .chain(block.assertions.iter().flat_map(|a| [&a.0, &a.1]))
+        .filter_map(|t| match t {
+            Term::Input(i, _) => Some(*i),
+            _ => None,
+        })
+        .min()
+        .unwrap_or(0);
+    scope_triples(
+        &mut block.patterns,
+        first_slot,
+        &mut block.bindings,
+        &mut block.assertions,
+        &mut block.filters,
+        dataset,
+    )?;
+    for nested in &mut block.nested_optionals {
+        scope_optional_block(nested, dataset)?;
+    }
+    Ok(())
+}
+
+fn scope_triples(
+    patterns: &mut [ContextualizedTriple],
+    first_slot: usize,
+    bindings: &mut Vec<Binding>,
+    assertions: &mut Vec<Assertion>,
+    filters: &mut Vec<Expression>,
+    dataset: &DatasetClause,
+) -> Result<(), String> {
+    let in_filter = |name: &str, iris: &[String]| {
+        Expression::In(
+            Box::new(Expression::Variable(Variable::new_unchecked(name))),
+            iris.iter()
+                .map(|iri| Expression::NamedNode(NamedNode::new_unchecked(iri.clone())))
+                .collect(),
+        )
+    };
+    let mut graph_vars: Vec<String> = Vec::new();
+    for (k, ct) in patterns.iter_mut().enumerate() {
+        let slot = first_slot + k;
+        match &ct.graph {
+            GraphContext::Default => {
+                let from = &dataset.default_graphs;
+                match from.as_slice() {
+                    [] => {
+                        return Err(
+                            "The query has FROM NAMED but no FROM clause, so its default graph is \
+                             empty and a pattern outside GRAPH can never match"
+                                .into(),
+                        );
+                    }
+                    [iri] => assertions.push(Assertion(
+                        Term::Static(GroundTerm::NamedNode(NamedNode::new_unchecked(iri.clone()))),
+                        Term::Input(slot, 3),
+                    )),
+                    _ => {
+                        let name = format!("__from_{}", slot);
+                        bindings.push(Binding {
+                            variable: name.clone(),
+                            term: Term::Input(slot, 3),
+                        });
+                        filters.push(in_filter(&name, from));
+                    }
+                }
+                ct.graph = GraphContext::Merged(from.clone());
+            }
+            GraphContext::NamedNode(iri) => {
+                if !dataset.named_graphs.contains(iri) {
+                    return Err(format!(
+                        "GRAPH <{}> is not among the query's FROM NAMED graphs, so it can never match",
+                        iri
+                    ));
+                }
+            }
+            GraphContext::Variable(name) => {
+                if dataset.named_graphs.is_empty() {
+                    return Err(format!(
+                        "GRAPH ?{} can never match: the query's dataset clause names no FROM NAMED graphs",
+                        name
+                    ));
+                }
+                if !graph_vars.contains(name) {
+                    graph_vars.push(name.clone());
+                }
+            }
+            GraphContext::Merged(_) => {}
+        }
+    }
+    for name in graph_vars {
+        filters.push(in_filter(&name, &dataset.named_graphs));
+    }
+    Ok(())
+}
+
+/// A parameter is a public input in its own right: projecting it would
+/// disclose the verifier's own value back to it, and a second
+/// declaration would give one variable two public slots.
+fn validate_parameters(parameters: &[QueryParameter], projected: &[String]) -> Result<(), String> {
+    for (i, p) in parameters.iter().enumerate() {
+        if parameters[..i].iter().any(|q| q.name == p.name) {
+            return Err(format!("Query parameter ${} is declared more than once", p.name));
+        }
+        if projected.contains(&p.name) {
+            return Err(format!(
+                "Query parameter ${} cannot be projected -- the verifier supplies its value as a \
+                 public input; drop it from the SELECT list",
+                p.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check `TransformOptions::nullifier_secret`. The secret must stay
+/// private, and it must be bound in every circuit variant -- by a
+/// triple outside OPTIONAL / UNION -- or the prover could pick it.
+fn resolve_nullifier_secret(
+    options: &TransformOptions,
+    pattern: &PatternInfo,
+    projected: &[String],
+) -> Result<Option<String>, String> {
+    let Some(name) = &options.nullifier_secret else {
+        return Ok(None);
+    };
+    if projected.contains(name) {
+        return Err(format!(
+            "Nullifier secret ?{} cannot be projected -- disclosing it would let the verifier \
+             link nullifiers across contexts and scopes",
+            name
+        ));
+    }
+    if !pattern.bindings.iter().any(|b| &b.variable == name) {
+        return Err(format!(
+            "Nullifier secret ?{} must be bound by a triple pattern outside OPTIONAL / UNION",
+            name
+        ));
+    }
+    Ok(Some(name.clone()))
+}
+
+/// Resolve `TransformOptions::disclosure` against the projection. A
+/// mode may only name a projected variable, and a variable the
+/// verifier must compute with -- an ORDER BY key, an aggregate's
+/// output, or the source of an aggregate that reads values -- has to
+/// stay revealed. `COUNT(DISTINCT ?x)` still works over pseudonyms,
+/// which are deterministic, but not over salted commitments or
+/// ciphertexts.
+fn resolve_disclosure(
+    disclosure: &std::collections::BTreeMap<String, DisclosureMode>,
+    projected: &[String],
+    aggregates: &[Aggregate],
+    order_by: &[OrderKey],
+) -> Result<Vec<(String, DisclosureMode)>, String> {
+    if let Some(name) = disclosure.keys().find(|name| !projected.contains(name)) {
+        return Err(format!(
+            "Disclosure mode given for ?{}, which is not projected by the query",
+            name
+        ));
+    }
+    let mut resolved = Vec::new();
+    for v in projected {
+        let mode = match disclosure.get(v) {
+            Some(mode) if *mode != DisclosureMode::Reveal => *mode,
+            _ => continue,
+        };
+        let needs_value = order_by.iter().any(|k| &k.variable == v)
+            || aggregates.iter().any(|a| {
+                &a.output == v
+                    || (a.source.as_ref() == Some(v)
+                        && match a.kind {
+                            AggregateKind::Count | AggregateKind::CountSolutions { .. } => false,
+                            AggregateKind::CountDistinct => mode != DisclosureMode::Pseudonym,
+                            _ => true,
+                        })
+            });
+        if needs_value {
+            return Err(format!(
+                "?{} cannot be disclosed as a {} -- the verifier orders or aggregates over its \
+                 value; reveal it or drop it from the ORDER BY / aggregate",
+                v,
+                match mode {
+                    DisclosureMode::Commit => "commitment",
+                    DisclosureMode::Encrypt => "ciphertext",
+                    _ => "pseudonym",
+                }
+            ));
+        }
+        resolved.push((v.clone(), mode));
+    }
+    Ok(resolved)
+}
+
+/// True if any expression in the algebra calls `NOW()`. Decided over
+/// the whole query rather than per circuit so that OPTIONAL variants
+/// which drop the calling FILTER still accept the same public inputs.
+fn pattern_uses_now(gp: &GraphPattern) -> bool {
+    match gp {
+        GraphPattern::Bgp { .. } | GraphPattern::Path { .. } | GraphPattern::Values { .. } => false,
+        GraphPattern::Join { left, right }
+        | GraphPattern::Union { left, right }
+        | GraphPattern::Minus { left, right } => pattern_uses_now(left) || pattern_uses_now(right),
+        GraphPattern::LeftJoin { left, right, expression } => {
+            pattern_uses_now(left)
+                || pattern_uses_now(right)
+                || expression.as_ref().is_some_and(expression_uses_now)
+        }
+        GraphPattern::Filter { expr, inner } => expression_uses_now(expr) || pattern_uses_now(inner),
+        GraphPattern::Extend { inner, expression, .. } => {
+            expression_uses_now(expression) || pattern_uses_now(inner)
+        }
+        GraphPattern::OrderBy { inner, expression } => {
+            pattern_uses_now(inner)
+                || expression.iter().any(|o| match o {
+                    OrderExpression::Asc(e) | OrderExpression::Desc(e) => expression_uses_now(e),
+                })
+        }
+        GraphPattern::Group { inner, aggregates, .. } => {
+            pattern_uses_now(inner)
+                || aggregates.iter().any(|(_, a)| match a {
+                    AggregateExpression::FunctionCall { expr, .. } => expression_uses_now(expr),
+                    AggregateExpression::CountSolutions { .. } => false,
+                })
+        }
+        GraphPattern::Graph { inner, .. }
+        | GraphPattern::Project { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. }
+        | GraphPattern::Service { inner, .. } => pattern_uses_now(inner),
+    }
+}
+
+fn expression_uses_now(expr: &Expression) -> bool {
+    match expr {
+        Expression::FunctionCall(Function::Now, _) => true,
+        Expression::NamedNode(_) | Expression::Literal(_) | Expression::Variable(_) | Expression::Bound(_) => false,
+        Expression::Or(a, b)
+        | Expression::And(a, b)
+        | Expression::Equal(a, b)
+        | Expression::SameTerm(a, b)
+        | Expression::Greater(a, b)
+        | Expression::GreaterOrEqual(a, b)
+        | Expression::Less(a, b)
+        | Expression::LessOrEqual(a, b)
+        | Expression::Add(a, b)
+        | Expression::Subtract(a, b)
+        | Expression::Multiply(a, b)
+        | Expression::Divide(a, b) => expression_uses_now(a) || expression_uses_now(b),
+        Expression::UnaryPlus(a) | Expression::UnaryMinus(a) | Expression::Not(a) => expression_uses_now(a),
+        Expression::In(a, list) => expression_uses_now(a) || list.iter().any(expression_uses_now),
+        Expression::If(a, b, c) => expression_uses_now(a) || expression_uses_now(b) || expression_uses_now(c),
+        Expression::Coalesce(args) | Expression::FunctionCall(_, args) => args.iter().any(expression_uses_now),
+        Expression::Exists(p) => pattern_uses_now(p),
+    }
 }
 
 pub(crate) fn process_query_with_options_and_form(
     gp: &GraphPattern,
     options: &TransformOptions,
     form: QueryForm,
+    dataset: Option<&DatasetClause>,
 ) -> Result<QueryInfo, String> {
     let (inner, mut post) = strip_post_processing(gp)?;
 
@@ -2815,7 +3363,7 @@ pub(crate) fn process_query_with_options_and_form(
 
             let body = unwrap_project_inner(inner, &mut post, &mut aggregate_alias)?;
 
-            let (pattern, aggregates) = match body {
+            let (mut pattern, aggregates) = match body {
                 GraphPattern::Group {
                     inner,
                     variables: group_vars,
@@ -2828,6 +3376,9 @@ pub(crate) fn process_query_with_options_and_form(
                                 .into(),
                         );
                     }
+                    if options.answer_bit {
+                        return Err("TransformOptions::answer_bit does not support aggregates".into());
+                    }
                     let pattern = process_graph_pattern_with_options(inner, options)?;
                     let mut translated: Vec<Aggregate> = Vec::with_capacity(aggs.len());
                     for (intermediate, agg_expr) in aggs {
@@ -2841,6 +3392,7 @@ pub(crate) fn process_query_with_options_and_form(
                     }
                     (pattern, translated)
                 }
+                other if options.answer_bit => (lower_answer_pattern(other, options)?, Vec::new()),
                 other => (process_graph_pattern_with_options(other, options)?, Vec::new()),
             };
 
@@ -2852,6 +3404,9 @@ pub(crate) fn process_query_with_options_and_form(
             // `validate_easy_optional_var_bindings` for the full
             // soundness rationale (issue #57 flag 1).
             validate_easy_optional_var_bindings(&pattern)?;
+            if let Some(dataset) = dataset {
+                apply_dataset_clause(&mut pattern, dataset)?;
+            }
 
             // Per the disclose-and-verify pattern (SPARQL_ROADMAP.md
             // §8.6 Q6 decision 2026-05-03), the circuit discloses the
@@ -2962,7 +3517,9 @@ pub(crate) fn process_query_with_options_and_form(
                     continue;
                 }
                 if let Some(name) = &eo.inner_only_var {
-                    let escapes = projected.contains(name.as_str())
+                    // An answer bit pins a projected inner-only variable
+                    // in the matched arm (see `emit`).
+                    let escapes = (!options.answer_bit && projected.contains(name.as_str()))
                         || pattern_references_variable(&pattern, name.as_str(), eo.id)
                         || aggregates.iter().any(|a| {
                             a.source.as_deref() == Some(name.as_str())
@@ -2985,6 +3542,30 @@ pub(crate) fn process_query_with_options_and_form(
                 }
             }
 
+            let complete_run = match options.completeness {
+                Some(capacity) => Some(lower_complete_run(&mut pattern, &circuit_vars, capacity)?),
+                None => None,
+            };
+            let extremum = if options.extremum {
+                Some(lower_extremum(&mut pattern, &circuit_vars, &aggregates)?)
+            } else {
+                None
+            };
+            let in_circuit_aggregate = match options.aggregate_rows {
+                Some(rows) => Some(lower_in_circuit_aggregate(
+                    &pattern,
+                    &aggregates,
+                    &options.parameters,
+                    rows,
+                    options.aggregate_threshold,
+                )?),
+                None => None,
+            };
+
+            validate_parameters(&options.parameters, &circuit_vars)?;
+            let disclosure =
+                resolve_disclosure(&options.disclosure, &circuit_vars, &aggregates, &post.order_by)?;
+            let nullifier_secret = resolve_nullifier_secret(options, &pattern, &circuit_vars)?;
             Ok(QueryInfo {
                 variables: circuit_vars,
                 pattern,
@@ -2992,6 +3573,16 @@ pub(crate) fn process_query_with_options_and_form(
                 order_by: post.order_by,
                 limit: post.limit,
                 offset: post.offset,
+                uses_now: pattern_uses_now(gp),
+                parameters: options.parameters.clone(),
+                disclosure,
+                dataset: dataset.cloned(),
+                binds_context: options.bind_context || nullifier_secret.is_some(),
+                nullifier_secret,
+                answer_bit: options.answer_bit,
+                complete_run,
+                extremum,
+                in_circuit_aggregate,
             })
         }
         // ASK queries return a boolean — they do NOT project bound
@@ -3006,30 +3597,50 @@ pub(crate) fn process_query_with_options_and_form(
         // regress those at the same time. The form is threaded from
         // `parse::query_form` so we can distinguish.
         _ => {
-            let pattern = process_graph_pattern_with_options(inner, options)?;
+            if options.completeness.is_some() {
+                return Err("TransformOptions::completeness needs a SELECT query".into());
+            }
+            if options.extremum {
+                return Err("TransformOptions::extremum needs a SELECT of MIN or MAX".into());
+            }
+            if options.aggregate_rows.is_some() {
+                return Err("TransformOptions::aggregate_rows needs a SELECT of COUNT or SUM".into());
+            }
+            let mut pattern = if options.answer_bit {
+                lower_answer_pattern(inner, options)?
+            } else {
+                process_graph_pattern_with_options(inner, options)?
+            };
             // Same easy-OPTIONAL deferred-binding check as the Project
             // arm above (issue #57 flag 1). Applies to ASK as well as
             // the auto-project fallback because both can emit
             // `easy_optionals` from a `GRAPH ?g { OPTIONAL { ground } }`
             // body that fails to bind `?g` via a sibling pattern.
             validate_easy_optional_var_bindings(&pattern)?;
+            if let Some(dataset) = dataset {
+                apply_dataset_clause(&mut pattern, dataset)?;
+            }
             let vars: Vec<String> = match form {
                 QueryForm::Ask => Vec::new(),
                 _ => {
                     // Filter out `__`-prefix names: blank-node internals
                     // and `__exists_*` inner-only EXISTS witnesses must
-                    // never appear in the disclosed projection.
+                    // never appear in the disclosed projection. Query
+                    // parameters are public inputs already.
                     let mut all: Vec<String> = pattern
                         .bindings
                         .iter()
                         .map(|b| b.variable.clone())
-                        .filter(|v| !v.starts_with("__"))
+                        .filter(|v| !v.starts_with("__") && !options.parameters.iter().any(|p| &p.name == v))
                         .collect();
                     all.sort();
                     all.dedup();
                     all
                 }
             };
+            validate_parameters(&options.parameters, &vars)?;
+            let disclosure = resolve_disclosure(&options.disclosure, &vars, &[], &post.order_by)?;
+            let nullifier_secret = resolve_nullifier_secret(options, &pattern, &vars)?;
             Ok(QueryInfo {
                 variables: vars,
                 pattern,
@@ -3037,6 +3648,16 @@ pub(crate) fn process_query_with_options_and_form(
                 order_by: post.order_by,
                 limit: post.limit,
                 offset: post.offset,
+                uses_now: pattern_uses_now(gp),
+                parameters: options.parameters.clone(),
+                disclosure,
+                dataset: dataset.cloned(),
+                binds_context: options.bind_context || nullifier_secret.is_some(),
+                nullifier_secret,
+                answer_bit: options.answer_bit,
+                complete_run: None,
+                extremum: None,
+                in_circuit_aggregate: None,
             })
         }
     }
@@ -4,11 +4,14 @@
 //! spargebra terms) into the JSON shape consumed by the TypeScript side.
 //! Pure presentation: no algebra-level decisions live here.
 
+use std::collections::BTreeMap;
+
 use spargebra::term::{GroundTerm, NamedNodePattern, TermPattern};
 
+use crate::expr::DECIMAL_SCALE;
 use crate::{
-    Aggregate, ContextualizedTriple, GraphContext, OptionalBlock, OrderDirection, OrderKey,
-    QueryInfo, Term,
+    Aggregate, AggregateKind, ContextualizedTriple, DisclosureMode, GraphContext, OptionalBlock, OrderDirection, OrderKey,
+    QueryInfo, StringHash, Term, TransformOptions,
 };
 
 /// Serialise a `Term` (the lowering-time representation, which can be a
@@ -45,10 +48,21 @@ pub(crate) fn term_to_json(term: &Term) -> serde_json::Value {
             "kind": "static",
             "term": { "termType": "DefaultGraph" },
         }),
+        // BIND-computed term. The witness itself is described in the
+        // `computedWitnesses` array; here we only surface the source
+        // expression for diagnostics.
+        Term::Computed(expr) => serde_json::json!({
+            "kind": "computed",
+            "expression": expr.to_string(),
+        }),
     }
 }
 
-fn aggregate_to_json(agg: &Aggregate) -> serde_json::Value {
+/// `evaluation` says who computes the aggregate: the verifier over the
+/// disclosed multiset, or the circuit over private rows
+/// (`TransformOptions::aggregate_rows`), whose value is only a lower
+/// bound (`bound: "lowerBound"`).
+fn aggregate_to_json(agg: &Aggregate, in_circuit: bool) -> serde_json::Value {
     let mut obj = serde_json::Map::new();
     obj.insert("kind".into(), serde_json::Value::String(agg.kind.metadata_tag().into()));
     obj.insert("output".into(), serde_json::Value::String(agg.output.clone()));
@@ -57,6 +71,11 @@ fn aggregate_to_json(agg: &Aggregate) -> serde_json::Value {
     } else {
         obj.insert("source".into(), serde_json::Value::Null);
     }
+    let evaluation = if in_circuit { "in-circuit" } else { "verifier" };
+    obj.insert("evaluation".into(), serde_json::Value::String(evaluation.into()));
+    if in_circuit {
+        obj.insert("bound".into(), serde_json::Value::String("lowerBound".into()));
+    }
     serde_json::Value::Object(obj)
 }
 
@@ -134,6 +153,7 @@ pub(crate) fn contextualized_pattern_to_json(ct: &ContextualizedTriple) -> serde
         GraphContext::Default => serde_json::json!({"termType": "DefaultGraph"}),
         GraphContext::NamedNode(iri) => serde_json::json!({"termType": "NamedNode", "value": iri}),
         GraphContext::Variable(name) => serde_json::json!({"termType": "Variable", "value": name}),
+        GraphContext::Merged(iris) => serde_json::json!({"termType": "DefaultGraph", "from": iris}),
     };
     serde_json::json!({
         "subject": term_pattern_to_json(&ct.pattern.subject),
@@ -147,11 +167,376 @@ pub(crate) fn contextualized_pattern_to_json(ct: &ContextualizedTriple) -> serde
 /// historically read both camelCase and snake_case spellings of every
 /// field, so each key is emitted twice; do not normalise without
 /// auditing the JS consumers first.
+/// Add the verifier-supplied public inputs to a circuit's metadata.
+///
+/// `now` is null unless the query calls `NOW()`. Otherwise the verifier
+/// supplies the public `now` input -- the current instant in epoch
+/// milliseconds, UTC -- and rejects a proof whose `now` is more than
+/// `toleranceMs` away from its own clock.
+///
+/// `disclosure` gives each projected variable's mode. A `commit` entry
+/// names its slot in the private `salts` input; a `pseudonym` entry is
+/// keyed by the public `verifier_context` input; an `encrypt` entry
+/// names its key-stream `slot`. When any variable is not revealed, the
+/// public values are the `disclosed` struct instead of `variables`.
+///
+/// `encryption` is null unless some variable is encrypted. Otherwise it
+/// describes the scheme: the verifier recomputes `shared = sk *
+/// ephemeral_key` on Grumpkin and reads each encrypted term hash as
+/// `disclosed.<var> - hash2([shared.x, slot])`.
+///
+/// `context` is null unless the proof is bound to the public `context`
+/// input, which the verifier sets to its nonce or domain. `nullifier`
+/// is null unless the circuit also derives the public `nullifier` from
+/// the `secret` variable's term, `context` and the public `scope`; the
+/// verifier rejects a nullifier it has seen before.
+///
+/// `parameters` lists each query parameter's slot in the public
+/// `params` array, which holds its term hash. `parameterValues` lists
+/// the further slots pinned to hidden inputs read off a parameter; each
+/// entry is the hidden-input description, evaluated by the verifier
+/// over the parameter's value, plus its `position`. `answer` is null
+/// unless the circuit proves the query's answer bit; the projected
+/// variables only mean something when that bit is true.
+pub(crate) fn attach_public_inputs(
+    metadata: &mut serde_json::Value,
+    info: &QueryInfo,
+    parameter_values: &[serde_json::Value],
+    now_tolerance_ms: u64,
+) {
+    metadata["now"] = if info.uses_now {
+        serde_json::json!({
+            "input": "now",
+            "unit": "epoch_milliseconds",
+            "timezone": "Z",
+            "toleranceMs": now_tolerance_ms,
+            "tolerance_ms": now_tolerance_ms,
+        })
+    } else {
+        serde_json::Value::Null
+    };
+    let parameters: Vec<serde_json::Value> = info
+        .parameters
+        .iter()
+        .enumerate()
+        .map(|(position, p)| {
+            serde_json::json!({
+                "name": p.name,
+                "position": position,
+                "termType": if p.datatype.is_some() { "Literal" } else { "NamedNode" },
+                "datatype": p.datatype,
+            })
+        })
+        .collect();
+    metadata["parameters"] = parameters.into();
+    metadata["parameterValues"] = parameter_values.into();
+    metadata["parameter_values"] = parameter_values.into();
+
+    let mut salt = 0;
+    let mut slot = 0;
+    let disclosure: Vec<serde_json::Value> = info
+        .variables
+        .iter()
+        .map(|v| {
+            let mode = info
+                .disclosure
+                .iter()
+                .find(|(d, _)| d == v)
+                .map_or(DisclosureMode::Reveal, |(_, mode)| *mode);
+            let mut entry = serde_json::json!({ "variable": v, "mode": mode.metadata_tag() });
+            match mode {
+                DisclosureMode::Commit => {
+                    entry["salt"] = salt.into();
+                    salt += 1;
+                }
+                DisclosureMode::Pseudonym => entry["context"] = "verifier_context".into(),
+                DisclosureMode::Encrypt => {
+                    entry["slot"] = slot.into();
+                    slot += 1;
+                }
+                DisclosureMode::Reveal => {}
+            }
+            entry
+        })
+        .collect();
+    metadata["disclosure"] = disclosure.into();
+    metadata["context"] = if info.binds_context {
+        serde_json::json!({ "input": "context" })
+    } else {
+        serde_json::Value::Null
+    };
+    metadata["nullifier"] = match &info.nullifier_secret {
+        Some(secret) => serde_json::json!({
+            "input": "nullifier",
+            "scopeInput": "scope",
+            "scope_input": "scope",
+            "secret": secret,
+            "derivation": "hash2([hash2([secret, context]), scope])",
+        }),
+        None => serde_json::Value::Null,
+    };
+    metadata["answer"] = if info.answer_bit {
+        let witness = match info.pattern.easy_optionals.first().and_then(|eo| eo.prefix_kind) {
+            Some(_) => "prefix3",
+            None => "leaf",
+        };
+        serde_json::json!({ "input": "answer", "falseWitness": witness, "false_witness": witness })
+    } else {
+        serde_json::Value::Null
+    };
+    metadata["encryption"] = if slot > 0 {
+        serde_json::json!({
+            "scheme": "ecdh-hash2-stream",
+            "curve": "grumpkin",
+            "publicKeyInput": "verifier_key",
+            "public_key_input": "verifier_key",
+            "ephemeralKeyInput": "ephemeral_key",
+            "ephemeral_key_input": "ephemeral_key",
+            "keyStream": "hash2([shared.x, slot])",
+            "key_stream": "hash2([shared.x, slot])",
+            "plaintext": "term_hash",
+        })
+    } else {
+        serde_json::Value::Null
+    };
+}
+
+/// Record which issuer each `bgp` slot is checked against.
+/// `datasetCount` is the length of the public `public_key` array (one
+/// key and root per dataset), and `bgpDatasets[i]` is the dataset --
+/// so the `public_key` / `roots` index -- of slot `i`. Non-membership
+/// and prefix-3 roots always belong to dataset 0.
+///
+/// `datasetClause` lists the graphs a query's `FROM` / `FROM NAMED`
+/// clauses allow, or is null when it has neither.
+///
+/// `issuerRegistry` is null unless the keys are checked against a
+/// registry of authorised issuers. Otherwise it names the public root
+/// input and the tree depth, and gives the leaf encoding of a key.
+///
+/// `rootValidity` is null unless roots carry a signed validity window.
+/// Otherwise it names the public time input and the signed message,
+/// and `revocation` (null when off) names the revocation-list root.
+///
+/// `completeness` is null unless the circuit proves its rows are every
+/// answer. Otherwise it gives the disclosed variable and row capacity,
+/// the `roots` index of the prefix-run commitment, the run inputs and
+/// the queried pattern, whose fixed positions key the run.
+///
+/// `extremum` is null unless the circuit proves its one row holds the
+/// `MIN` / `MAX` over every match. Otherwise it gives the disclosed
+/// variable, whether it is a maximum, the `roots` index of the
+/// value-sorted commitment, the witness inputs and the queried pattern.
+///
+/// `inCircuitAggregate` is null unless the circuit computes its COUNT /
+/// SUM over private rows. Otherwise it gives the aggregate, the row
+/// bound, the public input, the key variables that order the rows and
+/// the per-row slot counts of `bgp`, `hiddenInputs` and
+/// `computedWitnesses`.
+pub(crate) fn attach_datasets(
+    metadata: &mut serde_json::Value,
+    info: &QueryInfo,
+    options: &TransformOptions,
+    bgp_datasets: &[usize],
+) {
+    let datasets = options.datasets;
+    metadata["datasetCount"] = datasets.into();
+    metadata["dataset_count"] = datasets.into();
+    metadata["bgpDatasets"] = bgp_datasets.into();
+    metadata["bgp_datasets"] = bgp_datasets.into();
+    let clause = match &info.dataset {
+        Some(d) => serde_json::json!({
+            "from": d.default_graphs,
+            "fromNamed": d.named_graphs,
+            "from_named": d.named_graphs,
+        }),
+        None => serde_json::Value::Null,
+    };
+    metadata["datasetClause"] = clause.clone();
+    metadata["dataset_clause"] = clause;
+    let registry = match options.issuer_registry_depth {
+        Some(depth) => serde_json::json!({
+            "input": "issuer_registry",
+            "depth": depth,
+            "leaf": "fold hash2 over signature::public_key_fields(key), from PUBLIC_KEY_FIELDS",
+        }),
+        None => serde_json::Value::Null,
+    };
+    metadata["issuerRegistry"] = registry.clone();
+    metadata["issuer_registry"] = registry;
+    let validity = if options.root_validity {
+        let revocation = if options.revocation_check {
+            serde_json::json!({
+                "input": "revocation_root",
+                "witnessInput": "revocations",
+                "witness_input": "revocations",
+                "leaf": "hash4([value, issued_at, expires_at, 0]) in a sorted merkle() tree",
+            })
+        } else {
+            serde_json::Value::Null
+        };
+        serde_json::json!({
+            "timeInput": "now",
+            "time_input": "now",
+            "unit": "epoch-ms",
+            "window": "issued_at <= now < expires_at",
+            "message": "hash4([value, issued_at, expires_at, 0])",
+            "revocation": revocation,
+        })
+    } else {
+        serde_json::Value::Null
+    };
+    metadata["rootValidity"] = validity.clone();
+    metadata["root_validity"] = validity;
+    let completeness = match &info.complete_run {
+        Some(run) => {
+            let terms: Vec<serde_json::Value> = run.terms.iter().map(term_to_json).collect();
+            let fixed_positions = run.prefix_kind.fixed_positions();
+            serde_json::json!({
+                "variable": run.variable,
+                "capacity": run.capacity,
+                "root": datasets,
+                "leaf": "hash2([hash3_sp_g(s, p, g), hash4([s, p, o, g])]), sorted by hash3_sp_g then leaf",
+                "witnessInput": "run",
+                "witness_input": "run",
+                "lengthInput": "run_len",
+                "length_input": "run_len",
+                "boundaryInput": "boundary_cases_run",
+                "boundary_input": "boundary_cases_run",
+                "boundaryCaseDispatch": {
+                    "left": { "0": "lower", "1": "middle" },
+                    "right": { "1": "middle", "2": "upper" },
+                },
+                "prefixKind": run.prefix_kind.metadata_tag(),
+                "prefix_kind": run.prefix_kind.metadata_tag(),
+                "terms": terms,
+                "freePosition": run.prefix_kind.free_position(),
+                "free_position": run.prefix_kind.free_position(),
+                "fixedPositions": fixed_positions,
+                "fixed_positions": fixed_positions,
+            })
+        }
+        None => serde_json::Value::Null,
+    };
+    metadata["completeness"] = completeness;
+    let extremum = match &info.extremum {
+        Some(ext) => {
+            let terms: Vec<serde_json::Value> = ext.terms.iter().map(term_to_json).collect();
+            let fixed_positions = ext.prefix_kind.fixed_positions();
+            let dispatch = if ext.maximum {
+                serde_json::json!({ "1": "middle", "2": "upper" })
+            } else {
+                serde_json::json!({ "0": "lower", "1": "middle" })
+            };
+            serde_json::json!({
+                "variable": ext.variable,
+                "kind": if ext.maximum { "max" } else { "min" },
+                "root": datasets,
+                "leaf": "hash2([hash3_sp_g(s, p, g), hash4([s, p, o, g])]), sorted by hash3_sp_g, then the object's special encoding as i64, then leaf",
+                "witnessInput": "extremum",
+                "witness_input": "extremum",
+                "boundaryInput": "boundary_case_extremum",
+                "boundary_input": "boundary_case_extremum",
+                "boundaryCaseDispatch": dispatch,
+                "prefixKind": ext.prefix_kind.metadata_tag(),
+                "prefix_kind": ext.prefix_kind.metadata_tag(),
+                "terms": terms,
+                "freePosition": ext.prefix_kind.free_position(),
+                "free_position": ext.prefix_kind.free_position(),
+                "fixedPositions": fixed_positions,
+                "fixed_positions": fixed_positions,
+            })
+        }
+        None => serde_json::Value::Null,
+    };
+    metadata["extremum"] = extremum;
+    let aggregate = match &info.in_circuit_aggregate {
+        Some(agg) => {
+            let input = if agg.threshold { "threshold" } else { "aggregate" };
+            // The prover picks the rows, so the public value only ever
+            // bounds the true aggregate from below.
+            let claim = match agg.kind {
+                AggregateKind::Sum { .. } => format!(
+                    "lower bound: the query's SUM is at least `{}` (xsd:integer values only)",
+                    input
+                ),
+                _ => format!("lower bound: the query has at least `{}` distinct solutions", input),
+            };
+            let hidden = metadata["hiddenInputs"].as_array().map_or(0, Vec::len);
+            let computed = metadata["computedWitnesses"].as_array().map_or(0, Vec::len);
+            serde_json::json!({
+                "kind": agg.kind.metadata_tag(),
+                "source": agg.source,
+                "rows": agg.rows,
+                "input": input,
+                "comparison": if agg.threshold { ">=" } else { "==" },
+                "bound": "lowerBound",
+                "claim": claim,
+                "keyVariables": agg.key,
+                "key_variables": agg.key,
+                "key": "hash2 folded over the key variables' term hashes; rows sorted strictly ascending",
+                "bgpPerRow": info.pattern.patterns.len(),
+                "bgp_per_row": info.pattern.patterns.len(),
+                "hiddenPerRow": hidden,
+                "hidden_per_row": hidden,
+                "computedPerRow": computed,
+                "computed_per_row": computed,
+            })
+        }
+        None => serde_json::Value::Null,
+    };
+    metadata["inCircuitAggregate"] = aggregate.clone();
+    metadata["in_circuit_aggregate"] = aggregate;
+}
+
+/// Record the circuit fingerprint and, for a normalised query, the
+/// `variableMap` from canonical variable names (as they appear in
+/// `variables` and the hidden-input descriptions) back to the names in
+/// the source query. Every variant of a query carries the same
+/// fingerprint.
+pub(crate) fn attach_fingerprint(
+    metadata: &mut serde_json::Value,
+    fingerprint: &str,
+    variable_map: Option<&BTreeMap<String, String>>,
+) {
+    metadata["fingerprint"] = fingerprint.into();
+    let variable_map = variable_map.map_or(serde_json::Value::Null, |m| serde_json::json!(m));
+    metadata["variableMap"] = variable_map.clone();
+    metadata["variable_map"] = variable_map;
+}
+
+/// Record the string hash constant encodings were folded under, or
+/// `null` when the circuit computes them itself. A verifier must reject
+/// the circuit unless its `consts` package uses the same hash.
+///
+/// `config` records the hashes, signature scheme and sizes the circuit
+/// was generated for, with `constsDigest` the digest of the matching
+/// `consts` package (`TransformResult::consts`).
+pub(crate) fn attach_encoding(metadata: &mut serde_json::Value, options: &TransformOptions, consts_digest: &str) {
+    let hash = options.static_string_hash.map(StringHash::name);
+    metadata["staticStringHash"] = hash.into();
+    metadata["static_string_hash"] = hash.into();
+    metadata["config"] = serde_json::json!({
+        "fieldHash": options.field_hash.name(),
+        "field_hash": options.field_hash.name(),
+        "stringHash": options.string_hash.name(),
+        "string_hash": options.string_hash.name(),
+        "signature": options.signature.name(),
+        "merkleDepth": options.merkle_depth,
+        "merkle_depth": options.merkle_depth,
+        "stringLenMax": options.string_len_max,
+        "string_len_max": options.string_len_max,
+        "constsDigest": consts_digest,
+        "consts_digest": consts_digest,
+    });
+}
+
 pub(crate) fn build_base_metadata(
     info: &QueryInfo,
     all_optionals: &[OptionalBlock],
     skip_signing: bool,
     base_hidden: &[serde_json::Value],
+    base_computed: &[serde_json::Value],
     string_len_max: usize,
 ) -> serde_json::Value {
     let total_patterns: usize = info.pattern.patterns.len()
@@ -194,7 +579,10 @@ pub(crate) fn build_base_metadata(
         .unwrap_or_default();
 
     let aggregates_json: Vec<serde_json::Value> =
-        info.aggregates.iter().map(aggregate_to_json).collect();
+        info.aggregates
+            .iter()
+            .map(|agg| aggregate_to_json(agg, info.in_circuit_aggregate.is_some()))
+            .collect();
     let order_by_json: Vec<serde_json::Value> =
         info.order_by.iter().map(order_key_to_json).collect();
 
@@ -337,6 +725,12 @@ pub(crate) fn build_base_metadata(
         "optional_patterns": optional_patterns_json,
         "union_branches": union_branches_json,
         "hidden_inputs": base_hidden,
+        // Fresh output witnesses for string-valued functions (REPLACE /
+        // ENCODE_FOR_URI), one per entry of the private `computed`
+        // array. The prover evaluates each function over the referenced
+        // input and supplies the resulting bytes, length and hash.
+        "computedWitnesses": base_computed,
+        "computed_witnesses": base_computed,
         "num_optionals": all_optionals.len(),
         "total_patterns": total_patterns,
         "aggregates": aggregates_json,
@@ -357,6 +751,11 @@ pub(crate) fn build_base_metadata(
         // rest of this document follows.
         "stringLenMax": string_len_max,
         "string_len_max": string_len_max,
+        // Fractional digits of the fixed-point xsd:decimal values the
+        // circuit computes with; `decimal_value` hidden inputs are the
+        // value times 10^decimalScale.
+        "decimalScale": DECIMAL_SCALE,
+        "decimal_scale": DECIMAL_SCALE,
     })
 }
 
@@ -367,6 +766,7 @@ pub(crate) fn build_variant_metadata(
     matched_indices: &[usize],
     skip_signing: bool,
     circuit_hidden: &[serde_json::Value],
+    circuit_computed: &[serde_json::Value],
     string_len_max: usize,
 ) -> serde_json::Value {
     let mut optional_only_vars: std::collections::HashSet<String> =
@@ -411,7 +811,10 @@ pub(crate) fn build_variant_metadata(
     }
 
     let aggregates_json: Vec<serde_json::Value> =
-        info.aggregates.iter().map(aggregate_to_json).collect();
+        info.aggregates
+            .iter()
+            .map(|agg| aggregate_to_json(agg, info.in_circuit_aggregate.is_some()))
+            .collect();
     let order_by_json: Vec<serde_json::Value> =
         info.order_by.iter().map(order_key_to_json).collect();
 
@@ -421,6 +824,8 @@ pub(crate) fn build_variant_metadata(
         "inputPatterns": combo_patterns,
         "matchedOptionals": matched_indices,
         "hiddenInputs": circuit_hidden,
+        "computedWitnesses": circuit_computed,
+        "computed_witnesses": circuit_computed,
         "aggregates": aggregates_json,
         "orderBy": order_by_json,
         "order_by": order_by_json,
@@ -428,5 +833,7 @@ pub(crate) fn build_variant_metadata(
         "offset": info.offset,
         "stringLenMax": string_len_max,
         "string_len_max": string_len_max,
+        "decimalScale": DECIMAL_SCALE,
+        "decimal_scale": DECIMAL_SCALE,
     })
 }
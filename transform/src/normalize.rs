@@ -0,0 +1,414 @@
+//! Canonical query normalisation and the circuit fingerprint.
+//!
+//! `normalize_pattern` rewrites the algebra root before lowering so that
+//! alpha-equivalent spellings of a query lower to the same IR:
+//!
+//! - variables are renamed `v0`, `v1`, … in traversal order, projected
+//!   variables first (query parameters keep their names -- the caller
+//!   addresses them by name in `TransformOptions::parameters`), and
+//!   blank nodes are relabelled `b0`, `b1`, …;
+//! - the triples of each BGP are stably sorted on their shape, with
+//!   not-yet-named variables abstracted, so reordering a BGP does not
+//!   move its `bgp[i]` slots;
+//! - constant XSD casts and boolean connectives over constant booleans
+//!   are folded to literals.
+//!
+//! The ordering is deterministic but not a full graph canonicalisation:
+//! two triples of identical shape over fresh variables keep their
+//! source order.
+//!
+//! `fingerprint` is a SHA-256 over the lowered `QueryInfo`, the
+//! circuit-affecting `TransformOptions` and the crate version --
+//! everything the emitted circuit depends on -- so a cache can dedupe compilations and a
+//! verifier can pin a circuit without diffing Noir source.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use spargebra::algebra::{AggregateExpression, Expression, Function, GraphPattern, OrderExpression};
+use spargebra::term::{BlankNode, Literal, NamedNodePattern, TermPattern, TriplePattern, Variable};
+
+use crate::encode::sha256;
+use crate::expr::fold_xsd_cast;
+use crate::{QueryInfo, QueryParameter, TransformOptions};
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// A normalised algebra root plus the renaming that produced it.
+pub(crate) struct NormalizedPattern {
+    pub(crate) pattern: GraphPattern,
+    /// Canonical variable name → name in the source query.
+    pub(crate) variable_map: BTreeMap<String, String>,
+}
+
+pub(crate) fn normalize_pattern(gp: &GraphPattern, parameters: &[QueryParameter]) -> NormalizedPattern {
+    let mut normalizer = Normalizer {
+        reserved: parameters.iter().map(|p| p.name.clone()).collect(),
+        variables: BTreeMap::new(),
+        blank_nodes: BTreeMap::new(),
+        next_variable: 0,
+    };
+    let pattern = normalizer.pattern(gp);
+    let variable_map = normalizer
+        .variables
+        .into_iter()
+        .map(|(original, canonical)| (canonical, original))
+        .collect();
+    NormalizedPattern { pattern, variable_map }
+}
+
+struct Normalizer {
+    /// Parameter names, kept verbatim and never handed out as fresh names.
+    reserved: BTreeSet<String>,
+    /// Source variable name → canonical name.
+    variables: BTreeMap<String, String>,
+    /// Source blank-node label → canonical label.
+    blank_nodes: BTreeMap<String, String>,
+    next_variable: usize,
+}
+
+impl Normalizer {
+    fn variable(&mut self, v: &Variable) -> Variable {
+        if self.reserved.contains(v.as_str()) {
+            return v.clone();
+        }
+        if let Some(canonical) = self.variables.get(v.as_str()) {
+            return Variable::new_unchecked(canonical.clone());
+        }
+        let canonical = loop {
+            let candidate = format!("v{}", self.next_variable);
+            self.next_variable += 1;
+            if !self.reserved.contains(&candidate) {
+                break candidate;
+            }
+        };
+        self.variables.insert(v.as_str().to_string(), canonical.clone());
+        Variable::new_unchecked(canonical)
+    }
+
+    fn blank_node(&mut self, bn: &BlankNode) -> BlankNode {
+        let next = self.blank_nodes.len();
+        let label = self
+            .blank_nodes
+            .entry(bn.as_str().to_string())
+            .or_insert_with(|| format!("b{}", next));
+        BlankNode::new_unchecked(label.clone())
+    }
+
+    fn term_pattern(&mut self, tp: &TermPattern) -> TermPattern {
+        match tp {
+            TermPattern::Variable(v) => TermPattern::Variable(self.variable(v)),
+            TermPattern::BlankNode(bn) => TermPattern::BlankNode(self.blank_node(bn)),
+            other => other.clone(),
+        }
+    }
+
+    fn named_node_pattern(&mut self, nnp: &NamedNodePattern) -> NamedNodePattern {
+        match nnp {
+            NamedNodePattern::Variable(v) => NamedNodePattern::Variable(self.variable(v)),
+            other => other.clone(),
+        }
+    }
+
+    /// Sort key for a term position: constants by their N-Triples form,
+    /// already-named variables / blank nodes by their canonical name,
+    /// and fresh ones abstracted to their sigil.
+    fn term_key(&self, tp: &TermPattern) -> String {
+        match tp {
+            TermPattern::Variable(v) => self.variable_key(v),
+            TermPattern::BlankNode(bn) => match self.blank_nodes.get(bn.as_str()) {
+                Some(label) => format!("_:{}", label),
+                None => "_:".to_string(),
+            },
+            other => other.to_string(),
+        }
+    }
+
+    fn variable_key(&self, v: &Variable) -> String {
+        if self.reserved.contains(v.as_str()) {
+            return format!("${}", v.as_str());
+        }
+        match self.variables.get(v.as_str()) {
+            Some(canonical) => format!("?{}", canonical),
+            None => "?".to_string(),
+        }
+    }
+
+    fn triple_key(&self, t: &TriplePattern) -> (String, String, String) {
+        let predicate = match &t.predicate {
+            NamedNodePattern::NamedNode(nn) => nn.to_string(),
+            NamedNodePattern::Variable(v) => self.variable_key(v),
+        };
+        (predicate, self.term_key(&t.subject), self.term_key(&t.object))
+    }
+
+    fn pattern(&mut self, gp: &GraphPattern) -> GraphPattern {
+        match gp {
+            GraphPattern::Bgp { patterns } => {
+                let mut sorted: Vec<&TriplePattern> = patterns.iter().collect();
+                sorted.sort_by_cached_key(|t| self.triple_key(t));
+                let patterns = sorted
+                    .into_iter()
+                    .map(|t| TriplePattern {
+                        subject: self.term_pattern(&t.subject),
+                        predicate: self.named_node_pattern(&t.predicate),
+                        object: self.term_pattern(&t.object),
+                    })
+                    .collect();
+                GraphPattern::Bgp { patterns }
+            }
+            GraphPattern::Path { subject, path, object } => GraphPattern::Path {
+                subject: self.term_pattern(subject),
+                path: path.clone(),
+                object: self.term_pattern(object),
+            },
+            GraphPattern::Join { left, right } => GraphPattern::Join {
+                left: Box::new(self.pattern(left)),
+                right: Box::new(self.pattern(right)),
+            },
+            GraphPattern::LeftJoin { left, right, expression } => GraphPattern::LeftJoin {
+                left: Box::new(self.pattern(left)),
+                right: Box::new(self.pattern(right)),
+                expression: expression.as_ref().map(|e| self.expression(e)),
+            },
+            GraphPattern::Filter { expr, inner } => {
+                let inner = Box::new(self.pattern(inner));
+                GraphPattern::Filter { expr: self.expression(expr), inner }
+            }
+            GraphPattern::Union { left, right } => GraphPattern::Union {
+                left: Box::new(self.pattern(left)),
+                right: Box::new(self.pattern(right)),
+            },
+            GraphPattern::Graph { name, inner } => {
+                let name = self.named_node_pattern(name);
+                GraphPattern::Graph { name, inner: Box::new(self.pattern(inner)) }
+            }
+            GraphPattern::Extend { inner, variable, expression } => {
+                let inner = Box::new(self.pattern(inner));
+                let expression = self.expression(expression);
+                GraphPattern::Extend { inner, variable: self.variable(variable), expression }
+            }
+            GraphPattern::Minus { left, right } => GraphPattern::Minus {
+                left: Box::new(self.pattern(left)),
+                right: Box::new(self.pattern(right)),
+            },
+            GraphPattern::Values { variables, bindings } => GraphPattern::Values {
+                variables: variables.iter().map(|v| self.variable(v)).collect(),
+                bindings: bindings.clone(),
+            },
+            GraphPattern::OrderBy { inner, expression } => {
+                let inner = Box::new(self.pattern(inner));
+                let expression = expression
+                    .iter()
+                    .map(|o| match o {
+                        OrderExpression::Asc(e) => OrderExpression::Asc(self.expression(e)),
+                        OrderExpression::Desc(e) => OrderExpression::Desc(self.expression(e)),
+                    })
+                    .collect();
+                GraphPattern::OrderBy { inner, expression }
+            }
+            // Projected variables are named before the body so the
+            // disclosed `variables.*` fields come out as `v0`, `v1`, …
+            // in projection order.
+            GraphPattern::Project { inner, variables } => {
+                let variables = variables.iter().map(|v| self.variable(v)).collect();
+                GraphPattern::Project { inner: Box::new(self.pattern(inner)), variables }
+            }
+            GraphPattern::Distinct { inner } => GraphPattern::Distinct { inner: Box::new(self.pattern(inner)) },
+            GraphPattern::Reduced { inner } => GraphPattern::Reduced { inner: Box::new(self.pattern(inner)) },
+            GraphPattern::Slice { inner, start, length } => GraphPattern::Slice {
+                inner: Box::new(self.pattern(inner)),
+                start: *start,
+                length: *length,
+            },
+            GraphPattern::Group { inner, variables, aggregates } => {
+                let inner = Box::new(self.pattern(inner));
+                let variables = variables.iter().map(|v| self.variable(v)).collect();
+                let aggregates = aggregates
+                    .iter()
+                    .map(|(v, aggregate)| {
+                        let aggregate = match aggregate {
+                            AggregateExpression::CountSolutions { distinct } => {
+                                AggregateExpression::CountSolutions { distinct: *distinct }
+                            }
+                            AggregateExpression::FunctionCall { name, expr, distinct } => {
+                                AggregateExpression::FunctionCall {
+                                    name: name.clone(),
+                                    expr: self.expression(expr),
+                                    distinct: *distinct,
+                                }
+                            }
+                        };
+                        (self.variable(v), aggregate)
+                    })
+                    .collect();
+                GraphPattern::Group { inner, variables, aggregates }
+            }
+            GraphPattern::Service { name, inner, silent } => {
+                let name = self.named_node_pattern(name);
+                GraphPattern::Service { name, inner: Box::new(self.pattern(inner)), silent: *silent }
+            }
+        }
+    }
+
+    fn operands(&mut self, a: &Expression, b: &Expression) -> (Box<Expression>, Box<Expression>) {
+        (Box::new(self.expression(a)), Box::new(self.expression(b)))
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Expression {
+        match expr {
+            Expression::Variable(v) => Expression::Variable(self.variable(v)),
+            Expression::Bound(v) => Expression::Bound(self.variable(v)),
+            Expression::Or(a, b) => {
+                let (a, b) = self.operands(a, b);
+                match (boolean_constant(&a), boolean_constant(&b)) {
+                    (Some(x), Some(y)) => boolean_literal(x || y),
+                    _ => Expression::Or(a, b),
+                }
+            }
+            Expression::And(a, b) => {
+                let (a, b) = self.operands(a, b);
+                match (boolean_constant(&a), boolean_constant(&b)) {
+                    (Some(x), Some(y)) => boolean_literal(x && y),
+                    _ => Expression::And(a, b),
+                }
+            }
+            Expression::Not(a) => {
+                let a = self.expression(a);
+                match boolean_constant(&a) {
+                    Some(x) => boolean_literal(!x),
+                    None => Expression::Not(Box::new(a)),
+                }
+            }
+            Expression::Equal(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::Equal(a, b)
+            }
+            Expression::SameTerm(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::SameTerm(a, b)
+            }
+            Expression::Greater(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::Greater(a, b)
+            }
+            Expression::GreaterOrEqual(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::GreaterOrEqual(a, b)
+            }
+            Expression::Less(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::Less(a, b)
+            }
+            Expression::LessOrEqual(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::LessOrEqual(a, b)
+            }
+            Expression::Add(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::Add(a, b)
+            }
+            Expression::Subtract(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::Subtract(a, b)
+            }
+            Expression::Multiply(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::Multiply(a, b)
+            }
+            Expression::Divide(a, b) => {
+                let (a, b) = self.operands(a, b);
+                Expression::Divide(a, b)
+            }
+            Expression::UnaryPlus(a) => Expression::UnaryPlus(Box::new(self.expression(a))),
+            Expression::UnaryMinus(a) => Expression::UnaryMinus(Box::new(self.expression(a))),
+            Expression::In(a, args) => {
+                let a = Box::new(self.expression(a));
+                Expression::In(a, args.iter().map(|e| self.expression(e)).collect())
+            }
+            Expression::Exists(p) => Expression::Exists(Box::new(self.pattern(p))),
+            Expression::If(a, b, c) => {
+                let a = Box::new(self.expression(a));
+                let (b, c) = self.operands(b, c);
+                Expression::If(a, b, c)
+            }
+            Expression::Coalesce(args) => Expression::Coalesce(args.iter().map(|e| self.expression(e)).collect()),
+            Expression::FunctionCall(function, args) => {
+                let args: Vec<Expression> = args.iter().map(|e| self.expression(e)).collect();
+                // A cast that is a SPARQL error is left in place so the
+                // lowering still reports (or folds) it as before.
+                if let (Function::Custom(iri), [arg]) = (function, args.as_slice())
+                    && let Some(target) = iri.as_str().strip_prefix(XSD)
+                    && let Ok(Some(literal)) = fold_xsd_cast(target, arg)
+                {
+                    return Expression::Literal(literal);
+                }
+                Expression::FunctionCall(function.clone(), args)
+            }
+            Expression::NamedNode(_) | Expression::Literal(_) => expr.clone(),
+        }
+    }
+}
+
+fn boolean_constant(expr: &Expression) -> Option<bool> {
+    match expr {
+        Expression::Literal(l) if l.datatype().as_str() == format!("{}boolean", XSD) => match l.value() {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn boolean_literal(value: bool) -> Expression {
+    Expression::Literal(Literal::from(value))
+}
+
+/// Content fingerprint of a lowered query under the given options,
+/// formatted `sha256:<hex>`. The `Debug` form of the IR is exhaustive,
+/// and the crate version pins its rendering and the lowering that
+/// produced it; the options enter through [`circuit_options`].
+pub(crate) fn fingerprint(info: &QueryInfo, options: &TransformOptions) -> String {
+    let input = format!("{}\n{:?}\n{}", env!("CARGO_PKG_VERSION"), info, circuit_options(options));
+    let digest = sha256(input.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256:{}", hex)
+}
+
+/// Canonical JSON of the options the emitted circuit depends on, with
+/// keys sorted. Left out: `optional_cap` only bounds which queries are
+/// accepted, `now_tolerance_ms` is the verifier's clock policy, and
+/// `normalize` already shows in the lowered IR.
+fn circuit_options(options: &TransformOptions) -> String {
+    let parameters: Vec<_> = options
+        .parameters
+        .iter()
+        .map(|p| serde_json::json!({ "name": p.name, "datatype": p.datatype }))
+        .collect();
+    let disclosure: BTreeMap<_, _> = options.disclosure.iter().map(|(v, mode)| (v, mode.metadata_tag())).collect();
+    serde_json::json!({
+        "skipSigning": options.skip_signing,
+        "pathSegmentMax": options.path_segment_max,
+        "stringLenMax": options.string_len_max,
+        "parameters": parameters,
+        "staticStringHash": options.static_string_hash.map(|h| h.name()),
+        "disclosure": disclosure,
+        "datasets": options.datasets,
+        "datasetGraphs": options.dataset_graphs,
+        "issuerRegistryDepth": options.issuer_registry_depth,
+        "bindContext": options.bind_context,
+        "nullifierSecret": options.nullifier_secret,
+        "rootValidity": options.root_validity,
+        "revocationCheck": options.revocation_check,
+        "fieldHash": options.field_hash.name(),
+        "stringHash": options.string_hash.name(),
+        "signature": options.signature.name(),
+        "merkleDepth": options.merkle_depth,
+        "answerBit": options.answer_bit,
+        "completeness": options.completeness,
+        "extremum": options.extremum,
+        "aggregateRows": options.aggregate_rows,
+        "aggregateThreshold": options.aggregate_threshold,
+    })
+    .to_string()
+}
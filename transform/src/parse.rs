@@ -7,6 +7,8 @@
 use spargebra::algebra::GraphPattern;
 use spargebra::{Query, SparqlParser};
 
+use crate::DatasetClause;
+
 /// Which SPARQL query form produced the algebra root. Used by the
 /// lowering layer to drive ASK-vs-SELECT differences in the disclosed
 /// projection (audit item 8, sparql_noir #37 row).
@@ -33,6 +35,26 @@ pub(crate) fn root_pattern(query: &Query) -> &GraphPattern {
     }
 }
 
+/// The query's `FROM` / `FROM NAMED` clauses. `None` when it has
+/// neither, leaving the dataset to the prover.
+pub(crate) fn query_dataset(query: &Query) -> Option<DatasetClause> {
+    let dataset = match query {
+        Query::Select { dataset, .. }
+        | Query::Construct { dataset, .. }
+        | Query::Describe { dataset, .. }
+        | Query::Ask { dataset, .. } => dataset.as_ref()?,
+    };
+    Some(DatasetClause {
+        default_graphs: dataset.default.iter().map(|g| g.as_str().to_string()).collect(),
+        named_graphs: dataset
+            .named
+            .iter()
+            .flatten()
+            .map(|g| g.as_str().to_string())
+            .collect(),
+    })
+}
+
 pub(crate) fn query_form(query: &Query) -> QueryForm {
     match query {
         Query::Select { .. } => QueryForm::Select,
@@ -10,11 +10,15 @@
 //! Run `UPDATE_SNAPSHOTS=1 cargo test --test snapshot` to regenerate the
 //! fixtures after an intentional behavioural change.
 
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
 
-use transform::{transform_query, transform_with_opts, TransformOptions};
+use transform::{
+    transform_query, transform_with_opts, DisclosureMode, FieldHash, QueryParameter, SignatureScheme, StringHash,
+    TransformOptions,
+};
 
 struct Case {
     name: &'static str,
@@ -2222,6 +2226,1555 @@ fn contains_empty_needle_keeps_operand_binding() {
     );
 }
 
+/// REPLACE with a literal pattern is witnessed as a computed string:
+/// the prover supplies the output bytes in `computed[]` and the circuit
+/// checks them against the input with `utils::string_replace`. The
+/// result compares against the query literal by term hash.
+#[test]
+fn replace_emits_computed_witness() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:label ?o . FILTER(REPLACE(?o, \"-\", \"_\") = \"a_b\") }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("pub(crate) type Computed = [TermWitness; 1];"),
+        "REPLACE must declare one computed witness:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("utils::string_replace::<1, 1>(bgp[0].terms[2], computed[0]"),
+        "REPLACE must check the output against the input bytes:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.main_nr.contains("checkBinding(bgp, variables, computed)"),
+        "main.nr must thread `computed` into checkBinding:\n{}",
+        r.main_nr
+    );
+    let computed = r
+        .metadata
+        .get("computedWitnesses")
+        .and_then(|v| v.as_array())
+        .expect("computedWitnesses metadata array");
+    assert_eq!(computed.len(), 1);
+    assert_eq!(computed[0]["computedType"], "replace");
+    assert_eq!(computed[0]["pattern"], "-");
+    assert_eq!(computed[0]["replacement"], "_");
+}
+
+/// `BIND(ENCODE_FOR_URI(...) AS ?v)` materialises the computed witness
+/// once as a `bind_v` local; projecting `?v` discloses its term hash.
+#[test]
+fn encode_for_uri_bind_is_projected() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s ?slug WHERE { ?s ex:label ?o . BIND(ENCODE_FOR_URI(?o) AS ?slug) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("let bind_slug = {"),
+        "BIND must materialise a `bind_slug` local:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("utils::encode_for_uri(bgp[0].terms[2], computed[0])"),
+        "ENCODE_FOR_URI must check the output against the input bytes:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("assert(variables.slug == bind_slug.hash);"),
+        "projected ?slug must bind to the computed term hash:\n{}",
+        r.sparql_nr
+    );
+}
+
+/// REPLACE only compiles literal patterns and replacements. Regex
+/// metacharacters (escaped or not), `$` / `\` in the replacement and
+/// flags other than `q` are rejected at transform time; `q` makes both
+/// literal.
+#[test]
+fn replace_rejects_non_literal_pattern() {
+    let query = |pattern: &str, replacement: &str, flags: Option<&str>| {
+        let flags = flags.map(|f| format!(", \"{}\"", f)).unwrap_or_default();
+        format!(
+            "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE {{ ?s ex:label ?o . FILTER(REPLACE(?o, \"{}\", \"{}\"{}) = \"x\") }}",
+            pattern, replacement, flags
+        )
+    };
+    for (pattern, replacement, flags, expected) in [
+        ("a.", "b", None, "metacharacter '.'"),
+        ("a\\\\.", "b", None, "metacharacter '\\'"),
+        ("a", "b", Some("i"), "flag 'i'"),
+        ("a", "b", Some("x"), "flag 'x'"),
+        ("a", "$1", None, "uses '$'"),
+        ("a", "\\\\$", None, "uses '\\'"),
+    ] {
+        match transform_query(&query(pattern, replacement, flags)) {
+            Ok(r) => panic!("expected REPLACE to be rejected, got:\n{}", r.sparql_nr),
+            Err(err) => assert!(err.contains(expected), "rejection should name {}, got: {}", expected, err),
+        }
+    }
+    // The `q` flag matches metacharacters and substitutes `$` literally.
+    let r = transform_query(&query("a.", "$1", Some("q"))).expect("q-flagged REPLACE compiles");
+    assert_eq!(r.metadata["computedWitnesses"][0]["pattern"], "a.");
+    assert_eq!(r.metadata["computedWitnesses"][0]["replacement"], "$1");
+    assert!(r.sparql_nr.contains("utils::string_replace::<2, 2>("), "{}", r.sparql_nr);
+}
+
+/// `SHA256(?e) = "<hex>"` recomputes the digest from `?e`'s bound bytes
+/// and compares it by term hash -- no prover-supplied witness, so the
+/// circuit takes no `computed` input.
+#[test]
+fn sha256_equality_recomputes_digest_in_circuit() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:email ?e . \
+             FILTER(SHA256(?e) = \"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\") }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("utils::hex_string_term_hash(utils::sha256_hex(bgp[0].terms[2]))"),
+        "SHA256 must hash the bound operand in-circuit:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("utils::bind_term_bytes_plain_string_literal(bgp[0].terms[2]"),
+        "SHA256 must bind the operand's bytes first:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        !r.sparql_nr.contains("Computed"),
+        "hash functions need no computed witness:\n{}",
+        r.sparql_nr
+    );
+}
+
+/// A hash result read as a string (BIND, nested string operator) is
+/// wrapped in an in-circuit `hex_string_witness`.
+#[test]
+fn hash_function_feeds_string_operators() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s ?h WHERE { ?s ex:email ?e . BIND(MD5(?e) AS ?h) FILTER(STRSTARTS(SHA1(?e), \"ab\")) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("let bind_h = {")
+            && r.sparql_nr.contains("utils::hex_string_witness(utils::md5_hex(bgp[0].terms[2]))"),
+        "BIND(MD5(...)) must materialise a digest witness:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("utils::string_starts_with::<2>(utils::hex_string_witness(utils::sha1_hex(bgp[0].terms[2]))"),
+        "STRSTARTS over SHA1 must read the digest witness:\n{}",
+        r.sparql_nr
+    );
+}
+
+/// `BIND(IRI(?id) AS ?p)` recomputes the IRI's term hash from `?id`'s
+/// bound bytes, and the result joins with a later triple pattern.
+#[test]
+fn iri_constructor_bind_joins_with_bgp() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s ?p WHERE { ?s ex:id ?id . BIND(IRI(?id) AS ?p) ?p ex:name ?n }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("utils::construct_iri(bgp[0].terms[2])"),
+        "IRI() must construct from the bound operand:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("assert(variables.p == bind_p.hash);")
+            && r.sparql_nr.contains("assert(variables.p == bgp[1].terms[0].hash);"),
+        "the constructed IRI must join with the later pattern:\n{}",
+        r.sparql_nr
+    );
+}
+
+/// STRDT picks the constructor matching the datatype's value encoding;
+/// STRLANG lower-cases its constant tag; constant arguments fold.
+#[test]
+fn strdt_strlang_constructors_lower_by_term_hash() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?s WHERE { ?s ex:code ?c ; ex:n ?n . \
+             FILTER(STRDT(?c, xsd:integer) = ?n && sameTerm(STRLANG(?c, \"EN\"), STRLANG(\"x\", \"en\"))) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("utils::construct_strdt_integer(bgp[0].terms[2], consts::encode_string(\"http://www.w3.org/2001/XMLSchema#integer\")).hash } == bgp[1].terms[2].hash"),
+        "STRDT to xsd:integer must use the integer constructor:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("utils::construct_strlang(bgp[0].terms[2], consts::encode_string(\"en\"))"),
+        "STRLANG must lower-case its tag:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("consts::hash2([2, consts::hash4([consts::encode_string(\"x\"), consts::encode_string(\"x\"), consts::encode_string(\"en\")"),
+        "STRLANG over constants must fold:\n{}",
+        r.sparql_nr
+    );
+}
+
+/// Constructors with no deterministic result, or whose result is not a
+/// plain string, are rejected.
+#[test]
+fn term_constructor_rejections() {
+    for (q, needle) in [
+        (
+            "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:id ?id . FILTER(BNODE() = ?s) }",
+            "BNODE()",
+        ),
+        (
+            "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:id ?id . BIND(IRI(?id) AS ?p) FILTER(STRLEN(?p) > 2) }",
+            "not a plain string",
+        ),
+        (
+            "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:id ?id ; ex:dt ?dt . FILTER(STRDT(?id, ?dt) = ?s) }",
+            "constant datatype IRI",
+        ),
+    ] {
+        match transform_query(q) {
+            Ok(r) => panic!("expected rejection, got:\n{}", r.sparql_nr),
+            Err(err) => assert!(err.contains(needle), "expected {:?} in: {}", needle, err),
+        }
+    }
+}
+
+/// IN compares each member with type-aware equality: IRIs and strings
+/// by term hash, numerics by value. A NOT IN member that reads an
+/// unbound variable is an error, so NOT IN can never be true.
+#[test]
+fn in_mixes_term_identity_and_value_equality() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(?o IN (ex:a, \"x\"@EN, 1)) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains(
+            "ebv::EBVResult::ok(bgp[0].terms[2].hash == consts::hash2([0, consts::encode_string(\"http://example.org/a\")])).or("
+        ),
+        "IRI members must match by term hash, and never error:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("consts::encode_string(\"en\"), consts::encode_string(\"http://www.w3.org/1999/02/22-rdf-syntax-ns#langString\")"),
+        "language-tagged members must match by term hash:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("t.literal & !t.is_numeric() }) { ebv::EBVResult::type_error() } else { ebv::EBVResult::ok(hidden[6] == hidden[7]) }"),
+        "numeric members must go through value equality, erroring on other literals:\n{}",
+        r.sparql_nr
+    );
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(?o NOT IN (ex:a, ?unbound)) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains(".or(ebv::EBVResult::type_error()).not().unwrap_or(false)"),
+        "{}",
+        r.sparql_nr
+    );
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(?o NOT IN ()) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("assert(true);"), "{}", r.sparql_nr);
+}
+
+/// IF lowers to a Noir `if` in both boolean and value position;
+/// COALESCE skips arguments that read an unbound variable.
+#[test]
+fn if_and_coalesce_lower_natively() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o ; ex:q ?w . \
+             FILTER(IF(BOUND(?w), ?w = ex:a, ?o > 2) && IF(?o > 2, ?w, 0) = 4) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("({ let cond = ebv::EBVResult::ok(true); if cond.success & cond.value { "),
+        "boolean IF must branch on its condition:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("(if ((hidden[10] as i64) > (hidden[11] as i64)) { hidden[12] } else { hidden[13] })"),
+        "value IF must become a Noir if-expression:\n{}",
+        r.sparql_nr
+    );
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(COALESCE(?unbound, ?o) = ex:a) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("hidden[0] == hidden[1]")
+            && !r.metadata.to_string().contains("unbound"),
+        "COALESCE must skip the unbound argument:\n{}",
+        r.sparql_nr
+    );
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(COALESCE(?unbound, ?alsoUnbound)) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("assert(false);"), "{}", r.sparql_nr);
+}
+
+/// `||` and `!` apply the SPARQL truth tables: an operand that can
+/// error is guarded by its error condition, derived from the opened
+/// term type of each variable it compares. A root conjunction cannot
+/// tell an error from false, so it stays boolean.
+#[test]
+fn filters_use_three_valued_logic_where_errors_matter() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(?o > 5 || !(?o < 2)) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains(
+            "assert((if ({ let t = ebv::open_term_type(bgp[0].terms[2].hash, hidden[2], hidden[3], \
+             [hidden[4], hidden[5], hidden[6], hidden[7]]); !t.is_numeric() }) { ebv::EBVResult::type_error() } \
+             else { ebv::EBVResult::ok((hidden[0] as i64) > (hidden[1] as i64)) }).or((if ({ let t = \
+             ebv::open_term_type(bgp[0].terms[2].hash, hidden[10], hidden[11], [hidden[12], hidden[13], hidden[14], \
+             hidden[15]]); !t.is_numeric() }) { ebv::EBVResult::type_error() } else { ebv::EBVResult::ok((hidden[8] \
+             as i64) < (hidden[9] as i64)) }).not()).unwrap_or(false));"
+        ),
+        "`||` / `!` must combine three-valued results:\n{}",
+        r.sparql_nr
+    );
+    assert!(r.nargo_toml.contains("ebv = "), "{}", r.nargo_toml);
+    let hidden = r.metadata["hiddenInputs"].as_array().expect("hiddenInputs");
+    assert_eq!(hidden[2]["computedType"], "literal_component");
+    assert_eq!(hidden[2]["component"], "kind");
+    assert!(!r.metadata.to_string().contains("expr_error"), "{}", r.metadata);
+
+    // Two variables: `=` errs on distinct literals of incomparable types.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o ; ex:q ?w . FILTER(!(?o = ?w)) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("(if (ebv::equality_error(ebv::open_term_type("), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("bgp[0].terms[2].hash == bgp[1].terms[2].hash)"), "{}", r.sparql_nr);
+
+    // A leaf whose error the circuit cannot derive is rejected where
+    // the error would be observed, and accepted under a root `&&`.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(!(STRLEN(?o) > 3)) }";
+    let err = match transform_query(q) {
+        Err(e) => e,
+        Ok(r) => panic!("STRLEN's type error cannot be derived:\n{}", r.sparql_nr),
+    };
+    assert!(err.contains("STRLEN") && err.contains("type error"), "{}", err);
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(STRLEN(?o) > 3 && !(?o < 2)) }";
+    assert!(transform_query(q).is_ok());
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(?o > 5 && ?o < 9) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(!r.sparql_nr.contains("ebv::"), "{}", r.sparql_nr);
+
+    // `!` over an unbound variable is still an error, not true.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(!(?unbound > 5)) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("assert(false);"), "{}", r.sparql_nr);
+}
+
+/// LANGMATCHES is RFC 4647 basic filtering over the bound language-tag
+/// witness: the full range (lower-cased) is compared on subtag
+/// boundaries, not just its primary subtag.
+#[test]
+fn langmatches_filters_on_the_bound_tag_witness() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(LANGMATCHES(LANG(?o), \"en-GB\") && LANG(?o) = \"FR\") }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains(
+            "lang::bind_language_tag(bgp[0].terms[2].hash, computed[0].bytes, computed[0].length, hidden[0], hidden[1], hidden[2]);"
+        ),
+        "the tag witness must be bound to the literal's term hash:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("let range: [u8; 5] = [0x65, 0x6e, 0x2d, 0x67, 0x62]; lang::basic_filter("),
+        "the whole range must be matched, lower-cased:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("let expected: [u8; 2] = [0x66, 0x72]; lang::tag_equals("),
+        "LANG equality must be case-insensitive:\n{}",
+        r.sparql_nr
+    );
+    assert!(r.nargo_toml.contains("lang = "), "{}", r.nargo_toml);
+    let computed = r.metadata["computedWitnesses"].as_array().expect("computedWitnesses");
+    assert_eq!(computed[0]["computedType"], "language_tag");
+
+    // Constant tags fold at transform time.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(LANGMATCHES(LANG(\"x\"@en-GB), \"EN\") && !LANGMATCHES(\"eng\", \"en\")) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("assert((true) & (!(false)));"), "{}", r.sparql_nr);
+}
+
+/// The language-tag helpers do not need `utils`, so LANGMATCHES keeps
+/// working in skip-signing mode, which now takes `computed` too.
+#[test]
+fn langmatches_runs_in_skip_signing_mode() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(LANGMATCHES(LANG(?o), \"*\")) }";
+    let opts = TransformOptions {
+        skip_signing: true,
+        ..TransformOptions::default()
+    };
+    let r = transform_with_opts(q, opts).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("use super::TermWitness;"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("lang::matches_wildcard(computed[0].length)"), "{}", r.sparql_nr);
+    assert!(r.main_nr.contains("checkBinding(bgp, variables, hidden, computed)"), "{}", r.main_nr);
+}
+
+/// SPARQL 1.1 §17.5: a number computed in-circuit casts to a fresh
+/// string witness checked against its canonical lexical form, and a
+/// string computed in-circuit casts back by parsing its bound bytes.
+#[test]
+fn xsd_casts_check_lexical_forms_against_the_byte_witness() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(xsd:string(?o + 1) = \"42\") }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains(
+            "utils::bind_computed_plain_string_literal(computed[0]); \
+             assert(xpath::is_canonical_integer_lexical(computed[0].bytes, computed[0].length, "
+        ),
+        "numeric -> string must bind a canonical-form witness:\n{}",
+        r.sparql_nr
+    );
+    let computed = r.metadata["computedWitnesses"].as_array().expect("computedWitnesses");
+    assert_eq!(computed[0]["computedType"], "cast_string");
+    assert_eq!(computed[0]["sourceType"], "integer");
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . \
+             FILTER(xsd:byte(REPLACE(?o, \"x\", \"\")) > 3 && xsd:double(REPLACE(?o, \"y\", \"\")) > 3) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("xpath::parse_integer_lexical(computed[0].bytes, computed[0].length) as Field }) as i64; assert(v >= -128); assert(v <= 127);"),
+        "string -> xsd:byte must parse the witness and range-check it:\n{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("assert(xpath::lexical_rounds_to_double(computed[1].bytes, computed[1].length, v as u64));"),
+        "string -> xsd:double must check the prover's value against the bytes:\n{}",
+        r.sparql_nr
+    );
+    let hidden = r.metadata["hiddenInputs"].as_array().expect("hiddenInputs");
+    assert!(hidden.iter().any(|h| h["computedType"] == "cast_value" && h["targetType"] == "double"));
+}
+
+/// Casts of constants fold at transform time to their canonical form;
+/// a constant cast that fails is a SPARQL error, so the FILTER is false.
+#[test]
+fn constant_xsd_casts_fold_to_canonical_literals() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . \
+             FILTER(?o = xsd:string(1.50) && ?o = xsd:string(\"1e7\"^^xsd:double) && ?o = xsd:date(\"2020-01-02T10:00:00Z\"^^xsd:dateTime)) }";
+    let r = transform_query(q).expect("transform succeeds");
+    let hidden = serde_json::to_string(&r.metadata["hiddenInputs"]).unwrap();
+    for folded in ["\"value\":\"1.5\"", "\"value\":\"1.0E7\""] {
+        assert!(hidden.contains(folded), "expected {} among the folded constants: {}", folded, hidden);
+    }
+    // Dates compare by value: the cast folds to midnight 2020-01-02 UTC.
+    assert!(r.sparql_nr.contains("1577923200000"), "{}", r.sparql_nr);
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?s WHERE { ?s ex:p ?o . FILTER(xsd:integer(\"abc\") = ?o || xsd:byte(300) = ?o) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("assert(false);"), "{}", r.sparql_nr);
+}
+
+/// xsd:decimal is fixed-point: literals fold to their scaled value,
+/// variables in a decimal context are `decimal_value` hidden inputs at
+/// the recorded scale, and integer division yields a decimal.
+#[test]
+fn decimal_arithmetic_is_fixed_point() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:price ?p ; ex:qty ?q . FILTER(?p * 1.08 <= 21.60 && ?q / 4 > 0.5) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("xpath::numeric_multiply_decimal((hidden[0] as i64), (((1080000 as Field)) as i64))"),
+        "decimal product must use the fixed-point helper:\n{}",
+        r.sparql_nr
+    );
+    assert!(r.sparql_nr.contains("<= (((21600000 as Field)) as i64)"), "{}", r.sparql_nr);
+    assert!(
+        r.sparql_nr.contains("xpath::numeric_divide_decimal((hidden[1] as i64), xpath::decimal_from_integer((hidden[2]) as i64))"),
+        "integer division must yield a decimal:\n{}",
+        r.sparql_nr
+    );
+    assert_eq!(r.metadata["decimalScale"], 6);
+    let hidden = r.metadata["hiddenInputs"].as_array().expect("hiddenInputs");
+    assert_eq!(hidden[0]["computedType"], "decimal_value");
+    assert_eq!(hidden[0]["scale"], 6);
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:price ?p . FILTER(?p > 0.0000001) }";
+    let err = match transform_query(q) {
+        Err(e) => e,
+        Ok(_) => panic!("seven fractional digits are not representable"),
+    };
+    assert!(err.contains("not representable"), "{}", err);
+}
+
+/// Dates, times and durations are compared and combined by value on a
+/// UTC timeline: "issued less than 90 days before X" is `?d > X - P90D`
+/// in epoch milliseconds, and differently-zoned equal instants are equal.
+#[test]
+fn temporal_values_compare_on_the_timeline() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?s WHERE { ?s ex:issued ?d . \
+             FILTER(?d > \"2024-06-01T00:00:00Z\"^^xsd:dateTime - \"P90D\"^^xsd:dayTimeDuration) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("(hidden[0] as i64) > ((1717200000000 as i64) + (0 - (7776000000 as i64)))"),
+        "{}",
+        r.sparql_nr
+    );
+    let hidden = r.metadata["hiddenInputs"].as_array().expect("hiddenInputs");
+    assert_eq!(hidden[0]["computedType"], "temporal_value");
+    assert_eq!(hidden[0]["datatype"], "http://www.w3.org/2001/XMLSchema#dateTime");
+
+    let q = "PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?s WHERE { ?s ?p ?o . \
+             FILTER(\"2020-01-01T00:00:00+01:00\"^^xsd:dateTime = \"2019-12-31T23:00:00Z\"^^xsd:dateTime) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("(1577833200000 as i64) == (1577833200000 as i64)"), "{}", r.sparql_nr);
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?s ?t ?z WHERE { ?s ex:issued ?d . \
+             BIND(?d + \"PT1H\"^^xsd:dayTimeDuration AS ?t) BIND(TZ(?d) AS ?z) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("xpath::is_canonical_datetime_lexical("), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("utils::construct_datetime("), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("xpath::is_timezone_lexical("), "{}", r.sparql_nr);
+
+    // gYear has equality but no ordering: the comparison is a type error.
+    let q = "PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?s WHERE { ?s ?p ?o . FILTER(\"2020\"^^xsd:gYear < \"2021\"^^xsd:gYear) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("assert(false);"), "{}", r.sparql_nr);
+}
+
+/// Years whose epoch milliseconds overflow `i64` are rejected with an
+/// error instead of wrapping or panicking.
+#[test]
+fn temporal_literals_out_of_i64_range_are_rejected() {
+    for filter in [
+        "?o = \"9999999999-01-01\"^^xsd:date",
+        "YEAR(\"99999999999-01-01T00:00:00Z\"^^xsd:dateTime) < 1",
+        "?o < \"-99999999999-01-01T00:00:00+14:00\"^^xsd:dateTime",
+    ] {
+        let q = format!(
+            "PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\nSELECT ?s WHERE {{ ?s ?p ?o . FILTER({}) }}",
+            filter
+        );
+        let err = match transform_query(&q) {
+            Err(e) => e,
+            Ok(r) => panic!("{} transformed:\n{}", filter, r.sparql_nr),
+        };
+        assert!(err.contains("is not a valid xsd:"), "{}", err);
+    }
+}
+
+/// `NOW()` is the verifier's clock, not the prover's: a public `now`
+/// input threaded from `main` into `checkBinding`, with the accepted
+/// skew in metadata. Queries without `NOW()` keep the old ABI.
+#[test]
+fn now_is_a_verifier_supplied_public_input() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:validUntil ?v . FILTER(?v > NOW()) }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("hidden: Hidden, now: i64)"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("assert((hidden[0] as i64) > now);"), "{}", r.sparql_nr);
+    assert!(r.main_nr.contains("now: pub i64"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("checkBinding(bgp, variables, hidden, now)"), "{}", r.main_nr);
+    assert_eq!(r.metadata["now"]["unit"], "epoch_milliseconds");
+    assert_eq!(r.metadata["now"]["toleranceMs"], 300_000);
+
+    // Every OPTIONAL variant shares the base `main.nr`, so it takes
+    // `now` even when its own body does not read it.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s ?n WHERE { ?s ex:validUntil ?v . \
+             OPTIONAL { ?s ex:name ?n . FILTER(?v > NOW()) } }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(!r.optional_circuits.is_empty());
+    for c in &r.optional_circuits {
+        assert!(c.sparql_nr.contains("now: i64)"), "{}", c.sparql_nr);
+    }
+
+    let r = transform_query("SELECT ?s WHERE { ?s ?p ?o }").expect("transform succeeds");
+    assert!(!r.main_nr.contains("now"), "{}", r.main_nr);
+    assert!(r.metadata["now"].is_null());
+}
+
+/// Query parameters are public inputs: the same circuit serves every
+/// value. A parameter's term hash pins its triple positions, and each
+/// value read off it is pinned to a further public slot.
+#[test]
+fn query_parameters_are_public_inputs() {
+    let opts = TransformOptions {
+        parameters: vec![
+            QueryParameter {
+                name: "min_age".into(),
+                datatype: Some("http://www.w3.org/2001/XMLSchema#integer".into()),
+            },
+            QueryParameter { name: "issuer".into(), datatype: None },
+        ],
+        ..TransformOptions::default()
+    };
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:age ?age ; ex:issuer $issuer . FILTER(?age > $min_age) }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(!r.sparql_nr.contains("min_age"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("pub(crate) type Params = [Field; 3];"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("assert(params[1] == bgp[1].terms[2].hash);"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("assert(hidden[1] == params[2]);"), "{}", r.sparql_nr);
+    assert!(r.main_nr.contains("params: pub Params"), "{}", r.main_nr);
+    assert_eq!(r.metadata["parameters"][0]["name"], "min_age");
+    assert_eq!(r.metadata["parameters"][1]["termType"], "NamedNode");
+    assert_eq!(r.metadata["parameterValues"][0]["parameter"], "min_age");
+    assert_eq!(r.metadata["parameterValues"][0]["position"], 2);
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s $issuer WHERE { ?s ex:issuer $issuer . FILTER(?s != $min_age) }";
+    let err = match transform_with_opts(q, opts) {
+        Err(e) => e,
+        Ok(_) => panic!("a projected parameter must be rejected"),
+    };
+    assert!(err.contains("cannot be projected"), "{}", err);
+}
+
+#[test]
+fn normalised_alpha_equivalent_queries_share_a_fingerprint() {
+    let opts = TransformOptions { normalize: true, ..TransformOptions::default() };
+    let a = "PREFIX ex: <http://example.org/>\n\
+             PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n\
+             SELECT ?name WHERE { ?person ex:name ?name ; ex:age ?age . \
+             FILTER(?age > xsd:integer(\"18\")) }";
+    let b = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?n WHERE { ?x ex:age ?years . ?x ex:name ?n . FILTER(?years > 18) }";
+    let ra = transform_with_opts(a, opts.clone()).expect("transform succeeds");
+    let rb = transform_with_opts(b, opts.clone()).expect("transform succeeds");
+    assert_eq!(ra.sparql_nr, rb.sparql_nr);
+    assert_eq!(ra.fingerprint, rb.fingerprint);
+    assert!(ra.fingerprint.starts_with("sha256:") && ra.fingerprint.len() == 71, "{}", ra.fingerprint);
+    assert_eq!(ra.metadata["fingerprint"], ra.fingerprint.as_str());
+    assert_eq!(ra.metadata["variableMap"]["v0"], "name");
+    assert_eq!(rb.metadata["variableMap"]["v0"], "n");
+    assert!(ra.sparql_nr.contains("variables.v0"), "{}", ra.sparql_nr);
+
+    // Without normalisation the spellings differ, and so do the
+    // fingerprints; the options are part of the fingerprint too.
+    let plain_a = transform_query(a).expect("transform succeeds");
+    let plain_b = transform_query(b).expect("transform succeeds");
+    assert_ne!(plain_a.fingerprint, plain_b.fingerprint);
+    assert_ne!(plain_b.fingerprint, rb.fingerprint);
+    assert!(plain_a.metadata["variableMap"].is_null());
+    assert_eq!(plain_b.fingerprint, transform_query(b).expect("transform succeeds").fingerprint);
+
+    // Options that leave the circuit alone leave the fingerprint alone.
+    let policy = TransformOptions { now_tolerance_ms: 1, optional_cap: 1, ..TransformOptions::default() };
+    assert_eq!(plain_b.fingerprint, transform_with_opts(b, policy).expect("transform succeeds").fingerprint);
+    let deeper = TransformOptions { merkle_depth: 13, ..TransformOptions::default() };
+    assert_ne!(plain_b.fingerprint, transform_with_opts(b, deeper).expect("transform succeeds").fingerprint);
+}
+
+/// `Field::from_le_bytes(hash(s))` for a few strings, computed
+/// independently of the transform (Python `hashlib` / OpenSSL, reduced
+/// modulo the BN254 scalar field) -- the encodings `encode.ts` gets
+/// back from `nargo execute`.
+const STRING_ENCODING_FIXTURES: &[(StringHash, &str, &str)] = &[
+    (StringHash::Sha256, "http://example.org/name", "0x2a1db5fd13b30226833f09fce1108767d0d81777e025488a7b91a081789e0eb4"),
+    (StringHash::Sha256, "Alice", "0x12cc4bf6bf96fdd695ce054ffd2bfc05fbefb81c1373fec9496347037210c53a"),
+    (StringHash::Sha256, "http://www.w3.org/2001/XMLSchema#string", "0x20472c6afe2019333d738f0546f278bc0d8c3f85850161db98cbc5706e0dea24"),
+    (StringHash::Sha256, "", "0x255404053a67f57a944355ae62c055c9fc8587514f3b8b08d03b070452c4b0e2"),
+    (StringHash::Blake2s, "http://example.org/name", "0x021c321c6b761f4511464f667a95efe7dcdd7144a744244f5c1b77c51928926d"),
+    (StringHash::Blake2s, "Alice", "0x027f5521f2823db6987ccbee38ba7aeff9e858c19d45f32a55ee43de07e6aca2"),
+    (StringHash::Keccak256, "http://example.org/name", "0x1f0de091e7cd79ebea74b20fca381ce3dc6158e26932bf06f7a27af50fecd3a0"),
+];
+
+#[test]
+fn static_string_encodings_match_fixtures() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:name \"Alice\" }";
+    let in_circuit = transform_query(q).expect("transform succeeds");
+    assert!(in_circuit.sparql_nr.contains("consts::encode_string(\"Alice\")"), "{}", in_circuit.sparql_nr);
+    assert!(in_circuit.metadata["staticStringHash"].is_null());
+
+    for hash in [StringHash::Sha256, StringHash::Blake2s, StringHash::Keccak256] {
+        let opts = TransformOptions {
+            static_string_hash: Some(hash),
+            string_hash: hash,
+            ..TransformOptions::default()
+        };
+        let r = transform_with_opts(q, opts).expect("transform succeeds");
+        assert!(!r.sparql_nr.contains("encode_string"), "{}", r.sparql_nr);
+        assert_eq!(r.metadata["staticStringHash"], hash.name());
+        for (_, s, field) in STRING_ENCODING_FIXTURES.iter().filter(|(h, _, _)| *h == hash) {
+            assert!(r.sparql_nr.contains(field), "{} missing {} for {:?}:\n{}", hash.name(), field, s, r.sparql_nr);
+        }
+    }
+}
+
+/// Term encodings written by `src/scripts/encode-fixtures.ts` from
+/// `src/encode.ts`: the signer's Noir expression for each term must be
+/// the one the transform emits for the same constant, and -- when the
+/// fixtures were generated with `--values` -- its string encodings the
+/// ones the transform folds.
+#[test]
+fn term_encodings_match_encode_ts_fixtures() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/encode-ts.json");
+    let fixtures: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).expect("read encode-ts.json")).expect("parse encode-ts.json");
+    let terms = fixtures["terms"].as_array().expect("terms");
+    assert!(!terms.is_empty());
+    for fixture in terms {
+        let term = fixture["term"].as_str().expect("term");
+        // `encode.ts` calls the same `encode_string` through `utils`.
+        let expected = fixture["expression"].as_str().expect("expression").replace("utils::encode_string(", "consts::encode_string(");
+        let q = format!("SELECT ?s WHERE {{ ?s <http://example.org/p> {} }}", term);
+        let r = transform_query(&q).expect("transform succeeds");
+        assert!(r.sparql_nr.contains(&expected), "{} encodes differently from encode.ts:\n  expected {}\n{}", term, expected, r.sparql_nr);
+    }
+
+    let Some(strings) = fixtures["strings"].as_object() else { return };
+    let name = fixtures["stringHash"].as_str().expect("stringHash");
+    let hash = [StringHash::Sha256, StringHash::Blake2s, StringHash::Blake3, StringHash::Keccak256]
+        .into_iter()
+        .find(|h| h.name() == name)
+        .expect("known string hash");
+    for (s, field) in strings {
+        let q = format!("SELECT ?s WHERE {{ ?s <http://example.org/p> {} }}", serde_json::to_string(s).unwrap());
+        let opts = TransformOptions { static_string_hash: Some(hash), string_hash: hash, ..TransformOptions::default() };
+        let r = transform_with_opts(&q, opts).expect("transform succeeds");
+        let field = field.as_str().expect("encoding");
+        assert!(r.sparql_nr.contains(field), "{} missing {} for {:?}:\n{}", name, field, s, r.sparql_nr);
+    }
+}
+
+#[test]
+fn static_term_hashes_fold_only_under_poseidon() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?s WHERE { ?s ex:name \"Alice\" }";
+    let fold = |field_hash| {
+        let opts = TransformOptions {
+            static_string_hash: Some(StringHash::Sha256),
+            field_hash,
+            ..TransformOptions::default()
+        };
+        transform_with_opts(q, opts).expect("transform succeeds").sparql_nr
+    };
+    // Under Poseidon the literal's `hash2([2, hash4([..])])` is a single
+    // constant; the predicate IRI's `hash2([0, ..])` likewise.
+    let poseidon = fold(FieldHash::Poseidon);
+    assert!(!poseidon.contains("consts::hash4(["), "{}", poseidon);
+    assert!(!poseidon.contains("consts::hash2([0, 0x"), "{}", poseidon);
+    let pedersen = fold(FieldHash::Pedersen);
+    assert!(pedersen.contains("consts::hash4([0x"), "{}", pedersen);
+}
+
+#[test]
+fn disclosure_modes_commit_or_pseudonymise_projected_variables() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?person ?name ?id WHERE { ?person ex:name ?name ; ex:id ?id }";
+    let opts = TransformOptions {
+        disclosure: BTreeMap::from([
+            ("name".to_string(), DisclosureMode::Commit),
+            ("id".to_string(), DisclosureMode::Pseudonym),
+        ]),
+        ..TransformOptions::default()
+    };
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("pub(crate) struct Disclosed {"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("pub(crate) type Salts = [Field; 1];"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("assert(disclosed.person == variables.person);"), "{}", r.sparql_nr);
+    assert!(
+        r.sparql_nr.contains("assert(disclosed.name == consts::hash2([variables.name, salts[0]]));"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("assert(disclosed.id == consts::hash2([variables.id, verifier_context]));"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(r.main_nr.contains("    variables: Variables,\n    disclosed: pub Disclosed,"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("salts: Salts"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("verifier_context: pub Field"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("checkBinding(bgp, variables, disclosed, salts, verifier_context)"), "{}", r.main_nr);
+    assert_eq!(r.metadata["disclosure"][0]["mode"], "reveal");
+    assert_eq!(r.metadata["disclosure"][1]["mode"], "commit");
+    assert_eq!(r.metadata["disclosure"][1]["salt"], 0);
+    assert_eq!(r.metadata["disclosure"][2]["context"], "verifier_context");
+
+    // Modes follow the variable through normalisation's renaming.
+    let normalized = transform_with_opts(q, TransformOptions { normalize: true, ..opts })
+        .expect("transform succeeds");
+    assert_eq!(normalized.metadata["disclosure"][1]["variable"], "v1");
+    assert_eq!(normalized.metadata["disclosure"][1]["mode"], "commit");
+
+    // By default every variable is revealed through `variables`.
+    let plain = transform_query(q).expect("transform succeeds");
+    assert!(plain.main_nr.contains("variables: pub Variables"), "{}", plain.main_nr);
+    assert!(!plain.sparql_nr.contains("Disclosed"), "{}", plain.sparql_nr);
+
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?name WHERE { ?person ex:name ?name } ORDER BY ?name";
+    let opts = TransformOptions {
+        disclosure: BTreeMap::from([("name".to_string(), DisclosureMode::Commit)]),
+        ..TransformOptions::default()
+    };
+    let err = match transform_with_opts(q, opts.clone()) {
+        Err(e) => e,
+        Ok(_) => panic!("an ORDER BY key must stay revealed"),
+    };
+    assert!(err.contains("cannot be disclosed as a commitment"), "{}", err);
+    let q = "PREFIX ex: <http://example.org/>\nSELECT ?person WHERE { ?person ex:name ?name }";
+    let err = match transform_with_opts(q, opts) {
+        Err(e) => e,
+        Ok(_) => panic!("a disclosure mode needs a projected variable"),
+    };
+    assert!(err.contains("not projected"), "{}", err);
+}
+
+#[test]
+fn encrypted_disclosure_binds_ciphertexts_to_the_verifier_key() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?person ?name ?email WHERE { ?person ex:name ?name ; ex:email ?email }";
+    let opts = TransformOptions {
+        disclosure: BTreeMap::from([
+            ("name".to_string(), DisclosureMode::Encrypt),
+            ("email".to_string(), DisclosureMode::Encrypt),
+        ]),
+        ..TransformOptions::default()
+    };
+    let r = transform_with_opts(q, opts).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("use std::embedded_curve_ops::"), "{}", r.sparql_nr);
+    assert!(
+        r.sparql_nr.contains("let shared_secret = multi_scalar_mul([verifier_key], [ephemeral_secret]);"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("assert(ephemeral_key == fixed_base_scalar_mul(ephemeral_secret));"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("assert(disclosed.email == variables.email + consts::hash2([shared_secret.x, 1]));"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(!r.sparql_nr.contains("Salts"), "{}", r.sparql_nr);
+    assert!(r.main_nr.contains("verifier_key: pub std::embedded_curve_ops::EmbeddedCurvePoint"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("ephemeral_secret: std::embedded_curve_ops::EmbeddedCurveScalar"), "{}", r.main_nr);
+    assert!(
+        r.main_nr.contains("checkBinding(bgp, variables, disclosed, verifier_key, ephemeral_key, ephemeral_secret)"),
+        "{}",
+        r.main_nr
+    );
+    assert_eq!(r.metadata["disclosure"][1]["mode"], "encrypt");
+    assert_eq!(r.metadata["disclosure"][2]["slot"], 1);
+    assert_eq!(r.metadata["encryption"]["curve"], "grumpkin");
+    assert!(transform_query(q).expect("transform succeeds").metadata["encryption"].is_null());
+
+    // Ciphertexts are fresh per proof, so COUNT(DISTINCT) cannot read them.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT (COUNT(DISTINCT ?name) AS ?n) WHERE { ?person ex:name ?name }";
+    let opts = TransformOptions {
+        disclosure: BTreeMap::from([("name".to_string(), DisclosureMode::Encrypt)]),
+        ..TransformOptions::default()
+    };
+    let err = match transform_with_opts(q, opts) {
+        Err(e) => e,
+        Ok(_) => panic!("COUNT(DISTINCT) over a ciphertext must be rejected"),
+    };
+    assert!(err.contains("cannot be disclosed as a ciphertext"), "{}", err);
+}
+
+#[test]
+fn graphs_map_triple_patterns_to_separately_signed_datasets() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?person ?employer WHERE {\n\
+               GRAPH <http://gov.example/ids> { ?person ex:nationality ex:NL }\n\
+               GRAPH <http://corp.example/hr> { ?person ex:worksFor ?employer }\n\
+             }";
+    let opts = TransformOptions {
+        datasets: 2,
+        dataset_graphs: BTreeMap::from([("http://corp.example/hr".to_string(), 1)]),
+        ..TransformOptions::default()
+    };
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("pub(crate) global BGP_DATASETS: [u32; 2] = [0, 1];"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("pub(crate) global ROOT_KEYS: [u32; 2] = [0, 1];"), "{}", r.sparql_nr);
+    assert!(r.main_nr.contains("public_key: [PubKey; 2],"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("roots: [Root; 2],"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("verify_signature(public_key[sparql::ROOT_KEYS[i]], roots[i]);"), "{}", r.main_nr);
+    assert!(
+        r.main_nr.contains("verify_inclusion(bgp[i], roots[sparql::BGP_DATASETS[i]].value);"),
+        "{}",
+        r.main_nr
+    );
+    assert_eq!(r.metadata["datasetCount"], 2);
+    assert_eq!(r.metadata["bgpDatasets"], serde_json::json!([0, 1]));
+
+    // A single issuer keeps the one-key, one-root layout.
+    let single = transform_query(q).expect("transform succeeds");
+    assert!(single.main_nr.contains("public_key: [PubKey; 1],"), "{}", single.main_nr);
+    assert!(single.main_nr.contains("verify_inclusion(triple, roots[0].value);"), "{}", single.main_nr);
+    assert!(!single.sparql_nr.contains("BGP_DATASETS"), "{}", single.sparql_nr);
+
+    let bad = TransformOptions { datasets: 1, ..opts.clone() };
+    let err = match transform_with_opts(q, bad) {
+        Err(e) => e,
+        Ok(_) => panic!("a GRAPH mapped past the dataset count must be rejected"),
+    };
+    assert!(err.contains("only 1 dataset(s)"), "{}", err);
+
+    // A graph variable would be pinned to dataset 0 whatever it binds.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?person ?g WHERE { GRAPH ?g { ?person ex:worksFor ?employer } }";
+    let err = match transform_with_opts(q, opts.clone()) {
+        Err(e) => e,
+        Ok(_) => panic!("GRAPH ?g over several datasets must be rejected"),
+    };
+    assert!(err.contains("GRAPH ?g ranges over every named graph"), "{}", err);
+    transform_query(q).expect("GRAPH ?g over one dataset transforms");
+
+    // Absence can only be witnessed in dataset 0's sorted tree.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?person WHERE {\n\
+               ?person ex:nationality ex:NL\n\
+               FILTER NOT EXISTS { GRAPH <http://corp.example/hr> { ?person ex:worksFor ex:Acme } }\n\
+             }";
+    let err = match transform_with_opts(q, opts) {
+        Err(e) => e,
+        Ok(_) => panic!("non-membership in another issuer's dataset must be rejected"),
+    };
+    assert!(err.contains("only witnessed against dataset 0"), "{}", err);
+}
+
+#[test]
+fn from_clauses_scope_the_default_and_named_graphs() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?person ?name FROM <http://example.org/g1> WHERE { ?person ex:name ?name }";
+    let r = transform_query(q).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("bgp[0].terms[3]"), "{}", r.sparql_nr);
+    assert_eq!(r.metadata["datasetClause"]["from"], serde_json::json!(["http://example.org/g1"]));
+    assert!(transform_query("SELECT ?s WHERE { ?s ?p ?o }").unwrap().metadata["datasetClause"].is_null());
+
+    // Several FROM graphs merge: each triple may sit in any of them.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?person ?name FROM <http://example.org/g1> FROM <http://example.org/g2>\n\
+             WHERE { ?person ex:name ?name ; ex:knows ?friend }";
+    let merged = transform_query(q).expect("transform succeeds");
+    assert_eq!(merged.sparql_nr.matches("bgp[0].terms[3].hash ==").count(), 2, "{}", merged.sparql_nr);
+    assert_eq!(merged.sparql_nr.matches("bgp[1].terms[3].hash ==").count(), 2, "{}", merged.sparql_nr);
+
+    // GRAPH ?g ranges over the FROM NAMED set only.
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?g ?name FROM NAMED <http://example.org/a> FROM NAMED <http://example.org/b>\n\
+             WHERE { GRAPH ?g { ?person ex:name ?name } }";
+    let named = transform_query(q).expect("transform succeeds");
+    assert!(
+        named.sparql_nr.contains(
+            "assert((variables.g == consts::hash2([0, consts::encode_string(\"http://example.org/a\")])) | \
+             (variables.g == consts::hash2([0, consts::encode_string(\"http://example.org/b\")])));"
+        ),
+        "{}",
+        named.sparql_nr
+    );
+    assert_eq!(named.metadata["datasetClause"]["fromNamed"][1], "http://example.org/b");
+
+    for (q, expected) in [
+        (
+            "SELECT ?s FROM NAMED <http://example.org/a> WHERE { ?s ?p ?o }",
+            "default graph is empty",
+        ),
+        (
+            "SELECT ?s FROM <http://example.org/a> WHERE { GRAPH <http://example.org/b> { ?s ?p ?o } }",
+            "not among the query's FROM NAMED graphs",
+        ),
+    ] {
+        let err = match transform_query(q) {
+            Err(e) => e,
+            Ok(_) => panic!("{} can never match", q),
+        };
+        assert!(err.contains(expected), "{}", err);
+    }
+}
+
+#[test]
+fn issuer_registry_hides_which_authorised_key_signed() {
+    let q = "PREFIX ex: <http://example.org/>\nSELECT ?name WHERE { ?person ex:name ?name }";
+    let opts = TransformOptions {
+        issuer_registry_depth: Some(16),
+        ..TransformOptions::default()
+    };
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.main_nr.contains("public_key: [IssuerKey<16>; 1],\n    issuer_registry: pub Field,"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("verify_issuer_key(key, issuer_registry);"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("verify_signature(public_key[0].key, roots[i]);"), "{}", r.main_nr);
+    assert!(!r.main_nr.contains("PubKey"), "{}", r.main_nr);
+    assert_eq!(r.metadata["issuerRegistry"]["depth"], 16);
+
+    // Each issuer of a multi-dataset proof is checked against the registry.
+    let multi = transform_with_opts(q, TransformOptions { datasets: 2, ..opts.clone() })
+        .expect("transform succeeds");
+    assert!(multi.main_nr.contains("public_key: [IssuerKey<16>; 2],"), "{}", multi.main_nr);
+
+    let plain = transform_query(q).expect("transform succeeds");
+    assert!(plain.main_nr.contains("public_key: [PubKey; 1],"), "{}", plain.main_nr);
+    assert!(plain.metadata["issuerRegistry"].is_null());
+
+    let err = match transform_with_opts(q, TransformOptions { skip_signing: true, ..opts }) {
+        Err(e) => e,
+        Ok(_) => panic!("an issuer registry needs signatures"),
+    };
+    assert!(err.contains("skip_signing"), "{}", err);
+}
+
+#[test]
+fn context_binding_and_nullifiers_tie_proofs_to_a_verifier() {
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?age WHERE { ?holder ex:age ?age ; ex:holderSecret ?secret }";
+    let bound = transform_with_opts(
+        q,
+        TransformOptions {
+            bind_context: true,
+            ..TransformOptions::default()
+        },
+    )
+    .expect("transform succeeds");
+    assert!(bound.main_nr.contains("    context: pub Field\n) {"), "{}", bound.main_nr);
+    assert!(bound.main_nr.contains("let _ = context;"), "{}", bound.main_nr);
+    assert_eq!(bound.metadata["context"]["input"], "context");
+    assert!(bound.metadata["nullifier"].is_null());
+
+    let opts = TransformOptions {
+        nullifier_secret: Some("secret".to_string()),
+        ..TransformOptions::default()
+    };
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(
+        r.sparql_nr.contains("assert(nullifier == consts::hash2([consts::hash2([bgp[1].terms[2].hash, context]), scope]));"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(r.main_nr.contains("scope: pub Field,\n    nullifier: pub Field"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("checkBinding(bgp, variables, context, scope, nullifier)"), "{}", r.main_nr);
+    assert_eq!(r.metadata["nullifier"]["secret"], "secret");
+    assert_eq!(r.metadata["context"]["input"], "context");
+
+    // The nullifier arguments come last in both `main` and `checkBinding`.
+    let timed = transform_with_opts(
+        "PREFIX ex: <http://example.org/>\n\
+         SELECT ?age WHERE { ?holder ex:age ?age ; ex:holderSecret ?secret ; ex:until ?t FILTER(?t > NOW()) }",
+        opts.clone(),
+    )
+    .expect("transform succeeds");
+    assert!(timed.main_nr.contains(", now, context, scope, nullifier)"), "{}", timed.main_nr);
+    assert!(
+        timed.sparql_nr.contains("now: i64, context: Field, scope: Field, nullifier: Field)"),
+        "{}",
+        timed.sparql_nr
+    );
+
+    let plain = transform_query(q).expect("transform succeeds");
+    assert!(!plain.main_nr.contains("context"), "{}", plain.main_nr);
+
+    let q = "PREFIX ex: <http://example.org/>\nSELECT ?secret WHERE { ?holder ex:holderSecret ?secret }";
+    let err = match transform_with_opts(q, opts.clone()) {
+        Err(e) => e,
+        Ok(_) => panic!("a projected nullifier secret must be rejected"),
+    };
+    assert!(err.contains("cannot be projected"), "{}", err);
+    let q = "PREFIX ex: <http://example.org/>\n\
+             SELECT ?age WHERE { ?holder ex:age ?age OPTIONAL { ?holder ex:holderSecret ?secret . ?secret ex:issuedBy ?issuer } }";
+    let err = match transform_with_opts(q, opts) {
+        Err(e) => e,
+        Ok(_) => panic!("an optional nullifier secret must be rejected"),
+    };
+    assert!(err.contains("outside OPTIONAL"), "{}", err);
+}
+
+#[test]
+fn root_validity_windows_and_revocation_are_checked_in_main() {
+    let q = "PREFIX ex: <http://example.org/>\nSELECT ?name WHERE { ?person ex:name ?name }";
+    let opts = TransformOptions {
+        root_validity: true,
+        ..TransformOptions::default()
+    };
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.main_nr.contains("roots: [TimedRoot; 1],"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("now: pub i64"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("verify_timed_signature(public_key[0], roots[i], now);"), "{}", r.main_nr);
+    assert!(!r.main_nr.contains("revocation_root"), "{}", r.main_nr);
+    assert_eq!(r.metadata["rootValidity"]["timeInput"], "now");
+    assert!(r.metadata["rootValidity"]["revocation"].is_null());
+
+    let revoked = transform_with_opts(
+        q,
+        TransformOptions {
+            revocation_check: true,
+            ..opts.clone()
+        },
+    )
+    .expect("transform succeeds");
+    assert!(
+        revoked
+            .main_nr
+            .contains("revocations: [RevocationBracket; 1],\n    revocation_root: pub Field,"),
+        "{}",
+        revoked.main_nr
+    );
+    assert!(
+        revoked
+            .main_nr
+            .contains("verify_not_revoked(roots[i], revocations[i], revocation_root);"),
+        "{}",
+        revoked.main_nr
+    );
+    assert_eq!(revoked.metadata["rootValidity"]["revocation"]["input"], "revocation_root");
+
+    // `NOW()` and the validity window read the same public input.
+    let now_q = "PREFIX ex: <http://example.org/>\n\
+                 SELECT ?name WHERE { ?person ex:name ?name ; ex:born ?b FILTER(?b < NOW()) }";
+    let shared = transform_with_opts(now_q, opts.clone()).expect("transform succeeds");
+    assert_eq!(shared.main_nr.matches("now: pub i64").count(), 1, "{}", shared.main_nr);
+
+    let plain = transform_query(q).expect("transform succeeds");
+    assert!(plain.main_nr.contains("roots: [Root; 1],"), "{}", plain.main_nr);
+    assert!(plain.metadata["rootValidity"].is_null());
+
+    let err = match transform_with_opts(
+        q,
+        TransformOptions {
+            revocation_check: true,
+            ..TransformOptions::default()
+        },
+    ) {
+        Err(e) => e,
+        Ok(_) => panic!("revocation needs validity windows"),
+    };
+    assert!(err.contains("root_validity"), "{}", err);
+    let err = match transform_with_opts(q, TransformOptions { skip_signing: true, ..opts }) {
+        Err(e) => e,
+        Ok(_) => panic!("validity windows need signatures"),
+    };
+    assert!(err.contains("skip_signing"), "{}", err);
+}
+
+#[test]
+fn consts_package_follows_the_configured_hashes_and_signature() {
+    let q = "PREFIX ex: <http://example.org/>\nSELECT ?name WHERE { ?person ex:name ?name }";
+    let plain = transform_query(q).expect("transform succeeds");
+    assert!(plain.consts.lib_nr.contains("pub global MERKLE_DEPTH: u32 = 11;"), "{}", plain.consts.lib_nr);
+    assert!(plain.consts.lib_nr.contains("std::hash::pedersen_hash(input)"), "{}", plain.consts.lib_nr);
+    assert!(plain.consts.lib_nr.contains("dep::sha256::sha256_var(input, length as u64)"));
+    assert!(!plain.consts.lib_nr.contains("{{"), "{}", plain.consts.lib_nr);
+    assert!(plain.consts.nargo_toml.contains("signature = { path = \"../signatures/babyjubjubOpt\" }"));
+    assert!(plain.consts.nargo_toml.contains("sha256 = "));
+    assert!(!plain.consts.nargo_toml.contains("mimc"));
+    assert_eq!(plain.metadata["config"]["signature"], "babyjubjubOpt");
+    assert_eq!(plain.metadata["config"]["constsDigest"], plain.consts.digest().as_str());
+    assert!(
+        plain.nargo_toml.contains("# field_hash = \"pedersen\", string_hash = \"sha256\", signature = \"babyjubjubOpt\", merkle_depth = 11\n"),
+        "{}",
+        plain.nargo_toml
+    );
+    assert!(plain.nargo_toml.contains(&format!("# consts digest: {}\n", plain.consts.digest())), "{}", plain.nargo_toml);
+
+    let opts = TransformOptions {
+        field_hash: FieldHash::Poseidon2,
+        string_hash: StringHash::Blake3,
+        signature: SignatureScheme::Schnorr,
+        merkle_depth: 15,
+        ..TransformOptions::default()
+    };
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.consts.lib_nr.contains("pub global MERKLE_DEPTH: u32 = 15;"), "{}", r.consts.lib_nr);
+    assert!(r.consts.lib_nr.contains("dep::hashes::p2hash4(input)"), "{}", r.consts.lib_nr);
+    assert!(r.consts.lib_nr.contains("std::hash::blake3(input)"), "{}", r.consts.lib_nr);
+    assert!(r.consts.nargo_toml.contains("signature = { path = \"../signatures/schnorr\" }"));
+    assert!(r.consts.nargo_toml.contains("hashes = { path = \"../hashes\" }"));
+    assert!(!r.consts.nargo_toml.contains("sha256"), "{}", r.consts.nargo_toml);
+    assert_eq!(r.metadata["config"]["fieldHash"], "poseidon2");
+    assert_eq!(r.metadata["config"]["merkleDepth"], 15);
+    assert_ne!(r.metadata["config"]["constsDigest"], plain.metadata["config"]["constsDigest"]);
+    assert!(
+        r.nargo_toml.contains("# field_hash = \"poseidon2\", string_hash = \"blake3\", signature = \"schnorr\", merkle_depth = 15\n"),
+        "{}",
+        r.nargo_toml
+    );
+    assert!(r.nargo_toml.contains(&format!("# consts digest: {}\n", r.consts.digest())), "{}", r.nargo_toml);
+    assert_ne!(r.fingerprint, plain.fingerprint);
+
+    let err = match transform_with_opts(
+        q,
+        TransformOptions {
+            static_string_hash: Some(StringHash::Sha256),
+            ..opts.clone()
+        },
+    ) {
+        Err(e) => e,
+        Ok(_) => panic!("folded constants must match the consts string hash"),
+    };
+    assert!(err.contains("string_hash"), "{}", err);
+    // Only the depths src/config.ts builds: odd, 9 through 31.
+    for merkle_depth in [0, 1, 8, 12, 33] {
+        let err = match transform_with_opts(q, TransformOptions { merkle_depth, ..opts.clone() }) {
+            Err(e) => e,
+            Ok(_) => panic!("merkle_depth {} is outside src/config.ts merkleDepths", merkle_depth),
+        };
+        assert!(err.contains("merkle_depth") && err.contains("merkleDepths"), "{}", err);
+    }
+    for merkle_depth in [9, 31] {
+        transform_with_opts(q, TransformOptions { merkle_depth, ..opts.clone() }).expect("a config.ts depth transforms");
+    }
+    for string_hash in [StringHash::Sha384, StringHash::Sha512] {
+        let err = match transform_with_opts(q, TransformOptions { string_hash, ..TransformOptions::default() }) {
+            Err(e) => e,
+            Ok(_) => panic!("{} digests do not fit a Field", string_hash.name()),
+        };
+        assert!(err.contains(string_hash.name()) && err.contains("stringHashes"), "{}", err);
+    }
+    let err = match transform_with_opts(
+        q,
+        TransformOptions {
+            signature: SignatureScheme::BabyJubJub,
+            ..TransformOptions::default()
+        },
+    ) {
+        Err(e) => e,
+        Ok(_) => panic!("babyjubjub has no signer"),
+    };
+    assert!(err.contains("babyjubjub") && err.contains("BabyJubJubOpt"), "{}", err);
+}
+
+#[test]
+fn answer_bit_proves_a_query_has_no_solutions() {
+    let opts = TransformOptions {
+        answer_bit: true,
+        ..TransformOptions::default()
+    };
+    let q = "PREFIX ex: <http://example.org/>\nASK { ex:alice ex:criminalRecord ex:entry1 }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.main_nr.contains("    answer: pub bool\n) {"), "{}", r.main_nr);
+    assert!(r.main_nr.contains(", answer)"), "{}", r.main_nr);
+    assert!(r.sparql_nr.contains("answer: bool)"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("assert((answer & ("), "{}", r.sparql_nr);
+    assert!(
+        r.sparql_nr.contains("((!answer) & (utils::verify_non_membership_no_inclusion_check(bgp[1], bgp[2], "),
+        "{}",
+        r.sparql_nr
+    );
+    assert_eq!(r.metadata["answer"]["falseWitness"], "leaf");
+
+    // "No criminal record entry at all": the object is free, so a false
+    // answer brackets the (s, p, g) prefix.
+    let q = "PREFIX ex: <http://example.org/>\nASK { ex:alice ex:criminalRecord ?entry }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("utils::prefix3::hash3_sp_g("), "{}", r.sparql_nr);
+    assert!(!r.sparql_nr.contains("bgp[0].terms[2]"), "{}", r.sparql_nr);
+    assert_eq!(r.metadata["answer"]["falseWitness"], "prefix3");
+
+    // SELECT: a true answer pins the projected object.
+    let q = "PREFIX ex: <http://example.org/>\nSELECT ?entry WHERE { ex:alice ex:criminalRecord ?entry }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("(variables.entry == bgp[0].terms[2].hash)"), "{}", r.sparql_nr);
+
+    let plain = transform_query(q).expect("transform succeeds");
+    assert!(!plain.main_nr.contains("answer"), "{}", plain.main_nr);
+    assert!(plain.metadata["answer"].is_null());
+
+    for q in [
+        "PREFIX ex: <http://example.org/>\nASK { ?person ex:criminalRecord ex:entry1 }",
+        "PREFIX ex: <http://example.org/>\nASK { ex:alice ex:criminalRecord ?e . ?e ex:court ?c }",
+    ] {
+        let err = match transform_with_opts(q, opts.clone()) {
+            Err(e) => e,
+            Ok(_) => panic!("emptiness of {} cannot be witnessed", q),
+        };
+        assert!(err.contains("answer_bit"), "{}", err);
+    }
+}
+
+#[test]
+fn completeness_discloses_the_whole_prefix_run() {
+    let opts = TransformOptions {
+        completeness: Some(4),
+        ..TransformOptions::default()
+    };
+    let q = "PREFIX ex: <http://example.org/>\nSELECT ?degree WHERE { ex:alice ex:degree ?degree }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("pub(crate) type BGP = [Triple; 0];"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("pub(crate) degree: [Field; 4],"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("pub(crate) type CompleteRun = Run<4>;"), "{}", r.sparql_nr);
+    assert!(
+        r.sparql_nr.contains("run: CompleteRun, run_len: u32, boundary_cases_run: [Field; 2])"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("utils::run::verify_run_bounds(run, run_len, boundary_cases_run, utils::prefix3::hash3_sp_g("),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(r.sparql_nr.contains("assert(variables.degree[i] == run.rows[i].terms[2]);"), "{}", r.sparql_nr);
+    assert!(r.main_nr.contains("roots: [Root; 2],"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("run_len: pub u32,"), "{}", r.main_nr);
+    assert!(
+        r.main_nr.contains("verify_run_inclusion(run, run_len, boundary_cases_run, roots[1].value);"),
+        "{}",
+        r.main_nr
+    );
+    assert!(r.main_nr.contains(", run, run_len, boundary_cases_run)"), "{}", r.main_nr);
+    assert!(
+        r.sparql_nr.contains("pub(crate) global ROOT_TAGS: [Field; 2] = [0, dep::utils::run::RUN_ROOT_TAG];"),
+        "{}",
+        r.sparql_nr
+    );
+    assert_eq!(r.metadata["completeness"]["variable"], "degree");
+    assert_eq!(r.metadata["completeness"]["capacity"], 4);
+    assert_eq!(r.metadata["completeness"]["root"], 1);
+
+    // Aggregates are computed over the complete run.
+    let q = "PREFIX ex: <http://example.org/>\nSELECT (COUNT(?degree) AS ?n) WHERE { GRAPH ex:registry { ex:alice ex:degree ?degree } }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("pub(crate) degree: [Field; 4],"), "{}", r.sparql_nr);
+    assert_eq!(r.metadata["aggregates"][0]["kind"], "count");
+
+    // Under validity windows and revocation the bound message is the
+    // one signed, and checked against the revocation list.
+    let timed = TransformOptions {
+        root_validity: true,
+        revocation_check: true,
+        ..opts.clone()
+    };
+    let r = transform_with_opts(q, timed).expect("transform succeeds");
+    assert!(
+        r.main_nr.contains("let root = bind_timed_root(roots[i], sparql::ROOT_TAGS[i], roots[0].value);"),
+        "{}",
+        r.main_nr
+    );
+    assert!(r.main_nr.contains("verify_not_revoked(root, revocations[i], revocation_root);"), "{}", r.main_nr);
+
+    let plain = transform_query(q).expect("transform succeeds");
+    assert!(!plain.main_nr.contains("run"), "{}", plain.main_nr);
+    assert!(!plain.main_nr.contains("bind_root"), "{}", plain.main_nr);
+    assert!(plain.metadata["completeness"].is_null());
+
+    for q in [
+        "PREFIX ex: <http://example.org/>\nSELECT ?s ?o WHERE { ?s ex:degree ?o }",
+        "PREFIX ex: <http://example.org/>\nSELECT ?o WHERE { ex:alice ex:degree ?o . ?o ex:level ?l }",
+        "PREFIX ex: <http://example.org/>\nSELECT ?o WHERE { ex:alice ex:degree ?o FILTER(?o != ex:bsc) }",
+        "PREFIX ex: <http://example.org/>\nSELECT ?o WHERE { GRAPH ?g { ex:alice ex:degree ?o } }",
+        "PREFIX ex: <http://example.org/>\nASK { ex:alice ex:degree ?o }",
+    ] {
+        let err = match transform_with_opts(q, opts.clone()) {
+            Err(e) => e,
+            Ok(_) => panic!("{} is not a single prefix run", q),
+        };
+        assert!(err.contains("completeness"), "{}", err);
+    }
+    let too_many = TransformOptions {
+        completeness: Some(1 << 10),
+        ..TransformOptions::default()
+    };
+    assert!(transform_with_opts("SELECT ?o WHERE { <http://example.org/a> <http://example.org/p> ?o }", too_many).is_err());
+}
+
+#[test]
+fn extremum_proves_the_max_over_every_match() {
+    let opts = TransformOptions {
+        extremum: true,
+        ..TransformOptions::default()
+    };
+    let q = "PREFIX ex: <http://example.org/>\nSELECT (MAX(?year) AS ?latest) WHERE { ex:alice ex:degreeYear ?year }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("pub(crate) type BGP = [Triple; 0];"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("pub(crate) year: Field,"), "{}", r.sparql_nr);
+    assert!(
+        r.sparql_nr.contains("extremum: Extremum, boundary_case_extremum: Field)"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.sparql_nr.contains("utils::extremum::verify_extremum_bounds(extremum, true, boundary_case_extremum, utils::prefix3::hash3_sp_g("),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(r.sparql_nr.contains("assert(variables.year == extremum.row.terms[2]);"), "{}", r.sparql_nr);
+    assert!(r.main_nr.contains("roots: [Root; 2],"), "{}", r.main_nr);
+    // The value-sorted root is signed bound to the dataset root.
+    assert!(
+        r.sparql_nr.contains("pub(crate) global ROOT_TAGS: [Field; 2] = [0, dep::utils::extremum::EXTREMUM_ROOT_TAG];"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(
+        r.main_nr.contains(
+            "let root = bind_root(roots[i], sparql::ROOT_TAGS[i], roots[0].value);\n        verify_signature(public_key[0], root);"
+        ),
+        "{}",
+        r.main_nr
+    );
+    assert!(r.main_nr.contains("boundary_case_extremum: pub Field"), "{}", r.main_nr);
+    assert!(
+        r.main_nr.contains("verify_extremum_inclusion(extremum, boundary_case_extremum, roots[1].value);"),
+        "{}",
+        r.main_nr
+    );
+    assert!(r.main_nr.contains(", extremum, boundary_case_extremum)"), "{}", r.main_nr);
+    assert_eq!(r.metadata["extremum"]["variable"], "year");
+    assert_eq!(r.metadata["extremum"]["kind"], "max");
+    assert_eq!(r.metadata["extremum"]["root"], 1);
+    assert_eq!(r.metadata["aggregates"][0]["kind"], "max");
+
+    let q = "PREFIX ex: <http://example.org/>\nSELECT (MIN(?start) AS ?first) WHERE { GRAPH ex:hr { ex:alice ex:employedSince ?start } }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("verify_extremum_bounds(extremum, false,"), "{}", r.sparql_nr);
+    assert_eq!(r.metadata["extremum"]["kind"], "min");
+    assert_eq!(r.metadata["extremum"]["boundaryCaseDispatch"]["0"], "lower");
+
+    let plain = transform_query(q).expect("transform succeeds");
+    assert!(!plain.main_nr.contains("extremum"), "{}", plain.main_nr);
+    assert!(plain.metadata["extremum"].is_null());
+
+    for q in [
+        "PREFIX ex: <http://example.org/>\nSELECT (MAX(?o) AS ?m) WHERE { ?s ex:degreeYear ?o }",
+        "PREFIX ex: <http://example.org/>\nSELECT (COUNT(?o) AS ?n) WHERE { ex:alice ex:degreeYear ?o }",
+        "PREFIX ex: <http://example.org/>\nSELECT ?o WHERE { ex:alice ex:degreeYear ?o }",
+        "PREFIX ex: <http://example.org/>\nSELECT (MAX(?o) AS ?m) (MIN(?o) AS ?n) WHERE { ex:alice ex:degreeYear ?o }",
+        "PREFIX ex: <http://example.org/>\nSELECT (MAX(?o) AS ?m) WHERE { ex:alice ex:degreeYear ?o FILTER(?o > 2000) }",
+        "PREFIX ex: <http://example.org/>\nASK { ex:alice ex:degreeYear ?o }",
+    ] {
+        let err = match transform_with_opts(q, opts.clone()) {
+            Err(e) => e,
+            Ok(_) => panic!("{} is not a single-prefix MIN / MAX", q),
+        };
+        assert!(err.contains("extremum"), "{}", err);
+    }
+    let with_completeness = TransformOptions {
+        completeness: Some(4),
+        ..opts
+    };
+    assert!(transform_with_opts("SELECT (MAX(?o) AS ?m) WHERE { <http://example.org/a> <http://example.org/p> ?o }", with_completeness).is_err());
+}
+
+#[test]
+fn in_circuit_count_keeps_the_rows_private() {
+    let opts = TransformOptions {
+        aggregate_rows: Some(3),
+        ..TransformOptions::default()
+    };
+    let q = "PREFIX ex: <http://example.org/>\nSELECT (COUNT(?friend) AS ?n) WHERE { ex:alice ex:knows ?friend . ?friend ex:age ?age }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("pub(crate) type RowBGP = [Triple; 2];"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("pub(crate) type BGP = [Triple; 6];"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("pub(crate) rows: [Row; 3],"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("pub(crate) fn checkRow(bgp: RowBGP, variables: Row) -> Field {"), "{}", r.sparql_nr);
+    assert!(
+        r.sparql_nr.contains("pub(crate) fn checkBinding(bgp: BGP, variables: Variables, aggregate: i64) {"),
+        "{}",
+        r.sparql_nr
+    );
+    assert!(r.sparql_nr.contains("utils::aggregate::solution_key([bgp[1].terms[2].hash, variables.friend])"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("utils::aggregate::assert_strictly_ascending(keys, variables.row_count);"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("assert(variables.row_count as i64 == aggregate);"), "{}", r.sparql_nr);
+    assert!(r.main_nr.contains("variables: Variables,"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("aggregate: pub i64"), "{}", r.main_nr);
+    assert!(r.main_nr.contains("checkBinding(bgp, variables, aggregate)"), "{}", r.main_nr);
+    let agg = &r.metadata["inCircuitAggregate"];
+    assert_eq!(agg["kind"], "count");
+    assert_eq!(agg["rows"], 3);
+    assert_eq!(agg["input"], "aggregate");
+    assert_eq!(agg["bound"], "lowerBound");
+    assert_eq!(agg["claim"], "lower bound: the query has at least `aggregate` distinct solutions");
+    assert_eq!(agg["keyVariables"], serde_json::json!(["age", "friend"]));
+    assert_eq!(agg["bgpPerRow"], 2);
+    assert_eq!(r.metadata["aggregates"][0]["evaluation"], "in-circuit");
+    assert_eq!(r.metadata["aggregates"][0]["bound"], "lowerBound");
+
+    let threshold = TransformOptions {
+        aggregate_threshold: true,
+        ..opts.clone()
+    };
+    let r = transform_with_opts(q, threshold).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("assert(variables.row_count as i64 >= threshold);"), "{}", r.sparql_nr);
+    assert!(r.main_nr.contains("threshold: pub i64"), "{}", r.main_nr);
+    assert_eq!(r.metadata["inCircuitAggregate"]["input"], "threshold");
+
+    let q = "PREFIX ex: <http://example.org/>\nSELECT (COUNT(DISTINCT ?friend) AS ?n) WHERE { ex:alice ex:knows ?friend . ?friend ex:age ?age }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("utils::aggregate::solution_key([variables.friend])"), "{}", r.sparql_nr);
+    assert_eq!(r.metadata["inCircuitAggregate"]["keyVariables"], serde_json::json!(["friend"]));
+
+    let q = "PREFIX ex: <http://example.org/>\nSELECT (SUM(?amount) AS ?total) WHERE { ex:alice ex:paid ?payment . ?payment ex:amount ?amount }";
+    let r = transform_with_opts(q, opts.clone()).expect("transform succeeds");
+    assert!(r.sparql_nr.contains("pub(crate) values: [RowValue; 3],"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("checkRow(bgp: RowBGP, variables: Row, value: RowValue) -> Field {"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("utils::extremum::valued_literal(value.lexical, value.value, value.language,"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("total += variables.values[r].value;"), "{}", r.sparql_nr);
+    assert!(r.sparql_nr.contains("assert(total == aggregate);"), "{}", r.sparql_nr);
+    assert_eq!(r.metadata["inCircuitAggregate"]["kind"], "sum");
+    assert!(r.metadata["inCircuitAggregate"]["claim"].as_str().unwrap().starts_with("lower bound: the query's SUM"));
+
+    // SUM rows open an xsd:integer literal; a source that never is one
+    // is rejected up front.
+    for q in [
+        "PREFIX ex: <http://example.org/>\nSELECT (SUM(?payment) AS ?total) WHERE { ?payment ex:amount ?amount }",
+        "PREFIX ex: <http://example.org/>\nSELECT (SUM(?p) AS ?total) WHERE { ex:alice ?p ?amount }",
+        "PREFIX ex: <http://example.org/>\nSELECT (SUM(?x) AS ?total) WHERE { ex:alice ex:paid ?amount BIND(\"1.5\"^^<http://www.w3.org/2001/XMLSchema#decimal> AS ?x) }",
+        "PREFIX ex: <http://example.org/>\nSELECT (SUM(?x) AS ?total) WHERE { ex:alice ex:paid ?amount BIND(ENCODE_FOR_URI(?amount) AS ?x) }",
+    ] {
+        let err = match transform_with_opts(q, opts.clone()) {
+            Err(e) => e,
+            Ok(r) => panic!("{} does not sum xsd:integer values:\n{}", q, r.sparql_nr),
+        };
+        assert!(err.contains("sums xsd:integer values"), "{}", err);
+    }
+
+    let plain = transform_query(q).expect("transform succeeds");
+    assert!(plain.main_nr.contains("variables: pub Variables"), "{}", plain.main_nr);
+    assert!(plain.metadata["inCircuitAggregate"].is_null());
+    assert_eq!(plain.metadata["aggregates"][0]["evaluation"], "verifier");
+
+    for q in [
+        "PREFIX ex: <http://example.org/>\nSELECT (MIN(?age) AS ?m) WHERE { ?s ex:age ?age }",
+        "PREFIX ex: <http://example.org/>\nSELECT (AVG(?age) AS ?m) WHERE { ?s ex:age ?age }",
+        "PREFIX ex: <http://example.org/>\nSELECT ?age WHERE { ?s ex:age ?age }",
+        "PREFIX ex: <http://example.org/>\nSELECT (COUNT(?s) AS ?n) WHERE { ?s ex:age ?age OPTIONAL { ?s ex:name ?name } }",
+        "PREFIX ex: <http://example.org/>\nSELECT (COUNT(?s) AS ?n) WHERE { ?s ex:age ?age FILTER NOT EXISTS { ?s ex:banned true } }",
+        "PREFIX ex: <http://example.org/>\nSELECT (COUNT(?s) AS ?n) WHERE { { ?s ex:age ?age } UNION { ?s ex:years ?age } }",
+        "PREFIX ex: <http://example.org/>\nASK { ?s ex:age ?age }",
+    ] {
+        let err = match transform_with_opts(q, opts.clone()) {
+            Err(e) => e,
+            Ok(_) => panic!("{} is not an in-circuit COUNT / SUM", q),
+        };
+        assert!(err.contains("aggregate_rows"), "{}", err);
+    }
+    let q = "PREFIX ex: <http://example.org/>\nSELECT (COUNT(?s) AS ?n) WHERE { ?s ex:age ?age }";
+    for bad in [
+        TransformOptions { aggregate_rows: Some(0), ..TransformOptions::default() },
+        TransformOptions { aggregate_threshold: true, ..TransformOptions::default() },
+        TransformOptions { skip_signing: true, ..opts.clone() },
+        TransformOptions { extremum: true, ..opts.clone() },
+    ] {
+        assert!(transform_with_opts(q, bad).is_err());
+    }
+}
+
 #[test]
 fn corpus_byte_identical() {
     let update = env::var("UPDATE_SNAPSHOTS").map(|v| v == "1").unwrap_or(false);
@@ -2283,3 +3836,4 @@ fn corpus_byte_identical() {
         );
     }
 }
+